@@ -0,0 +1,343 @@
+use crate::{mipmap_count, mipmap_size, ColorSpace, Error, Format};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 4 + 124;
+const DX10_HEADER_SIZE: usize = HEADER_SIZE + 20;
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDSD_DEPTH: u32 = 0x800000;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// Formats stored with bit masks in the legacy header, as (format, pixel format flags, bits per
+/// pixel, red/luminance, green, blue, and alpha masks).
+const MASK_FORMATS: &[(Format<'static>, u32, u32, [u32; 4])] = &[
+    (Format::R5G6B5, DDPF_RGB, 16, [0xF800, 0x07E0, 0x001F, 0]),
+    (Format::A1R5G5B5, DDPF_RGB | DDPF_ALPHAPIXELS, 16, [0x7C00, 0x03E0, 0x001F, 0x8000]),
+    (Format::A4R4G4B4, DDPF_RGB | DDPF_ALPHAPIXELS, 16, [0x0F00, 0x00F0, 0x000F, 0xF000]),
+    (Format::X8R8G8B8, DDPF_RGB, 32, [0xFF0000, 0xFF00, 0xFF, 0]),
+    (Format::A8R8G8B8, DDPF_RGB | DDPF_ALPHAPIXELS, 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000]),
+    (Format::A8B8G8R8, DDPF_RGB | DDPF_ALPHAPIXELS, 32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000]),
+    (Format::A8, DDPF_ALPHA, 8, [0, 0, 0, 0xFF]),
+    (Format::Y8, DDPF_LUMINANCE, 8, [0xFF, 0, 0, 0]),
+    (Format::A8Y8, DDPF_LUMINANCE | DDPF_ALPHAPIXELS, 16, [0xFF, 0, 0, 0xFF00]),
+    (Format::A4Y4, DDPF_LUMINANCE | DDPF_ALPHAPIXELS, 8, [0x0F, 0, 0, 0xF0])
+];
+
+/// Header of a DDS (DirectDraw Surface) file.
+///
+/// The legacy header is used when it can describe the format, and the DX10 header is used
+/// otherwise, such as for [`BC7`](Format::BC7) or [`ColorSpace::Srgb`].
+///
+/// The data after the header has each face (six for cubemaps, otherwise one), and each face has
+/// every mipmap from largest to smallest, sized by [`Format::required_bytes`]. Each mipmap of a
+/// volume texture has all of its depth slices, and the depth is halved along with the width and
+/// height. With a full mipmap chain, each face of a 2D texture is laid out the same as
+/// [`Format::encode_mipmaps`].
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::{DdsHeader, Format};
+///
+/// let header = DdsHeader { mipmap_count: 3, ..DdsHeader::new(Format::DXT1, 4, 4) };
+/// assert_eq!(Ok(8 * 3), header.data_size());
+///
+/// let mut file = [0u8; 128 + 8 * 3];
+/// header.write(&mut file[..header.header_size()]).unwrap();
+///
+/// let (read, data) = DdsHeader::read(&file).unwrap();
+/// assert_eq!(header, read);
+/// assert_eq!(8 * 3, data.len());
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DdsHeader<'a> {
+    /// Format of the data.
+    pub format: Format<'a>,
+
+    /// Color space of the data, which only the DX10 header can store as sRGB.
+    pub color_space: ColorSpace,
+
+    /// Width of the base image in pixels.
+    pub width: usize,
+
+    /// Height of the base image in pixels.
+    pub height: usize,
+
+    /// Depth of the base image in pixels for volume textures, or 1 otherwise.
+    pub depth: usize,
+
+    /// Number of mipmaps, including the base image.
+    pub mipmap_count: usize,
+
+    /// Whether the texture is a cubemap with all six faces.
+    pub cubemap: bool
+}
+
+impl<'a> DdsHeader<'a> {
+    /// Create a header for a 2D texture without mipmaps.
+    pub const fn new(format: Format<'a>, width: usize, height: usize) -> Self {
+        Self {
+            format,
+            color_space: ColorSpace::Linear,
+            width,
+            height,
+            depth: 1,
+            mipmap_count: 1,
+            cubemap: false
+        }
+    }
+
+    /// Get the number of bytes [`write`](DdsHeader::write) writes, including the magic number.
+    ///
+    /// This is 148 if the DX10 header is needed, and 128 otherwise.
+    pub fn header_size(&self) -> usize {
+        if self.needs_dx10() { DX10_HEADER_SIZE } else { HEADER_SIZE }
+    }
+
+    /// Get the number of bytes of data after the header.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::SizeOverflow`] if the result overflows [usize]
+    /// * [`Error::InvalidHeader`] if the dimensions or mipmap count are invalid
+    pub fn data_size(&self) -> Result<usize, Error> {
+        self.validate()?;
+
+        let faces = if self.cubemap { 6 } else { 1 };
+        let mut face_size = 0usize;
+        for level in 0..self.mipmap_count {
+            let (width, height) = mipmap_size(self.width, self.height, level);
+            let (depth, _) = mipmap_size(self.depth, 1, level);
+            let bytes = self.format.checked_required_bytes(width, height)?.checked_mul(depth).ok_or(Error::SizeOverflow)?;
+            face_size = face_size.checked_add(bytes).ok_or(Error::SizeOverflow)?;
+        }
+        face_size.checked_mul(faces).ok_or(Error::SizeOverflow)
+    }
+
+    /// Write the header, including the magic number.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::InvalidOutputLength`] if `to_bytes.len() != self.header_size()`
+    /// * [`Error::UnsupportedFormat`] if DDS can't store the format in the color space
+    /// * [`Error::InvalidHeader`] if the dimensions or mipmap count are invalid or don't fit in the
+    ///   header
+    /// * [`Error::SizeOverflow`] if the size of the data overflows [usize]
+    pub fn write(&self, to_bytes: &mut [u8]) -> Result<(), Error> {
+        let header_size = self.header_size();
+        if to_bytes.len() != header_size {
+            return Err(Error::InvalidOutputLength { expected: header_size, actual: to_bytes.len() })
+        }
+        self.data_size()?;
+
+        let dx10_format = if self.needs_dx10() {
+            Some(self.format.to_dxgi(self.color_space)?)
+        }
+        else {
+            None
+        };
+
+        let (pixel_format_flags, fourcc, bit_count, masks) = if dx10_format.is_some() {
+            (DDPF_FOURCC, *b"DX10", 0, [0; 4])
+        }
+        else if let Ok(fourcc) = self.format.to_fourcc() {
+            (DDPF_FOURCC, fourcc, 0, [0; 4])
+        }
+        else if let Some((_, flags, bit_count, masks)) = MASK_FORMATS.iter().find(|(f, ..)| *f == self.format) {
+            (*flags, [0; 4], *bit_count, *masks)
+        }
+        else {
+            return Err(Error::UnsupportedFormat)
+        };
+
+        let to_u32 = |value: usize| u32::try_from(value).map_err(|_| Error::InvalidHeader);
+        let volume = self.depth > 1;
+        let (block_width, _) = self.format.block_size_pixels();
+
+        let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+        let pitch_or_linear_size = if block_width == 4 {
+            flags |= DDSD_LINEARSIZE;
+            self.format.required_bytes(self.width, self.height)
+        }
+        else {
+            flags |= DDSD_PITCH;
+            self.format.required_bytes(self.width, 1)
+        };
+        if self.mipmap_count > 1 {
+            flags |= DDSD_MIPMAPCOUNT;
+        }
+        if volume {
+            flags |= DDSD_DEPTH;
+        }
+
+        let mut caps = DDSCAPS_TEXTURE;
+        if self.mipmap_count > 1 {
+            caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+        }
+        if self.cubemap || volume {
+            caps |= DDSCAPS_COMPLEX;
+        }
+        let mut caps2 = 0;
+        if self.cubemap {
+            caps2 |= DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES;
+        }
+        if volume {
+            caps2 |= DDSCAPS2_VOLUME;
+        }
+
+        to_bytes.fill(0);
+        let mut write = |offset: usize, value: u32| to_bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        write(4, 124);
+        write(8, flags);
+        write(12, to_u32(self.height)?);
+        write(16, to_u32(self.width)?);
+        write(20, to_u32(pitch_or_linear_size)?);
+        write(24, if volume { to_u32(self.depth)? } else { 0 });
+        write(28, to_u32(self.mipmap_count)?);
+        write(76, 32);
+        write(80, pixel_format_flags);
+        write(84, u32::from_le_bytes(fourcc));
+        write(88, bit_count);
+        for (i, mask) in masks.into_iter().enumerate() {
+            write(92 + i * 4, mask);
+        }
+        write(108, caps);
+        write(112, caps2);
+
+        if let Some(dxgi_format) = dx10_format {
+            let dimension = if volume { D3D10_RESOURCE_DIMENSION_TEXTURE3D } else { D3D10_RESOURCE_DIMENSION_TEXTURE2D };
+            write(128, dxgi_format);
+            write(132, dimension);
+            write(136, if self.cubemap { D3D10_RESOURCE_MISC_TEXTURECUBE } else { 0 });
+            write(140, 1);
+        }
+
+        to_bytes[0..4].copy_from_slice(MAGIC);
+        Ok(())
+    }
+
+    /// Return an error if the dimensions or mipmap count are invalid.
+    fn validate(&self) -> Result<(), Error> {
+        let max_mipmaps = mipmap_count(self.width.max(self.height), self.depth);
+        let valid = self.width > 0
+            && self.height > 0
+            && self.depth > 0
+            && (1..=max_mipmaps).contains(&self.mipmap_count)
+            && (!self.cubemap || (self.depth == 1 && self.width == self.height));
+
+        if valid { Ok(()) } else { Err(Error::InvalidHeader) }
+    }
+
+    /// Returns true if the legacy header can't describe the texture.
+    fn needs_dx10(&self) -> bool {
+        self.color_space == ColorSpace::Srgb
+            || !(self.format.to_fourcc().is_ok() || MASK_FORMATS.iter().any(|(f, ..)| *f == self.format))
+    }
+}
+
+impl DdsHeader<'static> {
+    /// Read a DDS file, returning the header and its data.
+    ///
+    /// Anything after the data is ignored.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::InvalidHeader`] if the file doesn't start with a valid DDS header
+    /// * [`Error::UnsupportedFormat`] if the format isn't supported, or the file is a texture
+    ///   array or only has some faces of a cubemap
+    /// * [`Error::InvalidInputLength`] if the file is too short for its data
+    /// * [`Error::SizeOverflow`] if the size of the data overflows [usize]
+    pub fn read(from_bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let read = |offset: usize| -> Result<u32, Error> {
+            let bytes = from_bytes.get(offset..offset + 4).ok_or(Error::InvalidHeader)?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        if from_bytes.len() < HEADER_SIZE || from_bytes[0..4] != *MAGIC || read(4)? != 124 {
+            return Err(Error::InvalidHeader)
+        }
+
+        let height = read(12)? as usize;
+        let width = read(16)? as usize;
+        let depth = read(24)? as usize;
+        let mipmap_count = (read(28)? as usize).max(1);
+        let pixel_format_flags = read(80)?;
+        let fourcc = read(84)?.to_le_bytes();
+        let caps2 = read(112)?;
+
+        let (format, color_space, cubemap, volume, header_size) = if pixel_format_flags & DDPF_FOURCC != 0 && fourcc == *b"DX10" {
+            let dxgi_format = read(128)?;
+            let (format, color_space) = Format::from_dxgi(dxgi_format)?;
+
+            if read(140)? > 1 {
+                return Err(Error::UnsupportedFormat)
+            }
+
+            let cubemap = read(136)? & D3D10_RESOURCE_MISC_TEXTURECUBE != 0;
+            let volume = read(132)? == D3D10_RESOURCE_DIMENSION_TEXTURE3D;
+            (format, color_space, cubemap, volume, DX10_HEADER_SIZE)
+        }
+        else {
+            let format = if pixel_format_flags & DDPF_FOURCC != 0 {
+                Format::from_fourcc(fourcc).ok()
+            }
+            else {
+                // Alpha masks are only meaningful with the alpha flags.
+                let kind = pixel_format_flags & (DDPF_RGB | DDPF_LUMINANCE | DDPF_ALPHA | DDPF_ALPHAPIXELS);
+                let bit_count = read(88)?;
+                let mut masks = [read(92)?, read(96)?, read(100)?, read(104)?];
+                if kind & (DDPF_ALPHA | DDPF_ALPHAPIXELS) == 0 {
+                    masks[3] = 0;
+                }
+                MASK_FORMATS.iter().find(|(_, f, b, m)| (*f, *b, *m) == (kind, bit_count, masks)).map(|(format, ..)| *format)
+            };
+
+            let cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+            if cubemap && caps2 & DDSCAPS2_CUBEMAP_ALL_FACES != DDSCAPS2_CUBEMAP_ALL_FACES {
+                return Err(Error::UnsupportedFormat)
+            }
+
+            let volume = caps2 & DDSCAPS2_VOLUME != 0;
+            (format.ok_or(Error::UnsupportedFormat)?, ColorSpace::Linear, cubemap, volume, HEADER_SIZE)
+        };
+
+        let header = Self {
+            format,
+            color_space,
+            width,
+            height,
+            depth: if volume { depth.max(1) } else { 1 },
+            mipmap_count,
+            cubemap
+        };
+
+        let end = header_size.checked_add(header.data_size()?).ok_or(Error::SizeOverflow)?;
+        let data = from_bytes.get(header_size..end).ok_or(Error::InvalidInputLength { expected: end, actual: from_bytes.len() })?;
+        Ok((header, data))
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,126 @@
+use crate::{ColorSpace, DdsHeader, Error, Format};
+
+fn roundtrip(header: DdsHeader<'static>) -> [u8; 148] {
+    let mut bytes = [0u8; 148];
+    let header_size = header.header_size();
+    header.write(&mut bytes[..header_size]).unwrap();
+
+    let data_size = header.data_size().unwrap();
+    let mut file = [0u8; 4096];
+    file[..header_size].copy_from_slice(&bytes[..header_size]);
+    let (read, data) = DdsHeader::read(&file[..header_size + data_size]).unwrap();
+    assert_eq!(header, read);
+    assert_eq!(data_size, data.len());
+
+    // Too short for the data
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: header_size + data_size, actual: header_size + data_size - 1 }),
+        DdsHeader::read(&file[..header_size + data_size - 1])
+    );
+
+    bytes
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+pub fn legacy_formats() {
+    let formats = [
+        Format::DXT1, Format::DXT3, Format::DXT5, Format::R5G6B5, Format::A1R5G5B5, Format::A4R4G4B4,
+        Format::X8R8G8B8, Format::A8R8G8B8, Format::A8B8G8R8, Format::A8, Format::Y8, Format::A8Y8, Format::A4Y4
+    ];
+    for format in formats {
+        let header = DdsHeader { mipmap_count: 3, ..DdsHeader::new(format, 7, 5) };
+        assert_eq!(128, header.header_size());
+        roundtrip(header);
+    }
+
+    let bytes = roundtrip(DdsHeader::new(Format::A8R8G8B8, 7, 5));
+    assert_eq!(b"DDS ", &bytes[0..4]);
+    assert_eq!(7 * 4, read_u32(&bytes, 20));
+    assert_eq!(32, read_u32(&bytes, 88));
+    assert_eq!([0xFF0000, 0xFF00, 0xFF, 0xFF000000], [92, 96, 100, 104].map(|o| read_u32(&bytes, o)));
+
+    let bytes = roundtrip(DdsHeader::new(Format::DXT5, 7, 5));
+    assert_eq!(b"DXT5", &bytes[84..88]);
+    assert_eq!(2 * 2 * 16, read_u32(&bytes, 20));
+}
+
+#[test]
+pub fn dx10_formats() {
+    let header = DdsHeader::new(Format::BC7, 16, 16);
+    assert_eq!(148, header.header_size());
+    let bytes = roundtrip(header);
+    assert_eq!(b"DX10", &bytes[84..88]);
+    assert_eq!(98, read_u32(&bytes, 128));
+
+    // sRGB needs the DX10 header even if the format doesn't.
+    let header = DdsHeader { color_space: ColorSpace::Srgb, ..DdsHeader::new(Format::DXT1, 16, 16) };
+    assert_eq!(148, header.header_size());
+    let bytes = roundtrip(header);
+    assert_eq!(72, read_u32(&bytes, 128));
+
+    let header = DdsHeader { color_space: ColorSpace::Srgb, ..DdsHeader::new(Format::A8B8G8R8, 4, 4) };
+    assert_eq!(29, read_u32(&roundtrip(header), 128));
+}
+
+#[test]
+pub fn cubemaps_and_volumes() {
+    // Six faces with 8x8, 4x4, 2x2, and 1x1 mipmaps
+    let cubemap = DdsHeader { cubemap: true, mipmap_count: 4, ..DdsHeader::new(Format::A8, 8, 8) };
+    assert_eq!(Ok(6 * (64 + 16 + 4 + 1)), cubemap.data_size());
+    let bytes = roundtrip(cubemap);
+    assert_eq!(0xFE00, read_u32(&bytes, 112));
+    roundtrip(DdsHeader { format: Format::BC7, ..cubemap });
+
+    // Depth is halved along with the width and height: 8x2x4, 4x1x2, 2x1x1, and 1x1x1
+    let volume = DdsHeader { depth: 4, mipmap_count: 4, ..DdsHeader::new(Format::A8, 8, 2) };
+    assert_eq!(Ok(64 + 8 + 2 + 1), volume.data_size());
+    let bytes = roundtrip(volume);
+    assert_eq!(4, read_u32(&bytes, 24));
+    roundtrip(DdsHeader { format: Format::BC7, ..volume });
+}
+
+#[test]
+pub fn invalid_headers() {
+    let mut bytes = [0u8; 128];
+    let header = DdsHeader::new(Format::Y8, 4, 4);
+    assert_eq!(Err(Error::InvalidOutputLength { expected: 128, actual: 127 }), header.write(&mut bytes[..127]));
+
+    for invalid in [
+        DdsHeader { width: 0, ..header },
+        DdsHeader { mipmap_count: 0, ..header },
+        DdsHeader { mipmap_count: 4, ..header },
+        DdsHeader { cubemap: true, height: 2, ..header },
+        DdsHeader { width: u32::MAX as usize + 1, ..header }
+    ] {
+        assert_eq!(Err(Error::InvalidHeader), invalid.write(&mut bytes), "{invalid:?}");
+    }
+
+    let mut dx10_bytes = [0u8; 148];
+    for unsupported in [
+        DdsHeader { format: Format::AY8, ..header },
+        DdsHeader { format: Format::P8(&[crate::Pixel { alpha: 0, red: 0, green: 0, blue: 0 }]), ..header }
+    ] {
+        assert_eq!(Err(Error::UnsupportedFormat), unsupported.write(&mut dx10_bytes));
+    }
+    assert_eq!(Err(Error::UnsupportedFormat), DdsHeader { color_space: ColorSpace::Srgb, ..header }.write(&mut dx10_bytes));
+
+    header.write(&mut bytes).unwrap();
+    assert_eq!(Err(Error::InvalidHeader), DdsHeader::read(&bytes[..127]));
+
+    let mut bad_magic = bytes;
+    bad_magic[0] = b'd';
+    assert_eq!(Err(Error::InvalidHeader), DdsHeader::read(&bad_magic));
+
+    let mut unknown_fourcc = bytes;
+    unknown_fourcc[80..84].copy_from_slice(&0x4u32.to_le_bytes());
+    unknown_fourcc[84..88].copy_from_slice(b"ATI2");
+    assert_eq!(Err(Error::UnsupportedFormat), DdsHeader::read(&unknown_fourcc));
+
+    let mut partial_cubemap = bytes;
+    partial_cubemap[112..116].copy_from_slice(&0x0600u32.to_le_bytes());
+    assert_eq!(Err(Error::UnsupportedFormat), DdsHeader::read(&partial_cubemap));
+}
@@ -0,0 +1,58 @@
+use core::fmt;
+
+/// Errors returned by the fallible conversion functions.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Error {
+    /// The input buffer is not the length required for the format, width, and height.
+    InvalidInputLength {
+        expected: usize,
+        actual: usize
+    },
+
+    /// The output buffer is not the length required for the format, width, and height.
+    InvalidOutputLength {
+        expected: usize,
+        actual: usize
+    },
+
+    /// The error diffusion buffer is shorter than [`Dither::buffer_length`](crate::Dither::buffer_length)
+    /// for the width.
+    InvalidBufferLength {
+        expected: usize,
+        actual: usize
+    },
+
+    /// The width and height describe more pixels or bytes than fit in [usize].
+    SizeOverflow,
+
+    /// The palette has no colors or more colors than the format can index.
+    InvalidPaletteLength {
+        length: usize
+    },
+
+    /// The format is not supported for this operation.
+    UnsupportedFormat,
+
+    /// The data doesn't start with a valid header, such as a DDS header.
+    InvalidHeader,
+
+    /// The width, height, or depth is not a power of two, which swizzling requires.
+    NonPowerOfTwo
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidInputLength { expected, actual } => write!(f, "input is {actual} bytes/pixels, expected {expected}"),
+            Error::InvalidOutputLength { expected, actual } => write!(f, "output is {actual} bytes/pixels, expected {expected}"),
+            Error::InvalidBufferLength { expected, actual } => write!(f, "buffer is {actual} entries, expected at least {expected}"),
+            Error::SizeOverflow => f.write_str("size overflows usize"),
+            Error::InvalidPaletteLength { length } => write!(f, "palette has {length} colors, which the format can't use"),
+            Error::UnsupportedFormat => f.write_str("unsupported format"),
+            Error::InvalidHeader => f.write_str("invalid header"),
+            Error::NonPowerOfTwo => f.write_str("width, height, or depth is not a power of two")
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -1,8 +1,25 @@
-use crate::Pixel;
+use crate::{mipmap_count, mipmap_pixel_count, mipmap_size, Error, Pixel};
+pub(crate) use self::lookup::PaletteLookup;
+use core::ops::Range;
+
+mod bc7;
+mod codes;
+mod dither;
+mod dxt;
+mod fit;
+mod lookup;
+mod options;
+mod suggest;
+mod swizzle;
+
+pub use bc7::BC7Profile;
+pub use dither::Dither;
+pub use options::EncodeOptions;
+pub use suggest::{AlphaKind, FormatCandidate, FormatPolicy, FormatSuggestion, ImageAnalysis};
 
 /// Defines formats supported by the compressor/decompressor.
 #[derive(Copy, Clone, PartialEq, Debug)]
-pub enum Format {
+pub enum Format<'a> {
     /// DXT block compression with optional 1-bit alpha
     DXT1,
 
@@ -27,6 +44,20 @@ pub enum Format {
     /// 8-bit alpha with 8-bit luminosity
     A8Y8,
 
+    /// 4-bit alpha (100% white)
+    ///
+    /// Two pixels are stored in each byte, with the left pixel in the low 4 bits. Each row starts
+    /// on a new byte, so rows with an odd width end with 4 unused bits.
+    A4,
+
+    /// 4-bit luminosity (100% opaque)
+    ///
+    /// Pixels are stored in the same order as [`A4`](Format::A4).
+    Y4,
+
+    /// 4-bit alpha (high 4 bits) with 4-bit luminosity (low 4 bits)
+    A4Y4,
+
     /// 5-bit red, 6-bit green, and 5-bit blue (100% opaque)
     R5G6B5,
 
@@ -45,43 +76,39 @@ pub enum Format {
     /// 8-bit alpha, blue, green, and red
     A8B8G8R8,
 
-    /// 8-bit palettized
-    P8([Pixel; 256]),
+    /// 8-bit palettized with 1 to 256 colors
+    ///
+    /// Indices past the end of the palette decode as transparent black.
+    P8(&'a [Pixel]),
+
+    /// 4-bit palettized with 1 to 16 colors
+    ///
+    /// Indices are stored in the same order as [`A4`](Format::A4), and indices past the end of the
+    /// palette decode as transparent black.
+    P4(&'a [Pixel]),
 }
 
-impl Format {
-    /// Size of each block in pixels (in width and height).
+impl Format<'_> {
+    /// Size of each block in pixels, as width and height.
     ///
     /// For compressed textures utilizing block compression, the size will be rounded *up* to the
     /// nearest block. For example, with DXT1, a 16x17 texture will be compressed as 16x20, and
     /// the extra 3 pixels will be undetermined but valid.
     ///
+    /// Formats with 4 bits per pixel store two pixels per byte, so their blocks are 2x1, and rows
+    /// with an odd width are likewise rounded up.
+    ///
     /// # Examples
     ///
     /// ```rust
     /// use macaroni_tex::Format;
     ///
-    /// assert_eq!(4, Format::BC7.block_size_pixels());
-    /// assert_eq!(1, Format::A1R5G5B5.block_size_pixels());
+    /// assert_eq!((4, 4), Format::BC7.block_size_pixels());
+    /// assert_eq!((1, 1), Format::A1R5G5B5.block_size_pixels());
+    /// assert_eq!((2, 1), Format::Y4.block_size_pixels());
     /// ```
-    pub const fn block_size_pixels(self) -> usize {
-        match self {
-            Format::DXT1 => 4,
-            Format::DXT3 => 4,
-            Format::DXT5 => 4,
-            Format::BC7 => 4,
-            Format::A8 => 1,
-            Format::Y8 => 1,
-            Format::AY8 => 1,
-            Format::A8Y8 => 1,
-            Format::R5G6B5 => 1,
-            Format::A1R5G5B5 => 1,
-            Format::A4R4G4B4 => 1,
-            Format::X8R8G8B8 => 1,
-            Format::A8R8G8B8 => 1,
-            Format::A8B8G8R8 => 1,
-            Format::P8(_) => 1
-        }
+    pub const fn block_size_pixels(self) -> (usize, usize) {
+        self.info().block_size_pixels
     }
 
     /// Number of bytes each block takes up.
@@ -95,22 +122,148 @@ impl Format {
     /// assert_eq!(2, Format::A1R5G5B5.block_size_bytes());
     /// ```
     pub const fn block_size_bytes(self) -> usize {
+        self.info().block_size_bytes
+    }
+
+    /// Average number of bits each pixel takes up.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// assert_eq!(4, Format::DXT1.bits_per_pixel());
+    /// assert_eq!(8, Format::DXT5.bits_per_pixel());
+    /// assert_eq!(4, Format::A4.bits_per_pixel());
+    /// assert_eq!(32, Format::X8R8G8B8.bits_per_pixel());
+    /// ```
+    pub const fn bits_per_pixel(self) -> usize {
+        let info = self.info();
+        info.block_size_bytes * 8 / (info.block_size_pixels.0 * info.block_size_pixels.1)
+    }
+
+    /// Bits of precision of the alpha, red, green, and blue channels, in that order.
+    ///
+    /// Channels with 0 bits are not stored and decode to a constant, which is 255 for alpha and for
+    /// the color of alpha-only formats. Monochrome formats store one luminance value, which counts
+    /// for each of red, green, and blue, and [`AY8`](Format::AY8) uses it for alpha as well.
+    ///
+    /// For compressed formats, this is the precision of the block endpoints, and for palettized
+    /// formats, this is the precision of the palette.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// assert_eq!([1, 5, 5, 5], Format::A1R5G5B5.channel_bits());
+    /// assert_eq!([0, 8, 8, 8], Format::X8R8G8B8.channel_bits());
+    /// assert_eq!([8, 5, 6, 5], Format::DXT5.channel_bits());
+    /// ```
+    pub const fn channel_bits(self) -> [u8; 4] {
+        self.info().channel_bits
+    }
+
+    /// Bits of precision of the alpha channel, or 0 if the format is always opaque.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// assert_eq!(1, Format::DXT1.alpha_bits());
+    /// assert_eq!(4, Format::DXT3.alpha_bits());
+    /// assert_eq!(0, Format::R5G6B5.alpha_bits());
+    /// ```
+    pub const fn alpha_bits(self) -> u8 {
+        self.channel_bits()[0]
+    }
+
+    /// Returns true if the format can store pixels that aren't fully opaque.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// assert!(Format::A8.has_alpha());
+    /// assert!(!Format::X8R8G8B8.has_alpha());
+    /// ```
+    pub const fn has_alpha(self) -> bool {
+        self.alpha_bits() > 0
+    }
+
+    /// Returns true if the format uses block compression.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// assert!(Format::BC7.is_compressed());
+    /// assert!(!Format::A4.is_compressed());
+    /// ```
+    pub const fn is_compressed(self) -> bool {
+        matches!(self.info().kind, FormatKind::Compressed)
+    }
+
+    /// Returns true if the format stores indices into a palette.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// assert!(Format::P4(&[]).is_palettized());
+    /// assert!(!Format::A8R8G8B8.is_palettized());
+    /// ```
+    pub const fn is_palettized(self) -> bool {
+        matches!(self.info().kind, FormatKind::Palettized)
+    }
+
+    /// Returns true if the format can only store gray colors, where red, green, and blue are
+    /// equal.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// assert!(Format::A8Y8.is_monochrome());
+    /// assert!(Format::A8.is_monochrome());
+    /// assert!(!Format::DXT1.is_monochrome());
+    /// ```
+    pub const fn is_monochrome(self) -> bool {
+        matches!(self.info().kind, FormatKind::Monochrome)
+    }
+
+    /// Layout and precision of the format, which every query above is derived from.
+    const fn info(self) -> FormatInfo {
+        const fn info(block_size_pixels: (usize, usize), block_size_bytes: usize, channel_bits: [u8; 4], kind: FormatKind) -> FormatInfo {
+            FormatInfo { block_size_pixels, block_size_bytes, channel_bits, kind }
+        }
+
+        use FormatKind::*;
         match self {
-            Format::DXT1 => 8,
-            Format::DXT3 => 16,
-            Format::DXT5 => 16,
-            Format::BC7 => 16,
-            Format::A8 => 1,
-            Format::Y8 => 1,
-            Format::AY8 => 1,
-            Format::A8Y8 => 2,
-            Format::R5G6B5 => 2,
-            Format::A1R5G5B5 => 2,
-            Format::A4R4G4B4 => 2,
-            Format::X8R8G8B8 => 4,
-            Format::A8R8G8B8 => 4,
-            Format::A8B8G8R8 => 4,
-            Format::P8(_) => 1
+            Format::DXT1 => info((4, 4), 8, [1, 5, 6, 5], Compressed),
+            Format::DXT3 => info((4, 4), 16, [4, 5, 6, 5], Compressed),
+            Format::DXT5 => info((4, 4), 16, [8, 5, 6, 5], Compressed),
+            Format::BC7 => info((4, 4), 16, [8, 8, 8, 8], Compressed),
+            Format::A8 => info((1, 1), 1, [8, 0, 0, 0], Monochrome),
+            Format::Y8 => info((1, 1), 1, [0, 8, 8, 8], Monochrome),
+            Format::AY8 => info((1, 1), 1, [8, 8, 8, 8], Monochrome),
+            Format::A8Y8 => info((1, 1), 2, [8, 8, 8, 8], Monochrome),
+            Format::A4 => info((2, 1), 1, [4, 0, 0, 0], Monochrome),
+            Format::Y4 => info((2, 1), 1, [0, 4, 4, 4], Monochrome),
+            Format::A4Y4 => info((1, 1), 1, [4, 4, 4, 4], Monochrome),
+            Format::R5G6B5 => info((1, 1), 2, [0, 5, 6, 5], Color),
+            Format::A1R5G5B5 => info((1, 1), 2, [1, 5, 5, 5], Color),
+            Format::A4R4G4B4 => info((1, 1), 2, [4, 4, 4, 4], Color),
+            Format::X8R8G8B8 => info((1, 1), 4, [0, 8, 8, 8], Color),
+            Format::A8R8G8B8 => info((1, 1), 4, [8, 8, 8, 8], Color),
+            Format::A8B8G8R8 => info((1, 1), 4, [8, 8, 8, 8], Color),
+            Format::P8(_) => info((1, 1), 1, [8, 8, 8, 8], Palettized),
+            Format::P4(_) => info((2, 1), 1, [8, 8, 8, 8], Palettized)
         }
     }
 
@@ -120,84 +273,344 @@ impl Format {
     ///
     /// This function will panic if the result exceeds [usize::MAX].
     pub const fn required_bytes(self, width: usize, height: usize) -> usize {
+        match self.checked_required_bytes(width, height) {
+            Ok(bytes) => bytes,
+            Err(_) => panic!("required_bytes(): total bytes overflows usize")
+        }
+    }
+
+    /// Get the number of bytes required to represent height*width with the format.
+    ///
+    /// Returns [`Error::SizeOverflow`] if the result exceeds [usize::MAX].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Error, Format};
+    ///
+    /// assert_eq!(Ok(32), Format::DXT1.checked_required_bytes(5, 5));
+    /// assert_eq!(Ok(6), Format::A4.checked_required_bytes(5, 2));
+    /// assert_eq!(Err(Error::SizeOverflow), Format::A8R8G8B8.checked_required_bytes(usize::MAX, 1));
+    /// ```
+    pub const fn checked_required_bytes(self, width: usize, height: usize) -> Result<usize, Error> {
         let block_size_bytes = self.block_size_bytes();
-        let block_size_pixels = self.block_size_pixels();
-        let blocks_height = height.div_ceil(block_size_pixels);
-        let blocks_width = width.div_ceil(block_size_pixels);
+        let (block_width, block_height) = self.block_size_pixels();
+        let blocks_height = height.div_ceil(block_height);
+        let blocks_width = width.div_ceil(block_width);
 
         let Some(block_count) = blocks_height.checked_mul(blocks_width) else {
-            panic!("required_bytes(): blocks_height*block_width overflows usize")
+            return Err(Error::SizeOverflow)
         };
 
         let Some(bytes) = block_count.checked_mul(block_size_bytes) else {
-            panic!("required_bytes(): total bytes overflows usize")
+            return Err(Error::SizeOverflow)
         };
 
-        bytes
+        Ok(bytes)
+    }
+
+    /// Get the number of bytes required to represent every mipmap of a height*width image with the
+    /// format, with each mipmap taking [`required_bytes`](Format::required_bytes) for its size.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the result exceeds [usize::MAX].
+    pub const fn required_bytes_mipmapped(self, width: usize, height: usize) -> usize {
+        match self.checked_required_bytes_mipmapped(width, height) {
+            Ok(bytes) => bytes,
+            Err(_) => panic!("required_bytes_mipmapped(): total bytes overflows usize")
+        }
+    }
+
+    /// Get the number of bytes required to represent every mipmap of a height*width image with the
+    /// format, with each mipmap taking [`required_bytes`](Format::required_bytes) for its size.
+    ///
+    /// Returns [`Error::SizeOverflow`] if the result exceeds [usize::MAX].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// // 4x4, 2x2, and 1x1 all take one block.
+    /// assert_eq!(Ok(8 * 3), Format::DXT1.checked_required_bytes_mipmapped(4, 4));
+    /// assert_eq!(Ok(4 * (5*2 + 2*1 + 1*1)), Format::A8R8G8B8.checked_required_bytes_mipmapped(5, 2));
+    /// ```
+    pub const fn checked_required_bytes_mipmapped(self, width: usize, height: usize) -> Result<usize, Error> {
+        let mut total = 0usize;
+        let mut level = 0;
+        while level < mipmap_count(width, height) {
+            let (level_width, level_height) = mipmap_size(width, height, level);
+            let bytes = match self.checked_required_bytes(level_width, level_height) {
+                Ok(bytes) => bytes,
+                Err(e) => return Err(e)
+            };
+            let Some(sum) = total.checked_add(bytes) else {
+                return Err(Error::SizeOverflow)
+            };
+            total = sum;
+            level += 1;
+        }
+        Ok(total)
     }
 
     /// Convert pixels from 8-bit to the output format.
     ///
+    /// This is the same as [`encode_pixels_with`](Format::encode_pixels_with) with the default
+    /// [`EncodeOptions`].
+    ///
     /// # Panics
     ///
     /// This function will panic if:
     /// * `to_bytes.len() != self.requires_bytes(width, height)`
     /// * `from_pixels.len() != width*height`
+    /// * the format is palettized and the palette has no colors or too many colors
     pub fn encode_pixels(
         self,
         from_pixels: &[Pixel],
         to_bytes: &mut [u8],
         width: usize,
         height: usize
+    ) {
+        self.encode_pixels_with(&EncodeOptions::default(), from_pixels, to_bytes, width, height)
+    }
+
+    /// Convert pixels from 8-bit to the output format with the given options.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if:
+    /// * `to_bytes.len() != self.requires_bytes(width, height)`
+    /// * `from_pixels.len() != width*height`
+    /// * the format is palettized and the palette has no colors or too many colors
+    pub fn encode_pixels_with(
+        self,
+        options: &EncodeOptions,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        width: usize,
+        height: usize
     ) {
         assert_eq!(self.required_bytes(width, height), to_bytes.len());
         assert_eq!(width*height, from_pixels.len());
+        assert!(self.check_palette().is_ok(), "palette has no colors or too many colors");
 
+        self.encode_pixels_unchecked(options, from_pixels, to_bytes, None, width, height)
+    }
+
+    /// Convert pixels from 8-bit to the output format with the given options, using `buffer` to
+    /// hold errors so error diffusion is exact for images of any width.
+    ///
+    /// The contents of `buffer` don't matter and are overwritten. See [`Dither::buffer_length`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if:
+    /// * `to_bytes.len() != self.requires_bytes(width, height)`
+    /// * `from_pixels.len() != width*height`
+    /// * `buffer.len() < Dither::buffer_length(width)`
+    /// * the format is palettized and the palette has no colors or too many colors
+    pub fn encode_pixels_with_buffer(
+        self,
+        options: &EncodeOptions,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        buffer: &mut [[i32; 4]],
+        width: usize,
+        height: usize
+    ) {
+        assert_eq!(self.required_bytes(width, height), to_bytes.len());
+        assert_eq!(width*height, from_pixels.len());
+        assert!(buffer.len() >= Dither::buffer_length(width), "error buffer is too short");
+        assert!(self.check_palette().is_ok(), "palette has no colors or too many colors");
+
+        self.encode_pixels_unchecked(options, from_pixels, to_bytes, Some(buffer), width, height)
+    }
+
+    /// Convert pixels from 8-bit to the output format, returning an error instead of panicking if
+    /// the buffers are the wrong size.
+    ///
+    /// This is the same as [`try_encode_pixels_with`](Format::try_encode_pixels_with) with the
+    /// default [`EncodeOptions`].
+    pub fn try_encode_pixels(
+        self,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        width: usize,
+        height: usize
+    ) -> Result<(), Error> {
+        self.try_encode_pixels_with(&EncodeOptions::default(), from_pixels, to_bytes, width, height)
+    }
+
+    /// Convert pixels from 8-bit to the output format with the given options, returning an error
+    /// instead of panicking if the buffers are the wrong size.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::SizeOverflow`] if `width*height` or the required bytes overflow [usize]
+    /// * [`Error::InvalidInputLength`] if `from_pixels.len() != width*height`
+    /// * [`Error::InvalidOutputLength`] if `to_bytes.len() != self.required_bytes(width, height)`
+    /// * [`Error::InvalidPaletteLength`] if the format is palettized and the palette has no colors
+    ///   or too many colors
+    pub fn try_encode_pixels_with(
+        self,
+        options: &EncodeOptions,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        width: usize,
+        height: usize
+    ) -> Result<(), Error> {
+        self.check_palette()?;
+        check_length(width.checked_mul(height).ok_or(Error::SizeOverflow)?, from_pixels.len(), true)?;
+        check_length(self.checked_required_bytes(width, height)?, to_bytes.len(), false)?;
+
+        self.encode_pixels_unchecked(options, from_pixels, to_bytes, None, width, height);
+        Ok(())
+    }
+
+    /// Convert pixels from 8-bit to the output format with the given options and error buffer,
+    /// returning an error instead of panicking if the buffers are the wrong size.
+    ///
+    /// See [`encode_pixels_with_buffer`](Format::encode_pixels_with_buffer).
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::SizeOverflow`] if `width*height` or the required bytes overflow [usize]
+    /// * [`Error::InvalidInputLength`] if `from_pixels.len() != width*height`
+    /// * [`Error::InvalidOutputLength`] if `to_bytes.len() != self.required_bytes(width, height)`
+    /// * [`Error::InvalidBufferLength`] if `buffer.len() < Dither::buffer_length(width)`
+    /// * [`Error::InvalidPaletteLength`] if the format is palettized and the palette has no colors
+    ///   or too many colors
+    pub fn try_encode_pixels_with_buffer(
+        self,
+        options: &EncodeOptions,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        buffer: &mut [[i32; 4]],
+        width: usize,
+        height: usize
+    ) -> Result<(), Error> {
+        self.check_palette()?;
+        check_length(width.checked_mul(height).ok_or(Error::SizeOverflow)?, from_pixels.len(), true)?;
+        check_length(self.checked_required_bytes(width, height)?, to_bytes.len(), false)?;
+        if buffer.len() < Dither::buffer_length(width) {
+            return Err(Error::InvalidBufferLength { expected: Dither::buffer_length(width), actual: buffer.len() })
+        }
+
+        self.encode_pixels_unchecked(options, from_pixels, to_bytes, Some(buffer), width, height);
+        Ok(())
+    }
+
+    fn encode_pixels_unchecked(
+        self,
+        options: &EncodeOptions,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        buffer: Option<&mut [[i32; 4]]>,
+        width: usize,
+        height: usize
+    ) {
         match self {
             // Compressed formats
-            Format::DXT1 => todo!(),
-            Format::DXT3 => todo!(),
-            Format::DXT5 => todo!(),
-            Format::BC7 => todo!(),
+            Format::DXT1 => encode_blocks(from_pixels, to_bytes, width, height, |block| dxt::encode_bc1_block(block, options)),
+            Format::DXT3 => encode_blocks(from_pixels, to_bytes, width, height, |block| dxt::encode_bc2_block(block, options)),
+            Format::DXT5 => encode_blocks(from_pixels, to_bytes, width, height, |block| dxt::encode_bc3_block(block, options)),
+            Format::BC7 => encode_blocks(from_pixels, to_bytes, width, height, |block| bc7::encode_bc7_block(block, options)),
 
             // Palettized
-            Format::P8(palette) => {
-                encode_palettized(from_pixels.iter(), &palette)
+            Format::P8(palette) if options.dither == Dither::None => {
+                encode_palettized(from_pixels.iter(), palette, options)
                     .zip(to_bytes.iter_mut())
                     .for_each(|(input, output)| *output = input as u8)
             }
+            Format::P8(palette) => {
+                    let mut lookup = PaletteLookup::new(palette, options);
+                dither::dither_pixels(options.dither, from_pixels, width, height, dither::palette_steps(palette), buffer, |i, pixel| {
+                    let index = lookup.find(pixel);
+                    to_bytes[i] = index as u8;
+                    palette[index]
+                })
+            }
+
+            // 4 bits per pixel
+            Format::A4 | Format::Y4 | Format::P4(_) => {
+                let mut lookup = match self {
+                    Format::P4(palette) => Some(PaletteLookup::new(palette, options)),
+                    _ => None
+                };
+                let steps = match self {
+                    Format::A4 => dither::bit_steps([4, 0, 0, 0]),
+                    Format::P4(palette) => dither::palette_steps(palette),
+                    _ => dither::bit_steps([0, 4, 4, 4])
+                };
+
+                let bytes_per_row = width.div_ceil(2);
+                to_bytes.fill(0);
+                dither::dither_pixels(options.dither, from_pixels, width, height, steps, buffer, |i, pixel| {
+                    let (nibble, decoded) = match (self, &mut lookup) {
+                        (Format::A4, _) => (pixel.as_a4(), Pixel::from_a4(pixel.as_a4())),
+                        (Format::Y4, _) => (pixel.as_y4(), Pixel::from_y4(pixel.as_y4())),
+                        (Format::P4(palette), Some(lookup)) => {
+                            let index = lookup.find(pixel);
+                            (index as u8, palette[index])
+                        },
+                        _ => unreachable!()
+                    };
+
+                    let (x, y) = (i % width, i / width);
+                    let byte = &mut to_bytes[y * bytes_per_row + x / 2];
+                    *byte = *byte & !(0xF << (x % 2 * 4)) | nibble << (x % 2 * 4);
+                    decoded
+                })
+            }
 
             // Simple conversion (uncompressed)
             _ => {
-                let converter = match self {
-                    Format::A8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8()),
-                    Format::Y8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_y8()),
-                    Format::AY8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_ay8()),
-                    Format::A8Y8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8y8()),
-                    Format::R5G6B5 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_r5g6b5()),
-                    Format::A1R5G5B5 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a1r5g5b5()),
-                    Format::A4R4G4B4 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a4r4g4b4()),
-                    Format::X8R8G8B8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_x8r8g8b8()),
-                    Format::A8R8G8B8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8r8g8b8()),
-                    Format::A8B8G8R8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8b8g8r8()),
-                    n => unreachable!("can't convert {:?}", n)
+                let converter = self.pixel_encoder();
+
+                // 1-bit alpha is rounded to the threshold rather than halfway.
+                let alpha_threshold = options.alpha_threshold;
+                let threshold = |pixel: Pixel| match self {
+                    Format::A1R5G5B5 => Pixel { alpha: if pixel.alpha > alpha_threshold { 255 } else { 0 }, ..pixel },
+                    _ => pixel
                 };
 
                 let bytes_per_block = self.block_size_bytes();
-                for (chunk, pixel) in to_bytes.chunks_exact_mut(bytes_per_block).zip(from_pixels.iter()) {
-                    converter(*pixel, chunk);
+                let dither_bits = match self {
+                    Format::R5G6B5 => Some([0, 5, 6, 5]),
+                    Format::A1R5G5B5 => Some([1, 5, 5, 5]),
+                    Format::A4R4G4B4 => Some([4, 4, 4, 4]),
+                    Format::A4Y4 => Some([4, 4, 4, 4]),
+                    _ => None
+                };
+
+                match dither_bits {
+                    Some(bits) if options.dither != Dither::None => {
+                        let decoder = self.pixel_decoder();
+                        dither::dither_pixels(options.dither, from_pixels, width, height, dither::bit_steps(bits), buffer, |i, pixel| {
+                            let chunk = &mut to_bytes[i * bytes_per_block..(i + 1) * bytes_per_block];
+                            converter(threshold(pixel), chunk);
+                            decoder(chunk)
+                        })
+                    },
+                    _ => {
+                        for (chunk, pixel) in to_bytes.chunks_exact_mut(bytes_per_block).zip(from_pixels.iter()) {
+                            converter(threshold(*pixel), chunk);
+                        }
+                    }
                 }
             }
         }
     }
 
-    /// Convert pixels from 8-bit to the output format.
+    /// Convert pixels from the input format to 8-bit.
     ///
     /// # Panics
     ///
     /// This function will panic if:
     /// * `to_bytes.len() != self.requires_bytes(width, height)`
     /// * `pixels.len() != width*height`
+    /// * the format is palettized and the palette has no colors or too many colors
     pub fn decode_pixels(
         self,
         from_bytes: &[u8],
@@ -207,37 +620,75 @@ impl Format {
     ) {
         assert_eq!(self.required_bytes(width, height), from_bytes.len());
         assert_eq!(width*height, to_pixels.len());
+        assert!(self.check_palette().is_ok(), "palette has no colors or too many colors");
+
+        self.decode_pixels_unchecked(from_bytes, to_pixels, width, height)
+    }
 
+    /// Convert pixels from the input format to 8-bit, returning an error instead of panicking if
+    /// the buffers are the wrong size.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::SizeOverflow`] if `width*height` or the required bytes overflow [usize]
+    /// * [`Error::InvalidInputLength`] if `from_bytes.len() != self.required_bytes(width, height)`
+    /// * [`Error::InvalidOutputLength`] if `to_pixels.len() != width*height`
+    /// * [`Error::InvalidPaletteLength`] if the format is palettized and the palette has no colors
+    ///   or too many colors
+    pub fn try_decode_pixels(
+        self,
+        from_bytes: &[u8],
+        to_pixels: &mut [Pixel],
+        width: usize,
+        height: usize
+    ) -> Result<(), Error> {
+        self.check_palette()?;
+        check_length(self.checked_required_bytes(width, height)?, from_bytes.len(), true)?;
+        check_length(width.checked_mul(height).ok_or(Error::SizeOverflow)?, to_pixels.len(), false)?;
+
+        self.decode_pixels_unchecked(from_bytes, to_pixels, width, height);
+        Ok(())
+    }
+
+    fn decode_pixels_unchecked(
+        self,
+        from_bytes: &[u8],
+        to_pixels: &mut [Pixel],
+        width: usize,
+        height: usize
+    ) {
         match self {
             // Compressed formats
-            Format::DXT1 => todo!(),
-            Format::DXT3 => todo!(),
-            Format::DXT5 => todo!(),
-            Format::BC7 => todo!(),
+            Format::DXT1 => decode_blocks(from_bytes, to_pixels, width, height, dxt::decode_bc1_block),
+            Format::DXT3 => decode_blocks(from_bytes, to_pixels, width, height, dxt::decode_bc2_block),
+            Format::DXT5 => decode_blocks(from_bytes, to_pixels, width, height, dxt::decode_bc3_block),
+            Format::BC7 => decode_blocks(from_bytes, to_pixels, width, height, bc7::decode_bc7_block),
 
             // Palettized
             Format::P8(p) => {
                 for (chunk, pixel) in from_bytes.iter().zip(to_pixels.iter_mut()) {
-                    *pixel = p[*chunk as usize]
+                    *pixel = p.get(*chunk as usize).copied().unwrap_or_default()
+                }
+            },
+
+            // 4 bits per pixel
+            Format::A4 | Format::Y4 | Format::P4(_) => {
+                let bytes_per_row = width.div_ceil(2);
+                for (i, pixel) in to_pixels.iter_mut().enumerate() {
+                    let (x, y) = (i % width, i / width);
+                    let nibble = (from_bytes[y * bytes_per_row + x / 2] >> (x % 2 * 4)) & 0xF;
+                    *pixel = match self {
+                        Format::A4 => Pixel::from_a4(nibble),
+                        Format::Y4 => Pixel::from_y4(nibble),
+                        Format::P4(p) => p.get(nibble as usize).copied().unwrap_or_default(),
+                        _ => unreachable!()
+                    };
                 }
             },
 
             // Simple conversion (uncompressed)
             _ => {
-                let converter = match self {
-                    Format::A8 => |from: &[u8]| Pixel::from_a8(from.try_into().unwrap()),
-                    Format::Y8 => |from: &[u8]| Pixel::from_y8(from.try_into().unwrap()),
-                    Format::AY8 => |from: &[u8]| Pixel::from_ay8(from.try_into().unwrap()),
-                    Format::A8Y8 => |from: &[u8]| Pixel::from_a8y8(from.try_into().unwrap()),
-                    Format::R5G6B5 => |from: &[u8]| Pixel::from_r5g6b5(from.try_into().unwrap()),
-                    Format::A1R5G5B5 => |from: &[u8]| Pixel::from_a1r5g5b5(from.try_into().unwrap()),
-                    Format::A4R4G4B4 => |from: &[u8]| Pixel::from_a4r4g4b4(from.try_into().unwrap()),
-                    Format::X8R8G8B8 => |from: &[u8]| Pixel::from_x8r8g8b8(from.try_into().unwrap()),
-                    Format::A8R8G8B8 => |from: &[u8]| Pixel::from_a8r8g8b8(from.try_into().unwrap()),
-                    Format::A8B8G8R8 => |from: &[u8]| Pixel::from_a8b8g8r8(from.try_into().unwrap()),
-                    n => unreachable!("can't convert {:?}", n)
-                };
-
+                let converter = self.pixel_decoder();
                 let bytes_per_block = self.block_size_bytes();
                 for (chunk, pixel) in from_bytes.chunks_exact(bytes_per_block).zip(to_pixels.iter_mut()) {
                     *pixel = converter(chunk);
@@ -245,76 +696,270 @@ impl Format {
             }
         }
     }
+
+    /// Convert every mipmap of an image from 8-bit to the output format.
+    ///
+    /// This is the same as [`encode_mipmaps_with`](Format::encode_mipmaps_with) with the default
+    /// [`EncodeOptions`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if:
+    /// * `to_bytes.len() != self.required_bytes_mipmapped(width, height)`
+    /// * `from_pixels.len() != mipmap_pixel_count(width, height)`
+    /// * the format is palettized and the palette has no colors or too many colors
+    pub fn encode_mipmaps(
+        self,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        width: usize,
+        height: usize
+    ) {
+        self.encode_mipmaps_with(&EncodeOptions::default(), from_pixels, to_bytes, width, height)
+    }
+
+    /// Convert every mipmap of an image from 8-bit to the output format with the given options.
+    ///
+    /// `from_pixels` holds each mipmap one after the other as produced by
+    /// [`generate_mipmaps`](crate::generate_mipmaps), and each mipmap is encoded into `to_bytes`
+    /// one after the other, sized by [`required_bytes`](Format::required_bytes).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if:
+    /// * `to_bytes.len() != self.required_bytes_mipmapped(width, height)`
+    /// * `from_pixels.len() != mipmap_pixel_count(width, height)`
+    /// * the format is palettized and the palette has no colors or too many colors
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{generate_mipmaps, mipmap_pixel_count, Format, MipmapOptions, Pixel};
+    ///
+    /// let image = [Pixel { alpha: 255, red: 255, green: 0, blue: 0 }; 8*8];
+    /// let mut mipmaps = [Pixel::default(); mipmap_pixel_count(8, 8)];
+    /// generate_mipmaps(&MipmapOptions::default(), &image, 8, 8, &mut mipmaps);
+    ///
+    /// let mut bytes = [0u8; Format::DXT1.required_bytes_mipmapped(8, 8)];
+    /// Format::DXT1.encode_mipmaps(&mipmaps, &mut bytes, 8, 8);
+    ///
+    /// let mut decoded = [Pixel::default(); mipmap_pixel_count(8, 8)];
+    /// Format::DXT1.decode_mipmaps(&bytes, &mut decoded, 8, 8);
+    /// assert_eq!(mipmaps, decoded);
+    /// ```
+    pub fn encode_mipmaps_with(
+        self,
+        options: &EncodeOptions,
+        from_pixels: &[Pixel],
+        to_bytes: &mut [u8],
+        width: usize,
+        height: usize
+    ) {
+        assert_eq!(self.required_bytes_mipmapped(width, height), to_bytes.len());
+        assert_eq!(mipmap_pixel_count(width, height), from_pixels.len());
+        assert!(self.check_palette().is_ok(), "palette has no colors or too many colors");
+
+        self.for_each_mipmap(width, height, |pixels, bytes, width, height| {
+            self.encode_pixels_unchecked(options, &from_pixels[pixels], &mut to_bytes[bytes], None, width, height)
+        })
+    }
+
+    /// Convert every mipmap of an image from the input format to 8-bit.
+    ///
+    /// This reverses [`encode_mipmaps_with`](Format::encode_mipmaps_with).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if:
+    /// * `from_bytes.len() != self.required_bytes_mipmapped(width, height)`
+    /// * `to_pixels.len() != mipmap_pixel_count(width, height)`
+    /// * the format is palettized and the palette has no colors or too many colors
+    pub fn decode_mipmaps(
+        self,
+        from_bytes: &[u8],
+        to_pixels: &mut [Pixel],
+        width: usize,
+        height: usize
+    ) {
+        assert_eq!(self.required_bytes_mipmapped(width, height), from_bytes.len());
+        assert_eq!(mipmap_pixel_count(width, height), to_pixels.len());
+        assert!(self.check_palette().is_ok(), "palette has no colors or too many colors");
+
+        self.for_each_mipmap(width, height, |pixels, bytes, width, height| {
+            self.decode_pixels_unchecked(&from_bytes[bytes], &mut to_pixels[pixels], width, height)
+        })
+    }
+
+    /// Call a function with the ranges of pixels and bytes taken by each mipmap, along with its
+    /// width and height.
+    fn for_each_mipmap(
+        self,
+        width: usize,
+        height: usize,
+        mut f: impl FnMut(Range<usize>, Range<usize>, usize, usize)
+    ) {
+        let mut pixel_offset = 0;
+        let mut byte_offset = 0;
+        for level in 0..mipmap_count(width, height) {
+            let (level_width, level_height) = mipmap_size(width, height, level);
+            let pixels = level_width * level_height;
+            let bytes = self.required_bytes(level_width, level_height);
+            f(pixel_offset..pixel_offset + pixels, byte_offset..byte_offset + bytes, level_width, level_height);
+            pixel_offset += pixels;
+            byte_offset += bytes;
+        }
+    }
+
+    /// Return an error if the format is palettized and the palette has no colors or more colors
+    /// than the format can index (256 for `P8` and 16 for `P4`).
+    const fn check_palette(self) -> Result<(), Error> {
+        let (palette, max) = match self {
+            Format::P8(palette) => (palette, 256),
+            Format::P4(palette) => (palette, 16),
+            _ => return Ok(())
+        };
+
+        if palette.is_empty() || palette.len() > max {
+            Err(Error::InvalidPaletteLength { length: palette.len() })
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    /// Get the function that stores a pixel for an uncompressed format.
+    fn pixel_encoder(self) -> fn(Pixel, &mut [u8]) {
+        match self {
+            Format::A8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8()),
+            Format::Y8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_y8()),
+            Format::AY8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_ay8()),
+            Format::A8Y8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8y8()),
+            Format::A4Y4 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a4y4()),
+            Format::R5G6B5 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_r5g6b5()),
+            Format::A1R5G5B5 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a1r5g5b5()),
+            Format::A4R4G4B4 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a4r4g4b4()),
+            Format::X8R8G8B8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_x8r8g8b8()),
+            Format::A8R8G8B8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8r8g8b8()),
+            Format::A8B8G8R8 => |pixel: Pixel, to: &mut [u8]| to.copy_from_slice(&pixel.as_a8b8g8r8()),
+            n => unreachable!("can't convert {:?}", n)
+        }
+    }
+
+    /// Get the function that loads a pixel for an uncompressed format.
+    fn pixel_decoder(self) -> fn(&[u8]) -> Pixel {
+        match self {
+            Format::A8 => |from: &[u8]| Pixel::from_a8(from.try_into().unwrap()),
+            Format::Y8 => |from: &[u8]| Pixel::from_y8(from.try_into().unwrap()),
+            Format::AY8 => |from: &[u8]| Pixel::from_ay8(from.try_into().unwrap()),
+            Format::A8Y8 => |from: &[u8]| Pixel::from_a8y8(from.try_into().unwrap()),
+            Format::A4Y4 => |from: &[u8]| Pixel::from_a4y4(from.try_into().unwrap()),
+            Format::R5G6B5 => |from: &[u8]| Pixel::from_r5g6b5(from.try_into().unwrap()),
+            Format::A1R5G5B5 => |from: &[u8]| Pixel::from_a1r5g5b5(from.try_into().unwrap()),
+            Format::A4R4G4B4 => |from: &[u8]| Pixel::from_a4r4g4b4(from.try_into().unwrap()),
+            Format::X8R8G8B8 => |from: &[u8]| Pixel::from_x8r8g8b8(from.try_into().unwrap()),
+            Format::A8R8G8B8 => |from: &[u8]| Pixel::from_a8r8g8b8(from.try_into().unwrap()),
+            Format::A8B8G8R8 => |from: &[u8]| Pixel::from_a8b8g8r8(from.try_into().unwrap()),
+            n => unreachable!("can't convert {:?}", n)
+        }
+    }
 }
 
 /// Return an iterator that encodes one iterator of pixels into indices that correspond to a
 /// given palette.
 ///
+/// See [`PaletteLookup::new`] for how alpha is handled.
+///
 /// # Panics
 ///
 /// Panics if `palette.is_empty()`
 pub fn encode_palettized<'a, 'b, I: Iterator<Item = &'a Pixel> + 'a + 'b>(
     from_pixels: I,
-    palette: &'b [Pixel]
+    palette: &'b [Pixel],
+    options: &EncodeOptions
 ) -> impl Iterator<Item = usize> + 'a + 'b where 'b: 'a {
-    assert!(palette.len() > 0, "empty palette");
-
-    let mut no_alpha = true;
-    let mut one_bit_alpha = true;
-    let mut alpha_255_exists = false;
-    for i in palette {
-        if i.alpha != 255 {
-            no_alpha = false;
-            if i.alpha != 0 {
-                one_bit_alpha = false;
-            }
-        } else {
-            alpha_255_exists = true;
-        }
-    }
-
-    // If all pixels in the palette are 100% transparent, disregard the alpha when
-    // encoding, because it doesn't make sense to check alpha when finding a pixel.
-    if one_bit_alpha && alpha_255_exists {
-        one_bit_alpha = false;
-        no_alpha = true;
-    }
+    let mut lookup = PaletteLookup::new(palette, options);
+    from_pixels.map(move |pixel| lookup.find(*pixel))
+}
 
-    let get_distance = if one_bit_alpha || no_alpha {
-        Pixel::distance_rgb
-    } else {
-        Pixel::distance_argb
-    };
+/// Layout and precision of a format.
+#[derive(Copy, Clone)]
+struct FormatInfo {
+    block_size_pixels: (usize, usize),
+    block_size_bytes: usize,
+    channel_bits: [u8; 4],
+    kind: FormatKind
+}
 
-    from_pixels
-        .map(move |pixel| {
-            let mut output = None;
-            let mut distance = u32::MAX;
+#[derive(Copy, Clone)]
+enum FormatKind {
+    Compressed,
+    Color,
+    Monochrome,
+    Palettized
+}
 
-            for i in 0..palette.len() {
-                let palette_pixel = palette[i];
+/// Return an error if a buffer's length isn't what was expected.
+pub(crate) fn check_length(expected: usize, actual: usize, input: bool) -> Result<(), Error> {
+    match (expected == actual, input) {
+        (true, _) => Ok(()),
+        (false, true) => Err(Error::InvalidInputLength { expected, actual }),
+        (false, false) => Err(Error::InvalidOutputLength { expected, actual })
+    }
+}
 
-                // For one-bit alpha, consider alpha as binary rather than calculating the difference.
-                if one_bit_alpha {
-                    if pixel.alpha <= 127 && pixel.alpha == 255 {
-                        continue;
-                    }
-                    if pixel.alpha > 127 && pixel.alpha == 0 {
-                        continue;
-                    }
-                }
+/// Encode a block-compressed texture one 4x4 block at a time.
+///
+/// Edge blocks that extend past `width`x`height` are padded by repeating the last row and column.
+fn encode_blocks<const N: usize>(
+    from_pixels: &[Pixel],
+    to_bytes: &mut [u8],
+    width: usize,
+    height: usize,
+    encoder: impl Fn(&[Pixel; 16]) -> [u8; N]
+) {
+    let blocks_width = width.div_ceil(4);
+
+    for (block_index, block) in to_bytes.chunks_exact_mut(N).enumerate() {
+        let block_x = (block_index % blocks_width) * 4;
+        let block_y = (block_index / blocks_width) * 4;
+
+        let mut pixels = [Pixel::default(); 16];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let x = (block_x + i % 4).min(width - 1);
+            let y = (block_y + i / 4).min(height - 1);
+            *pixel = from_pixels[x + y * width];
+        }
 
-                let new_distance = get_distance(palette_pixel, pixel);
+        block.copy_from_slice(&encoder(&pixels));
+    }
+}
 
-                if distance > new_distance {
-                    distance = new_distance;
-                    output = Some(i);
-                }
+/// Decode a block-compressed texture one 4x4 block at a time.
+///
+/// Pixels of edge blocks that fall outside of `width`x`height` are discarded.
+fn decode_blocks<const N: usize>(
+    from_bytes: &[u8],
+    to_pixels: &mut [Pixel],
+    width: usize,
+    height: usize,
+    decoder: fn(&[u8; N]) -> [Pixel; 16]
+) {
+    let blocks_width = width.div_ceil(4);
+
+    for (block_index, block) in from_bytes.chunks_exact(N).enumerate() {
+        let block_x = (block_index % blocks_width) * 4;
+        let block_y = (block_index / blocks_width) * 4;
+        let decoded = decoder(block.try_into().unwrap());
+
+        for (i, pixel) in decoded.into_iter().enumerate() {
+            let x = block_x + i % 4;
+            let y = block_y + i / 4;
+            if x < width && y < height {
+                to_pixels[x + y * width] = pixel;
             }
-
-            output.expect("no pixels found")
-        })
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
@@ -0,0 +1,880 @@
+use crate::pixel::difference_squared;
+use crate::{ColorSpace, EncodeOptions, Pixel};
+use super::fit;
+
+/// Decode a BC7 block.
+///
+/// Blocks using the reserved mode (i.e. the first byte is zero) decode to transparent black.
+pub(super) fn decode_bc7_block(block: &[u8; 16]) -> [Pixel; 16] {
+    let mode = block[0].trailing_zeros() as usize;
+    if mode >= MODES.len() {
+        return [Pixel { alpha: 0, red: 0, green: 0, blue: 0 }; 16];
+    }
+
+    let info = &MODES[mode];
+    let mut reader = BitReader::new(block);
+    reader.skip(mode as u32 + 1);
+
+    let partition = reader.read(info.partition_bits) as usize;
+    let rotation = reader.read(info.rotation_bits);
+    let index_selection = reader.read(info.index_selection_bits);
+
+    // Endpoints are stored channel by channel: red for every endpoint, then green, and so on.
+    let mut endpoints = [[[0u8; 4]; 2]; 3];
+    for channel in 0..4 {
+        let bits = if channel == 3 { info.alpha_bits } else { info.color_bits };
+        for subset in endpoints.iter_mut().take(info.subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = reader.read(bits) as u8;
+            }
+        }
+    }
+
+    let mut pbits = [[0u8; 2]; 3];
+    for subset in pbits.iter_mut().take(info.subsets) {
+        match info.pbits {
+            PBits::None => (),
+            PBits::Unique => {
+                subset[0] = reader.read(1) as u8;
+                subset[1] = reader.read(1) as u8;
+            },
+            PBits::Shared => {
+                let pbit = reader.read(1) as u8;
+                *subset = [pbit, pbit];
+            }
+        }
+    }
+
+    for (subset, pbits) in endpoints.iter_mut().zip(pbits).take(info.subsets) {
+        for (endpoint, pbit) in subset.iter_mut().zip(pbits) {
+            *endpoint = info.unquantize(*endpoint, pbit);
+        }
+    }
+
+    let subsets = partition_subsets(info.subsets, partition);
+    let anchors = anchor_indices(info.subsets, partition);
+
+    let mut primary = [0u8; 16];
+    for (i, index) in primary.iter_mut().enumerate() {
+        let bits = if anchors.contains(&i) { info.index_bits - 1 } else { info.index_bits };
+        *index = reader.read(bits) as u8;
+    }
+
+    let mut secondary = [0u8; 16];
+    if info.secondary_index_bits > 0 {
+        for (i, index) in secondary.iter_mut().enumerate() {
+            let bits = if i == 0 { info.secondary_index_bits - 1 } else { info.secondary_index_bits };
+            *index = reader.read(bits) as u8;
+        }
+    }
+
+    // Pick which set of indices is used for color and which is used for alpha.
+    let (color_indices, color_bits, alpha_indices, alpha_bits) = if info.secondary_index_bits == 0 {
+        (&primary, info.index_bits, &primary, info.index_bits)
+    }
+    else if index_selection == 0 {
+        (&primary, info.index_bits, &secondary, info.secondary_index_bits)
+    }
+    else {
+        (&secondary, info.secondary_index_bits, &primary, info.index_bits)
+    };
+
+    let mut output = [Pixel::default(); 16];
+    for (i, pixel) in output.iter_mut().enumerate() {
+        let [start, end] = endpoints[subsets[i] as usize];
+        let color_weight = weights(color_bits)[color_indices[i] as usize];
+        let alpha_weight = weights(alpha_bits)[alpha_indices[i] as usize];
+
+        let mut channels = [
+            interpolate(start[0], end[0], color_weight),
+            interpolate(start[1], end[1], color_weight),
+            interpolate(start[2], end[2], color_weight),
+            interpolate(start[3], end[3], alpha_weight)
+        ];
+
+        // Rotation swaps alpha with one of the color channels.
+        if rotation > 0 {
+            channels.swap(3, rotation as usize - 1);
+        }
+
+        let [red, green, blue, alpha] = channels;
+        *pixel = Pixel { alpha, red, green, blue };
+    }
+
+    output
+}
+
+/// Speed and quality trade-off used when encoding BC7.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum BC7Profile {
+    /// Only use mode 6 (one subset with 4-bit indices).
+    ///
+    /// This is intended for quick previews.
+    Fast,
+
+    /// Also try the most promising partitions of modes 1 and 3 for opaque blocks, and modes 4, 5,
+    /// and 7 for blocks with alpha.
+    #[default]
+    Balanced,
+
+    /// Try every mode, rotation, and index selection, along with more partitions and refinement
+    /// passes.
+    ///
+    /// This is intended for final builds.
+    Thorough
+}
+
+/// Search parameters of a [`BC7Profile`].
+struct ProfileSettings {
+    /// Modes to try for blocks that are fully opaque.
+    opaque_modes: &'static [usize],
+
+    /// Modes to try for blocks with alpha.
+    alpha_modes: &'static [usize],
+
+    /// Number of partitions to try for modes with more than one subset.
+    partitions: usize,
+
+    /// Try all rotations and index selections for modes 4 and 5.
+    all_rotations: bool,
+
+    /// Number of least squares refinement passes done after the principal axis fit.
+    refine_iterations: usize
+}
+
+impl BC7Profile {
+    const fn settings(self) -> ProfileSettings {
+        match self {
+            BC7Profile::Fast => ProfileSettings {
+                opaque_modes: &[6],
+                alpha_modes: &[6],
+                partitions: 0,
+                all_rotations: false,
+                refine_iterations: 1
+            },
+            BC7Profile::Balanced => ProfileSettings {
+                opaque_modes: &[6, 1, 3],
+                alpha_modes: &[6, 5, 4, 7],
+                partitions: 4,
+                all_rotations: false,
+                refine_iterations: 2
+            },
+            BC7Profile::Thorough => ProfileSettings {
+                opaque_modes: &[6, 0, 1, 2, 3, 4, 5],
+                alpha_modes: &[6, 5, 4, 7],
+                partitions: 16,
+                all_rotations: true,
+                refine_iterations: 4
+            }
+        }
+    }
+}
+
+/// Encode a BC7 block, keeping whichever mode searched by the profile has the lowest error.
+pub(super) fn encode_bc7_block(pixels: &[Pixel; 16], options: &EncodeOptions) -> [u8; 16] {
+    let block = pixels.map(|p| [p.red, p.green, p.blue, p.alpha]);
+    let opaque = pixels.iter().all(|p| p.alpha == 255);
+    let settings = options.bc7_profile.settings();
+
+    // Weight each channel by the metric, using the mean red of the whole block for redmean.
+    let mean_red = pixels.iter().map(|p| p.red as u32).sum::<u32>() / 16;
+    let [red, green, blue] = options.metric.channel_weights(mean_red);
+    let weights = [red, green, blue, options.metric.alpha_weight()];
+
+    // Color error is measured in linear light for sRGB, but alpha is always linear.
+    let srgb = options.color_space == ColorSpace::Srgb;
+    let srgb = [srgb, srgb, srgb, false];
+
+    let mut best = Encoded { block: [0; 16], error: u32::MAX };
+    let modes = if opaque { settings.opaque_modes } else { settings.alpha_modes };
+
+    for &mode in modes {
+        let info = &MODES[mode];
+        if info.rotation_bits > 0 {
+            let rotations = if settings.all_rotations { 4 } else { 1 };
+            let index_selections = if settings.all_rotations { 1 << info.index_selection_bits } else { 1 };
+            for rotation in 0..rotations {
+                for index_selection in 0..index_selections {
+                    best.keep(encode_separate(&block, mode, rotation, index_selection, &settings, weights, srgb));
+                }
+            }
+        }
+        else if info.subsets > 1 {
+            let (partitions, count) = best_partitions(&block, mode, settings.partitions, weights);
+            for &partition in &partitions[..count] {
+                best.keep(encode_joint(&block, mode, partition, &settings, weights, srgb));
+            }
+        }
+        else {
+            best.keep(encode_joint(&block, mode, 0, &settings, weights, srgb));
+        }
+    }
+
+    best.block
+}
+
+/// An encoded block along with its total weighted squared error.
+struct Encoded {
+    block: [u8; 16],
+    error: u32
+}
+
+impl Encoded {
+    fn keep(&mut self, other: Encoded) {
+        if other.error < self.error {
+            *self = other;
+        }
+    }
+}
+
+/// Estimate which partitions fit the block best by how well each subset fits a line.
+///
+/// Returns the partition numbers along with how many there are.
+fn best_partitions(block: &[[u8; 4]; 16], mode: usize, limit: usize, weights: [u32; 4]) -> ([usize; 64], usize) {
+    let info = &MODES[mode];
+    let partition_count = 1 << info.partition_bits;
+    let channels = joint_channels(info, weights);
+    let channel_weights = channels.map(|w| w as f32);
+
+    let mut estimates = [(f32::MAX, 0usize); 64];
+    for (partition, estimate) in estimates.iter_mut().enumerate().take(partition_count) {
+        let subsets = partition_subsets(info.subsets, partition);
+        let mut error = 0.0;
+        for subset in 0..info.subsets {
+            let (points, count) = subset_points(block, &subsets, subset as u8, channels);
+            error += fit::line_fit_error(&points[..count], &channel_weights);
+        }
+        *estimate = (error, partition);
+    }
+
+    estimates[..partition_count].sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+    let count = limit.min(partition_count);
+    let mut partitions = [0usize; 64];
+    for (partition, estimate) in partitions.iter_mut().zip(estimates).take(count) {
+        *partition = estimate.1;
+    }
+    (partitions, count)
+}
+
+/// Encode a block using a mode where color and alpha share indices (modes 0-3, 6 and 7).
+fn encode_joint(
+    block: &[[u8; 4]; 16],
+    mode: usize,
+    partition: usize,
+    settings: &ProfileSettings,
+    weights: [u32; 4],
+    srgb: [bool; 4]
+) -> Encoded {
+    let info = &MODES[mode];
+    let subsets = partition_subsets(info.subsets, partition);
+    let anchors = anchor_indices(info.subsets, partition);
+    let channels = joint_channels(info, weights);
+
+    let mut fits = [SubsetFit::default(); 3];
+    let mut error = 0;
+    for (subset, fit) in fits.iter_mut().enumerate().take(info.subsets) {
+        *fit = fit_subset(block, &subsets, subset as u8, channels, srgb, info, info.index_bits, settings.refine_iterations);
+        fit.fix_anchor(&subsets, subset as u8, anchors[subset], info.index_bits);
+        error += fit.error;
+    }
+
+    // Modes without alpha always decode to fully opaque.
+    if info.alpha_bits == 0 {
+        error += block.iter().map(|p| (255 - p[3] as u32).pow(2) * weights[3]).sum::<u32>();
+    }
+
+    let mut endpoints = [[[0u8; 4]; 2]; 3];
+    let mut pbits = [[0u8; 2]; 3];
+    let mut indices = [0u8; 16];
+    for subset in 0..info.subsets {
+        endpoints[subset] = fits[subset].endpoints;
+        pbits[subset] = fits[subset].pbits;
+    }
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = fits[subsets[i] as usize].indices[i];
+    }
+
+    let block = pack_block(mode, partition, 0, 0, &endpoints, &pbits, &indices, &[0; 16]);
+    Encoded { block, error }
+}
+
+/// Encode a block using a mode with separate color and alpha indices (modes 4 and 5).
+fn encode_separate(
+    block: &[[u8; 4]; 16],
+    mode: usize,
+    rotation: u32,
+    index_selection: u32,
+    settings: &ProfileSettings,
+    weights: [u32; 4],
+    srgb: [bool; 4]
+) -> Encoded {
+    let info = &MODES[mode];
+
+    // Rotation swaps alpha with one of the color channels, so do the same before fitting.
+    let mut rotated = *block;
+    let mut weights = weights;
+    let mut srgb = srgb;
+    if rotation > 0 {
+        for pixel in rotated.iter_mut() {
+            pixel.swap(3, rotation as usize - 1);
+        }
+        weights.swap(3, rotation as usize - 1);
+        srgb.swap(3, rotation as usize - 1);
+    }
+    let [red, green, blue, alpha] = weights;
+
+    let (color_index_bits, alpha_index_bits) = if index_selection == 0 {
+        (info.index_bits, info.secondary_index_bits)
+    }
+    else {
+        (info.secondary_index_bits, info.index_bits)
+    };
+
+    let subsets = [0u8; 16];
+    let refine = settings.refine_iterations;
+    let mut color = fit_subset(&rotated, &subsets, 0, [red, green, blue, 0], srgb, info, color_index_bits, refine);
+    let mut alpha = fit_subset(&rotated, &subsets, 0, [0, 0, 0, alpha], srgb, info, alpha_index_bits, refine);
+    color.fix_anchor(&subsets, 0, 0, color_index_bits);
+    alpha.fix_anchor(&subsets, 0, 0, alpha_index_bits);
+
+    let mut endpoints = [[[0u8; 4]; 2]; 3];
+    for (endpoint, (color, alpha)) in endpoints[0].iter_mut().zip(color.endpoints.iter().zip(alpha.endpoints)) {
+        *endpoint = [color[0], color[1], color[2], alpha[3]];
+    }
+
+    let (primary, secondary) = if index_selection == 0 {
+        (&color.indices, &alpha.indices)
+    }
+    else {
+        (&alpha.indices, &color.indices)
+    };
+
+    let block = pack_block(mode, 0, rotation, index_selection, &endpoints, &[[0; 2]; 3], primary, secondary);
+    Encoded { block, error: color.error + alpha.error }
+}
+
+/// Endpoints and indices fitted for one subset.
+#[derive(Copy, Clone, Default)]
+struct SubsetFit {
+    /// Endpoint components before p-bits are applied
+    endpoints: [[u8; 4]; 2],
+    pbits: [u8; 2],
+
+    /// Index of each pixel (only valid for pixels in the subset)
+    indices: [u8; 16],
+    error: u32
+}
+
+impl SubsetFit {
+    /// Swap the endpoints if needed so that the anchor index's most significant bit is zero.
+    fn fix_anchor(&mut self, subsets: &[u8; 16], subset: u8, anchor: usize, index_bits: u32) {
+        let max_index = (1 << index_bits) - 1;
+        if self.indices[anchor] <= max_index / 2 {
+            return
+        }
+
+        self.endpoints.swap(0, 1);
+        self.pbits.swap(0, 1);
+        for (index, _) in self.indices.iter_mut().zip(subsets).filter(|(_, s)| **s == subset) {
+            *index = max_index - *index;
+        }
+    }
+}
+
+/// Get the weights of the channels fitted by a mode where color and alpha share indices.
+///
+/// Modes without alpha don't fit alpha at all, so its weight is zero.
+fn joint_channels(info: &ModeInfo, weights: [u32; 4]) -> [u32; 4] {
+    if info.alpha_bits > 0 {
+        weights
+    }
+    else {
+        [weights[0], weights[1], weights[2], 0]
+    }
+}
+
+/// Get the pixels of a subset as points, with unused (zero weight) channels set to zero.
+fn subset_points(block: &[[u8; 4]; 16], subsets: &[u8; 16], subset: u8, channels: [u32; 4]) -> ([[f32; 4]; 16], usize) {
+    let mut points = [[0.0f32; 4]; 16];
+    let mut count = 0;
+    for (pixel, _) in block.iter().zip(subsets).filter(|(_, s)| **s == subset) {
+        for channel in 0..4 {
+            if channels[channel] > 0 {
+                points[count][channel] = pixel[channel] as f32;
+            }
+        }
+        count += 1;
+    }
+    (points, count)
+}
+
+/// Fit the endpoints and indices of one subset for the channels with a nonzero weight.
+///
+/// Error is measured in linear light for channels marked as `srgb`.
+#[allow(clippy::too_many_arguments)]
+fn fit_subset(
+    block: &[[u8; 4]; 16],
+    subsets: &[u8; 16],
+    subset: u8,
+    channels: [u32; 4],
+    srgb: [bool; 4],
+    info: &ModeInfo,
+    index_bits: u32,
+    refine_iterations: usize
+) -> SubsetFit {
+    let (points, count) = subset_points(block, subsets, subset, channels);
+    let (start, end) = fit::principal_axis_endpoints(&points[..count], &channels.map(|w| w as f32));
+    let mut best = quantize_subset(block, subsets, subset, channels, srgb, info, index_bits, start, end);
+
+    // Refine the endpoints with a least squares fit of the chosen indices.
+    for _ in 0..refine_iterations {
+        let weights = weights(index_bits);
+        let mut point_weights = [0.0f32; 16];
+        for (weight, (index, _)) in point_weights.iter_mut().zip(best.indices.iter().zip(subsets).filter(|(_, s)| **s == subset)) {
+            *weight = weights[*index as usize] as f32 / 64.0;
+        }
+
+        let Some((start, end)) = fit::least_squares_endpoints(&points[..count], &point_weights[..count]) else {
+            break
+        };
+
+        let refined = quantize_subset(block, subsets, subset, channels, srgb, info, index_bits, start, end);
+        if refined.error >= best.error {
+            break
+        }
+        best = refined;
+    }
+
+    best
+}
+
+/// Quantize the endpoints with every allowed p-bit combination and pick the best indices for each,
+/// keeping whichever has the lowest error.
+#[allow(clippy::too_many_arguments)]
+fn quantize_subset(
+    block: &[[u8; 4]; 16],
+    subsets: &[u8; 16],
+    subset: u8,
+    channels: [u32; 4],
+    srgb: [bool; 4],
+    info: &ModeInfo,
+    index_bits: u32,
+    start: [f32; 4],
+    end: [f32; 4]
+) -> SubsetFit {
+    let pbit_combinations: &[[u8; 2]] = match info.pbits {
+        PBits::None => &[[0, 0]],
+        PBits::Unique => &[[0, 0], [0, 1], [1, 0], [1, 1]],
+        PBits::Shared => &[[0, 0], [1, 1]]
+    };
+    let weights = weights(index_bits);
+
+    let mut best = SubsetFit { error: u32::MAX, ..Default::default() };
+    for &pbits in pbit_combinations {
+        let endpoints = [
+            quantize_endpoint(start, info, pbits[0]),
+            quantize_endpoint(end, info, pbits[1])
+        ];
+        let [start8, end8] = [info.unquantize(endpoints[0], pbits[0]), info.unquantize(endpoints[1], pbits[1])];
+
+        let mut palette = [[0u8; 4]; 16];
+        for (entry, &weight) in palette.iter_mut().zip(weights) {
+            for channel in 0..4 {
+                entry[channel] = interpolate(start8[channel], end8[channel], weight);
+            }
+        }
+
+        let mut fit = SubsetFit { endpoints, pbits, indices: [0; 16], error: 0 };
+        for (i, pixel) in block.iter().enumerate().filter(|(i, _)| subsets[*i] == subset) {
+            let (index, distance) = palette[..weights.len()]
+                .iter()
+                .map(|entry| {
+                    (0..4)
+                        .map(|c| difference_squared(entry[c], pixel[c], srgb[c]) * channels[c])
+                        .sum::<u32>()
+                })
+                .enumerate()
+                .min_by_key(|(_, distance)| *distance)
+                .unwrap();
+            fit.indices[i] = index as u8;
+            fit.error += distance;
+        }
+
+        if fit.error < best.error {
+            best = fit;
+        }
+    }
+
+    best
+}
+
+/// Quantize an endpoint to the mode's precision, given its p-bit.
+fn quantize_endpoint(endpoint: [f32; 4], info: &ModeInfo, pbit: u8) -> [u8; 4] {
+    let mut output = [0u8; 4];
+    for (channel, component) in output.iter_mut().enumerate() {
+        let bits = if channel == 3 { info.alpha_bits } else { info.color_bits };
+        if bits == 0 {
+            continue
+        }
+
+        let value = endpoint[channel].clamp(0.0, 255.0);
+        let max = (1u32 << bits) - 1;
+        let expand = |c: u32| match info.pbits {
+            PBits::None => expand_bits(c as u8, bits),
+            _ => expand_bits(((c as u8) << 1) | pbit, bits + 1)
+        };
+
+        // Round to the nearest level, then check its neighbors since expansion isn't linear.
+        let guess = match info.pbits {
+            PBits::None => (value * max as f32 / 255.0 + 0.5) as u32,
+            _ => ((value * (2 * max + 1) as f32 / 255.0 - pbit as f32) / 2.0 + 0.5).max(0.0) as u32
+        }.min(max);
+
+        *component = (guess.saturating_sub(1)..=(guess + 1).min(max))
+            .min_by(|a, b| (expand(*a) as f32 - value).abs().total_cmp(&(expand(*b) as f32 - value).abs()))
+            .unwrap() as u8;
+    }
+    output
+}
+
+/// Write a block's fields in the order defined by its mode.
+#[allow(clippy::too_many_arguments)]
+fn pack_block(
+    mode: usize,
+    partition: usize,
+    rotation: u32,
+    index_selection: u32,
+    endpoints: &[[[u8; 4]; 2]; 3],
+    pbits: &[[u8; 2]; 3],
+    primary: &[u8; 16],
+    secondary: &[u8; 16]
+) -> [u8; 16] {
+    let info = &MODES[mode];
+    let mut writer = BitWriter::new();
+    writer.write(1 << mode, mode as u32 + 1);
+    writer.write(partition as u32, info.partition_bits);
+    writer.write(rotation, info.rotation_bits);
+    writer.write(index_selection, info.index_selection_bits);
+
+    for channel in 0..4 {
+        let bits = if channel == 3 { info.alpha_bits } else { info.color_bits };
+        for subset in endpoints.iter().take(info.subsets) {
+            for endpoint in subset {
+                writer.write(endpoint[channel] as u32, bits);
+            }
+        }
+    }
+
+    for subset in pbits.iter().take(info.subsets) {
+        match info.pbits {
+            PBits::None => (),
+            PBits::Unique => {
+                writer.write(subset[0] as u32, 1);
+                writer.write(subset[1] as u32, 1);
+            },
+            PBits::Shared => writer.write(subset[0] as u32, 1)
+        }
+    }
+
+    let anchors = anchor_indices(info.subsets, partition);
+    for (i, &index) in primary.iter().enumerate() {
+        let bits = if anchors.contains(&i) { info.index_bits - 1 } else { info.index_bits };
+        writer.write(index as u32, bits);
+    }
+
+    if info.secondary_index_bits > 0 {
+        for (i, &index) in secondary.iter().enumerate() {
+            let bits = if i == 0 { info.secondary_index_bits - 1 } else { info.secondary_index_bits };
+            writer.write(index as u32, bits);
+        }
+    }
+
+    writer.finish()
+}
+
+/// Describes how a BC7 mode lays out its bits.
+struct ModeInfo {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    pbits: PBits,
+    index_bits: u32,
+    secondary_index_bits: u32
+}
+
+impl ModeInfo {
+    /// Apply the p-bit (if any) and expand each channel of an endpoint to 8 bits.
+    ///
+    /// Channels without any bits (i.e. alpha in modes 0-3) are set to 255.
+    fn unquantize(&self, endpoint: [u8; 4], pbit: u8) -> [u8; 4] {
+        let mut output = [0u8; 4];
+        for (channel, value) in output.iter_mut().enumerate() {
+            let bits = if channel == 3 { self.alpha_bits } else { self.color_bits };
+            if bits == 0 {
+                *value = 255;
+                continue
+            }
+
+            let (component, bits) = match self.pbits {
+                PBits::None => (endpoint[channel], bits),
+                _ => ((endpoint[channel] << 1) | pbit, bits + 1)
+            };
+            *value = expand_bits(component, bits);
+        }
+        output
+    }
+}
+
+/// How p-bits (the shared least significant bit of each channel of an endpoint) are stored.
+#[derive(Copy, Clone, PartialEq)]
+enum PBits {
+    None,
+    Unique,
+    Shared
+}
+
+const MODES: [ModeInfo; 8] = [
+    ModeInfo { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, pbits: PBits::Unique, index_bits: 3, secondary_index_bits: 0 },
+    ModeInfo { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, pbits: PBits::Shared, index_bits: 3, secondary_index_bits: 0 },
+    ModeInfo { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, pbits: PBits::None, index_bits: 2, secondary_index_bits: 0 },
+    ModeInfo { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, pbits: PBits::Unique, index_bits: 2, secondary_index_bits: 0 },
+    ModeInfo { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, pbits: PBits::None, index_bits: 2, secondary_index_bits: 3 },
+    ModeInfo { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, pbits: PBits::None, index_bits: 2, secondary_index_bits: 2 },
+    ModeInfo { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, pbits: PBits::Unique, index_bits: 4, secondary_index_bits: 0 },
+    ModeInfo { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, pbits: PBits::Unique, index_bits: 2, secondary_index_bits: 0 },
+];
+
+/// Reads bits from a block, starting from the least significant bit of the first byte.
+struct BitReader {
+    bits: u128,
+    position: u32
+}
+
+impl BitReader {
+    fn new(block: &[u8; 16]) -> Self {
+        Self { bits: u128::from_le_bytes(*block), position: 0 }
+    }
+
+    fn skip(&mut self, count: u32) {
+        self.position += count;
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0
+        }
+        let value = (self.bits >> self.position) as u32 & ((1 << count) - 1);
+        self.position += count;
+        value
+    }
+}
+
+/// Writes bits into a block, starting from the least significant bit of the first byte.
+struct BitWriter {
+    bits: u128,
+    position: u32
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bits: 0, position: 0 }
+    }
+
+    fn write(&mut self, value: u32, count: u32) {
+        if count == 0 {
+            return
+        }
+        self.bits |= ((value & ((1 << count) - 1)) as u128) << self.position;
+        self.position += count;
+    }
+
+    fn finish(self) -> [u8; 16] {
+        debug_assert_eq!(128, self.position);
+        self.bits.to_le_bytes()
+    }
+}
+
+/// Expand an n-bit value to 8 bits by replicating its most significant bits.
+const fn expand_bits(value: u8, bits: u32) -> u8 {
+    if bits >= 8 {
+        value
+    }
+    else {
+        (value << (8 - bits)) | (value >> (2 * bits - 8))
+    }
+}
+
+const fn interpolate(start: u8, end: u8, weight: u8) -> u8 {
+    (((64 - weight as u16) * start as u16 + weight as u16 * end as u16 + 32) >> 6) as u8
+}
+
+const fn weights(bits: u32) -> &'static [u8] {
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        4 => &WEIGHTS_4,
+        _ => unreachable!()
+    }
+}
+
+const WEIGHTS_2: [u8; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u8; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u8; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Get the subset each pixel belongs to.
+fn partition_subsets(subsets: usize, partition: usize) -> [u8; 16] {
+    match subsets {
+        1 => [0; 16],
+        2 => PARTITIONS_2[partition],
+        3 => PARTITIONS_3[partition],
+        _ => unreachable!()
+    }
+}
+
+/// Get the anchor pixel of each subset (the pixel whose index omits the most significant bit).
+///
+/// Unused subsets are given the anchor of subset 0.
+fn anchor_indices(subsets: usize, partition: usize) -> [usize; 3] {
+    match subsets {
+        1 => [0, 0, 0],
+        2 => [0, ANCHORS_2[partition] as usize, 0],
+        3 => [0, ANCHORS_3_1[partition] as usize, ANCHORS_3_2[partition] as usize],
+        _ => unreachable!()
+    }
+}
+
+/// Two-subset partitions, where bit `n` is the subset of pixel `n`.
+const PARTITION_MASKS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+const PARTITIONS_2: [[u8; 16]; 64] = {
+    let mut partitions = [[0u8; 16]; 64];
+    let mut partition = 0;
+    while partition < 64 {
+        let mut pixel = 0;
+        while pixel < 16 {
+            partitions[partition][pixel] = ((PARTITION_MASKS_2[partition] >> pixel) & 1) as u8;
+            pixel += 1;
+        }
+        partition += 1;
+    }
+    partitions
+};
+
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0,0,1,1, 0,0,1,1, 0,2,2,1, 2,2,2,2],
+    [0,0,0,1, 0,0,1,1, 2,2,1,1, 2,2,2,1],
+    [0,0,0,0, 2,0,0,1, 2,2,1,1, 2,2,1,1],
+    [0,2,2,2, 0,0,2,2, 0,0,1,1, 0,1,1,1],
+    [0,0,0,0, 0,0,0,0, 1,1,2,2, 1,1,2,2],
+    [0,0,1,1, 0,0,1,1, 0,0,2,2, 0,0,2,2],
+    [0,0,2,2, 0,0,2,2, 1,1,1,1, 1,1,1,1],
+    [0,0,1,1, 0,0,1,1, 2,2,1,1, 2,2,1,1],
+    [0,0,0,0, 0,0,0,0, 1,1,1,1, 2,2,2,2],
+    [0,0,0,0, 1,1,1,1, 1,1,1,1, 2,2,2,2],
+    [0,0,0,0, 1,1,1,1, 2,2,2,2, 2,2,2,2],
+    [0,0,1,2, 0,0,1,2, 0,0,1,2, 0,0,1,2],
+    [0,1,1,2, 0,1,1,2, 0,1,1,2, 0,1,1,2],
+    [0,1,2,2, 0,1,2,2, 0,1,2,2, 0,1,2,2],
+    [0,0,1,1, 0,1,1,2, 1,1,2,2, 1,2,2,2],
+    [0,0,1,1, 2,0,0,1, 2,2,0,0, 2,2,2,0],
+    [0,0,0,1, 0,0,1,1, 0,1,1,2, 1,1,2,2],
+    [0,1,1,1, 0,0,1,1, 2,0,0,1, 2,2,0,0],
+    [0,0,0,0, 1,1,2,2, 1,1,2,2, 1,1,2,2],
+    [0,0,2,2, 0,0,2,2, 0,0,2,2, 1,1,1,1],
+    [0,1,1,1, 0,1,1,1, 0,2,2,2, 0,2,2,2],
+    [0,0,0,1, 0,0,0,1, 2,2,2,1, 2,2,2,1],
+    [0,0,0,0, 0,0,1,1, 0,1,2,2, 0,1,2,2],
+    [0,0,0,0, 1,1,0,0, 2,2,1,0, 2,2,1,0],
+    [0,1,2,2, 0,1,2,2, 0,0,1,1, 0,0,0,0],
+    [0,0,1,2, 0,0,1,2, 1,1,2,2, 2,2,2,2],
+    [0,1,1,0, 1,2,2,1, 1,2,2,1, 0,1,1,0],
+    [0,0,0,0, 0,1,1,0, 1,2,2,1, 1,2,2,1],
+    [0,0,2,2, 1,1,0,2, 1,1,0,2, 0,0,2,2],
+    [0,1,1,0, 0,1,1,0, 2,0,0,2, 2,2,2,2],
+    [0,0,1,1, 0,1,2,2, 0,1,2,2, 0,0,1,1],
+    [0,0,0,0, 2,0,0,0, 2,2,1,1, 2,2,2,1],
+    [0,0,0,0, 0,0,0,2, 1,1,2,2, 1,2,2,2],
+    [0,2,2,2, 0,0,2,2, 0,0,1,2, 0,0,1,1],
+    [0,0,1,1, 0,0,1,2, 0,0,2,2, 0,2,2,2],
+    [0,1,2,0, 0,1,2,0, 0,1,2,0, 0,1,2,0],
+    [0,0,0,0, 1,1,1,1, 2,2,2,2, 0,0,0,0],
+    [0,1,2,0, 1,2,0,1, 2,0,1,2, 0,1,2,0],
+    [0,1,2,0, 2,0,1,2, 1,2,0,1, 0,1,2,0],
+    [0,0,1,1, 2,2,0,0, 1,1,2,2, 0,0,1,1],
+    [0,0,1,1, 1,1,2,2, 2,2,0,0, 0,0,1,1],
+    [0,1,0,1, 0,1,0,1, 2,2,2,2, 2,2,2,2],
+    [0,0,0,0, 0,0,0,0, 2,1,2,1, 2,1,2,1],
+    [0,0,2,2, 1,1,2,2, 0,0,2,2, 1,1,2,2],
+    [0,0,2,2, 0,0,1,1, 0,0,2,2, 0,0,1,1],
+    [0,2,2,0, 1,2,2,1, 0,2,2,0, 1,2,2,1],
+    [0,1,0,1, 2,2,2,2, 2,2,2,2, 0,1,0,1],
+    [0,0,0,0, 2,1,2,1, 2,1,2,1, 2,1,2,1],
+    [0,1,0,1, 0,1,0,1, 0,1,0,1, 2,2,2,2],
+    [0,2,2,2, 0,1,1,1, 0,2,2,2, 0,1,1,1],
+    [0,0,0,2, 1,1,1,2, 0,0,0,2, 1,1,1,2],
+    [0,0,0,0, 2,1,1,2, 2,1,1,2, 2,1,1,2],
+    [0,2,2,2, 0,1,1,1, 0,1,1,1, 0,2,2,2],
+    [0,0,0,2, 1,1,1,2, 1,1,1,2, 0,0,0,2],
+    [0,1,1,0, 0,1,1,0, 0,1,1,0, 2,2,2,2],
+    [0,0,0,0, 0,0,0,0, 2,1,1,2, 2,1,1,2],
+    [0,1,1,0, 0,1,1,0, 2,2,2,2, 2,2,2,2],
+    [0,0,2,2, 0,0,1,1, 0,0,1,1, 0,0,2,2],
+    [0,0,2,2, 1,1,2,2, 1,1,2,2, 0,0,2,2],
+    [0,0,0,0, 0,0,0,0, 0,0,0,0, 2,1,1,2],
+    [0,0,0,2, 0,0,0,1, 0,0,0,2, 0,0,0,1],
+    [0,2,2,2, 1,2,2,2, 0,2,2,2, 1,2,2,2],
+    [0,1,0,1, 2,2,2,2, 2,2,2,2, 2,2,2,2],
+    [0,1,1,1, 2,0,1,1, 2,2,0,1, 2,2,2,0],
+];
+
+/// Anchor pixel of the second subset of each two-subset partition.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15,  2,  8,  2,  2,  8,  8, 15,
+     2,  8,  2,  2,  8,  8,  2,  2,
+    15, 15,  6,  8,  2,  8, 15, 15,
+     2,  8,  2,  2,  2, 15, 15,  6,
+     6,  2,  6,  8, 15, 15,  2,  2,
+    15, 15, 15, 15, 15,  2,  2, 15,
+];
+
+/// Anchor pixel of the second subset of each three-subset partition.
+const ANCHORS_3_1: [u8; 64] = [
+     3,  3, 15, 15,  8,  3, 15, 15,
+     8,  8,  6,  6,  6,  5,  3,  3,
+     3,  3,  8, 15,  3,  3,  6, 10,
+     5,  8,  8,  6,  8,  5, 15, 15,
+     8, 15,  3,  5,  6, 10,  8, 15,
+    15,  3, 15,  5, 15, 15, 15, 15,
+     3, 15,  5,  5,  5,  8,  5, 10,
+     5, 10,  8, 13, 15, 12,  3,  3,
+];
+
+/// Anchor pixel of the third subset of each three-subset partition.
+const ANCHORS_3_2: [u8; 64] = [
+    15,  8,  8,  3, 15, 15,  3,  8,
+    15, 15, 15, 15, 15, 15, 15,  8,
+    15,  8, 15,  3, 15,  8, 15,  8,
+     3, 15,  6, 10, 15, 15, 10,  8,
+    15,  3, 15, 10, 10,  8,  9, 10,
+     6, 15,  8, 15,  3,  6,  6,  8,
+    15,  3, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15,  3, 15, 15,  8,
+];
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,248 @@
+use super::*;
+
+#[test]
+pub fn anchors_are_in_their_subsets() {
+    for partition in 0..64 {
+        assert_eq!(0, PARTITIONS_2[partition][0]);
+        assert_eq!(1, PARTITIONS_2[partition][ANCHORS_2[partition] as usize], "partition {partition}");
+
+        assert_eq!(0, PARTITIONS_3[partition][0]);
+        assert_eq!(1, PARTITIONS_3[partition][ANCHORS_3_1[partition] as usize], "partition {partition}");
+        assert_eq!(2, PARTITIONS_3[partition][ANCHORS_3_2[partition] as usize], "partition {partition}");
+    }
+}
+
+#[test]
+pub fn modes_are_128_bits() {
+    for (mode, info) in MODES.iter().enumerate() {
+        let channels = if info.alpha_bits > 0 { 3 * info.color_bits + info.alpha_bits } else { 3 * info.color_bits };
+        let pbits = match info.pbits {
+            PBits::None => 0,
+            PBits::Unique => 2,
+            PBits::Shared => 1
+        };
+        let subsets = info.subsets as u32;
+        let secondary = if info.secondary_index_bits > 0 { 16 * info.secondary_index_bits - 1 } else { 0 };
+
+        let total = mode as u32 + 1
+            + info.partition_bits
+            + info.rotation_bits
+            + info.index_selection_bits
+            + subsets * (2 * channels + pbits)
+            + 16 * info.index_bits - subsets
+            + secondary;
+        assert_eq!(128, total, "mode {mode}");
+    }
+}
+
+#[test]
+pub fn decode_reserved_mode() {
+    assert_eq!([Pixel { alpha: 0, red: 0, green: 0, blue: 0 }; 16], decode_bc7_block(&[0; 16]));
+}
+
+#[test]
+pub fn decode_mode_6() {
+    let mut writer = BitWriter::new();
+    writer.write(1 << 6, 7);
+
+    // RGBA endpoints: (0, 0, 0, 0) to (127, 127, 127, 127), then p-bits 0 and 1
+    for _ in 0..4 {
+        writer.write(0, 7);
+        writer.write(127, 7);
+    }
+    writer.write(0, 1);
+    writer.write(1, 1);
+
+    // Index i for pixel i (the anchor index has one less bit)
+    writer.write(0, 3);
+    for i in 1..16 {
+        writer.write(i, 4);
+    }
+
+    let pixels = decode_bc7_block(&writer.finish());
+    for (i, pixel) in pixels.into_iter().enumerate() {
+        let value = ((WEIGHTS_4[i] as u16 * 255 + 32) >> 6) as u8;
+        assert_eq!(Pixel { alpha: value, red: value, green: value, blue: value }, pixel);
+    }
+}
+
+#[test]
+pub fn decode_mode_5_rotation() {
+    let mut writer = BitWriter::new();
+    writer.write(1 << 5, 6);
+
+    // Rotation 1 swaps red and alpha
+    writer.write(1, 2);
+
+    // Color endpoints: red 127-127, green 0-0, blue 64-64
+    writer.write(127, 7);
+    writer.write(127, 7);
+    writer.write(0, 7);
+    writer.write(0, 7);
+    writer.write(64, 7);
+    writer.write(64, 7);
+
+    // Alpha endpoints: 0 to 255
+    writer.write(0, 8);
+    writer.write(255, 8);
+
+    // Color indices are all 0, alpha indices are all 1
+    writer.write(0, 31);
+    writer.write(1, 1);
+    for _ in 1..16 {
+        writer.write(1, 2);
+    }
+
+    let pixels = decode_bc7_block(&writer.finish());
+    let alpha = interpolate(0, 255, WEIGHTS_2[1]);
+    assert_eq!([Pixel { alpha: 255, red: alpha, green: 0, blue: 129 }; 16], pixels);
+}
+
+#[test]
+pub fn decode_mode_4_index_selection() {
+    let mut writer = BitWriter::new();
+    writer.write(1 << 4, 5);
+    writer.write(0, 2);
+
+    // Index selection 1: 3-bit indices for color, 2-bit indices for alpha
+    writer.write(1, 1);
+
+    // Color endpoints: 0 to 31 for all channels; alpha 63 to 0
+    for _ in 0..3 {
+        writer.write(0, 5);
+        writer.write(31, 5);
+    }
+    writer.write(63, 6);
+    writer.write(0, 6);
+
+    // 2-bit indices (alpha) are all 1, 3-bit indices (color) are all 2
+    writer.write(1, 1);
+    for _ in 1..16 {
+        writer.write(1, 2);
+    }
+    writer.write(2, 2);
+    for _ in 1..16 {
+        writer.write(2, 3);
+    }
+
+    let pixels = decode_bc7_block(&writer.finish());
+    let color = interpolate(0, 255, WEIGHTS_3[2]);
+    let alpha = interpolate(255, 0, WEIGHTS_2[1]);
+    assert_eq!([Pixel { alpha, red: color, green: color, blue: color }; 16], pixels);
+}
+
+#[test]
+pub fn decode_mode_1_partition() {
+    let partition = 13; // 0xFF00: the top two rows are subset 0, the bottom two are subset 1
+
+    let mut writer = BitWriter::new();
+    writer.write(1 << 1, 2);
+    writer.write(partition, 6);
+
+    // Subset 0 is black and subset 1 is white (6-bit endpoints with shared p-bits)
+    for _ in 0..3 {
+        writer.write(0, 6);
+        writer.write(0, 6);
+        writer.write(63, 6);
+        writer.write(63, 6);
+    }
+    writer.write(0, 1);
+    writer.write(1, 1);
+
+    // Subset 0 uses index 0 (the start), subset 1 uses index 7 (the end)
+    let anchor = ANCHORS_2[partition as usize] as usize;
+    assert_eq!(15, anchor);
+    for i in 0..16 {
+        match i {
+            0 => writer.write(0, 2),
+            n if n == anchor => writer.write(3, 2),
+            n if n < 8 => writer.write(0, 3),
+            _ => writer.write(7, 3)
+        }
+    }
+
+    let pixels = decode_bc7_block(&writer.finish());
+    let black = Pixel { alpha: 255, red: 0, green: 0, blue: 0 };
+    let white = Pixel { alpha: 255, red: 255, green: 255, blue: 255 };
+    assert_eq!([black; 8], pixels[0..8]);
+    assert_eq!([white; 8], pixels[8..16]);
+}
+
+/// Simple xorshift generator so the tests are deterministic.
+fn random_block(seed: &mut u32, alpha: bool) -> [[u8; 4]; 16] {
+    let mut next = || {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 17;
+        *seed ^= *seed << 5;
+        *seed
+    };
+
+    // Random endpoints with a bit of noise, so blocks are neither trivial nor pure noise
+    let [a, b] = [next().to_le_bytes(), next().to_le_bytes()];
+    let mut block = [[0u8; 4]; 16];
+    for pixel in block.iter_mut() {
+        let t = next() % 256;
+        let noise = next().to_le_bytes();
+        for channel in 0..4 {
+            let value = (a[channel] as u32 * (255 - t) + b[channel] as u32 * t) / 255;
+            pixel[channel] = (value as u8).saturating_add(noise[channel] % 16);
+        }
+        if !alpha {
+            pixel[3] = 255;
+        }
+    }
+    block
+}
+
+fn decoded_error(block: &[[u8; 4]; 16], encoded: &[u8; 16]) -> u32 {
+    let decoded = decode_bc7_block(encoded);
+    block.iter().zip(decoded).map(|(original, decoded)| {
+        let decoded = [decoded.red, decoded.green, decoded.blue, decoded.alpha];
+        (0..4).map(|c| (original[c] as i32 - decoded[c] as i32).pow(2) as u32).sum::<u32>()
+    }).sum()
+}
+
+#[test]
+pub fn encoded_error_matches_decoded() {
+    // The error the encoder computes for each mode, partition, and rotation should match what the
+    // decoder actually produces, or the block was packed incorrectly.
+    let settings = BC7Profile::Balanced.settings();
+    let mut seed = 0x1234_5678;
+
+    for _ in 0..4 {
+        let block = random_block(&mut seed, true);
+
+        for (mode, info) in MODES.iter().enumerate() {
+            if info.rotation_bits > 0 {
+                for rotation in 0..4 {
+                    for index_selection in 0..(1 << info.index_selection_bits) {
+                        let encoded = encode_separate(&block, mode, rotation, index_selection, &settings, [1; 4], [false; 4]);
+                        assert_eq!(encoded.error, decoded_error(&block, &encoded.block), "mode {mode} rotation {rotation}");
+                    }
+                }
+            }
+            else {
+                for partition in 0..(1 << info.partition_bits) {
+                    let encoded = encode_joint(&block, mode, partition, &settings, [1; 4], [false; 4]);
+                    assert_eq!(encoded.error, decoded_error(&block, &encoded.block), "mode {mode} partition {partition}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+pub fn profiles_improve_quality() {
+    let mut seed = 0x8765_4321;
+    for alpha in [false, true] {
+        let mut errors = [0u32; 3];
+        for _ in 0..32 {
+            let block = random_block(&mut seed, alpha);
+            let pixels = block.map(|[red, green, blue, alpha]| Pixel { alpha, red, green, blue });
+            for (error, profile) in errors.iter_mut().zip([BC7Profile::Fast, BC7Profile::Balanced, BC7Profile::Thorough]) {
+                *error += decoded_error(&block, &encode_bc7_block(&pixels, &EncodeOptions { bc7_profile: profile, ..Default::default() }));
+            }
+        }
+        assert!(errors[0] >= errors[1] && errors[1] >= errors[2], "{errors:?}");
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{ColorSpace, Error, Format};
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+const D3DFMT_A8R8G8B8: u32 = 21;
+const D3DFMT_X8R8G8B8: u32 = 22;
+const D3DFMT_R5G6B5: u32 = 23;
+const D3DFMT_A1R5G5B5: u32 = 25;
+const D3DFMT_A4R4G4B4: u32 = 26;
+const D3DFMT_A8: u32 = 28;
+const D3DFMT_A8B8G8R8: u32 = 32;
+const D3DFMT_P8: u32 = 41;
+const D3DFMT_L8: u32 = 50;
+const D3DFMT_A8L8: u32 = 51;
+const D3DFMT_A4L4: u32 = 52;
+const D3DFMT_DXT1: u32 = fourcc(b"DXT1");
+const D3DFMT_DXT3: u32 = fourcc(b"DXT3");
+const D3DFMT_DXT5: u32 = fourcc(b"DXT5");
+
+const DXGI_FORMAT_R8G8B8A8_UNORM: u32 = 28;
+const DXGI_FORMAT_R8G8B8A8_UNORM_SRGB: u32 = 29;
+const DXGI_FORMAT_A8_UNORM: u32 = 65;
+const DXGI_FORMAT_BC1_UNORM: u32 = 71;
+const DXGI_FORMAT_BC1_UNORM_SRGB: u32 = 72;
+const DXGI_FORMAT_BC2_UNORM: u32 = 74;
+const DXGI_FORMAT_BC2_UNORM_SRGB: u32 = 75;
+const DXGI_FORMAT_BC3_UNORM: u32 = 77;
+const DXGI_FORMAT_BC3_UNORM_SRGB: u32 = 78;
+const DXGI_FORMAT_B5G6R5_UNORM: u32 = 85;
+const DXGI_FORMAT_B5G5R5A1_UNORM: u32 = 86;
+const DXGI_FORMAT_B8G8R8A8_UNORM: u32 = 87;
+const DXGI_FORMAT_B8G8R8X8_UNORM: u32 = 88;
+const DXGI_FORMAT_B8G8R8A8_UNORM_SRGB: u32 = 91;
+const DXGI_FORMAT_B8G8R8X8_UNORM_SRGB: u32 = 93;
+const DXGI_FORMAT_BC7_UNORM: u32 = 98;
+const DXGI_FORMAT_BC7_UNORM_SRGB: u32 = 99;
+const DXGI_FORMAT_B4G4R4A4_UNORM: u32 = 115;
+
+impl Format<'_> {
+    /// Get the format for a Direct3D 9 `D3DFORMAT` value.
+    ///
+    /// Block compressed formats use their FourCC code as the value, such as `0x31545844` for
+    /// `D3DFMT_DXT1`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] if the value has no equivalent format. This includes
+    /// `D3DFMT_P8`, since the palette is not part of the value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Error, Format};
+    ///
+    /// assert_eq!(Ok(Format::A8R8G8B8), Format::from_d3dformat(21));
+    /// assert_eq!(Ok(Format::DXT5), Format::from_d3dformat(u32::from_le_bytes(*b"DXT5")));
+    /// assert_eq!(Err(Error::UnsupportedFormat), Format::from_d3dformat(20)); // D3DFMT_R8G8B8
+    /// ```
+    pub const fn from_d3dformat(d3dformat: u32) -> Result<Format<'static>, Error> {
+        match d3dformat {
+            D3DFMT_A8R8G8B8 => Ok(Format::A8R8G8B8),
+            D3DFMT_X8R8G8B8 => Ok(Format::X8R8G8B8),
+            D3DFMT_R5G6B5 => Ok(Format::R5G6B5),
+            D3DFMT_A1R5G5B5 => Ok(Format::A1R5G5B5),
+            D3DFMT_A4R4G4B4 => Ok(Format::A4R4G4B4),
+            D3DFMT_A8 => Ok(Format::A8),
+            D3DFMT_A8B8G8R8 => Ok(Format::A8B8G8R8),
+            D3DFMT_L8 => Ok(Format::Y8),
+            D3DFMT_A8L8 => Ok(Format::A8Y8),
+            D3DFMT_A4L4 => Ok(Format::A4Y4),
+            D3DFMT_DXT1 => Ok(Format::DXT1),
+            D3DFMT_DXT3 => Ok(Format::DXT3),
+            D3DFMT_DXT5 => Ok(Format::DXT5),
+            _ => Err(Error::UnsupportedFormat)
+        }
+    }
+
+    /// Get the Direct3D 9 `D3DFORMAT` value for the format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] if Direct3D 9 has no equivalent format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Error, Format};
+    ///
+    /// assert_eq!(Ok(50), Format::Y8.to_d3dformat()); // D3DFMT_L8
+    /// assert_eq!(Err(Error::UnsupportedFormat), Format::BC7.to_d3dformat());
+    /// ```
+    pub const fn to_d3dformat(self) -> Result<u32, Error> {
+        match self {
+            Format::DXT1 => Ok(D3DFMT_DXT1),
+            Format::DXT3 => Ok(D3DFMT_DXT3),
+            Format::DXT5 => Ok(D3DFMT_DXT5),
+            Format::A8 => Ok(D3DFMT_A8),
+            Format::Y8 => Ok(D3DFMT_L8),
+            Format::A8Y8 => Ok(D3DFMT_A8L8),
+            Format::A4Y4 => Ok(D3DFMT_A4L4),
+            Format::R5G6B5 => Ok(D3DFMT_R5G6B5),
+            Format::A1R5G5B5 => Ok(D3DFMT_A1R5G5B5),
+            Format::A4R4G4B4 => Ok(D3DFMT_A4R4G4B4),
+            Format::X8R8G8B8 => Ok(D3DFMT_X8R8G8B8),
+            Format::A8R8G8B8 => Ok(D3DFMT_A8R8G8B8),
+            Format::A8B8G8R8 => Ok(D3DFMT_A8B8G8R8),
+            Format::P8(_) => Ok(D3DFMT_P8),
+            Format::BC7 | Format::AY8 | Format::A4 | Format::Y4 | Format::P4(_) => Err(Error::UnsupportedFormat)
+        }
+    }
+
+    /// Get the format and color space for a Direct3D 10+ `DXGI_FORMAT` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] if the value has no equivalent format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{ColorSpace, Error, Format};
+    ///
+    /// assert_eq!(Ok((Format::BC7, ColorSpace::Linear)), Format::from_dxgi(98));
+    /// assert_eq!(Ok((Format::DXT1, ColorSpace::Srgb)), Format::from_dxgi(72));
+    /// assert_eq!(Err(Error::UnsupportedFormat), Format::from_dxgi(83)); // DXGI_FORMAT_BC5_UNORM
+    /// ```
+    pub const fn from_dxgi(dxgi_format: u32) -> Result<(Format<'static>, ColorSpace), Error> {
+        match dxgi_format {
+            DXGI_FORMAT_R8G8B8A8_UNORM => Ok((Format::A8B8G8R8, ColorSpace::Linear)),
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => Ok((Format::A8B8G8R8, ColorSpace::Srgb)),
+            DXGI_FORMAT_A8_UNORM => Ok((Format::A8, ColorSpace::Linear)),
+            DXGI_FORMAT_BC1_UNORM => Ok((Format::DXT1, ColorSpace::Linear)),
+            DXGI_FORMAT_BC1_UNORM_SRGB => Ok((Format::DXT1, ColorSpace::Srgb)),
+            DXGI_FORMAT_BC2_UNORM => Ok((Format::DXT3, ColorSpace::Linear)),
+            DXGI_FORMAT_BC2_UNORM_SRGB => Ok((Format::DXT3, ColorSpace::Srgb)),
+            DXGI_FORMAT_BC3_UNORM => Ok((Format::DXT5, ColorSpace::Linear)),
+            DXGI_FORMAT_BC3_UNORM_SRGB => Ok((Format::DXT5, ColorSpace::Srgb)),
+            DXGI_FORMAT_B5G6R5_UNORM => Ok((Format::R5G6B5, ColorSpace::Linear)),
+            DXGI_FORMAT_B5G5R5A1_UNORM => Ok((Format::A1R5G5B5, ColorSpace::Linear)),
+            DXGI_FORMAT_B8G8R8A8_UNORM => Ok((Format::A8R8G8B8, ColorSpace::Linear)),
+            DXGI_FORMAT_B8G8R8X8_UNORM => Ok((Format::X8R8G8B8, ColorSpace::Linear)),
+            DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => Ok((Format::A8R8G8B8, ColorSpace::Srgb)),
+            DXGI_FORMAT_B8G8R8X8_UNORM_SRGB => Ok((Format::X8R8G8B8, ColorSpace::Srgb)),
+            DXGI_FORMAT_BC7_UNORM => Ok((Format::BC7, ColorSpace::Linear)),
+            DXGI_FORMAT_BC7_UNORM_SRGB => Ok((Format::BC7, ColorSpace::Srgb)),
+            DXGI_FORMAT_B4G4R4A4_UNORM => Ok((Format::A4R4G4B4, ColorSpace::Linear)),
+            _ => Err(Error::UnsupportedFormat)
+        }
+    }
+
+    /// Get the Direct3D 10+ `DXGI_FORMAT` value for the format in the given color space.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] if DXGI has no equivalent format, such as for
+    /// luminance formats, or no sRGB variant of it if `color_space` is [`ColorSpace::Srgb`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{ColorSpace, Error, Format};
+    ///
+    /// assert_eq!(Ok(77), Format::DXT5.to_dxgi(ColorSpace::Linear)); // DXGI_FORMAT_BC3_UNORM
+    /// assert_eq!(Ok(78), Format::DXT5.to_dxgi(ColorSpace::Srgb)); // DXGI_FORMAT_BC3_UNORM_SRGB
+    /// assert_eq!(Err(Error::UnsupportedFormat), Format::R5G6B5.to_dxgi(ColorSpace::Srgb));
+    /// ```
+    pub const fn to_dxgi(self, color_space: ColorSpace) -> Result<u32, Error> {
+        let (linear, srgb) = match self {
+            Format::DXT1 => (DXGI_FORMAT_BC1_UNORM, Some(DXGI_FORMAT_BC1_UNORM_SRGB)),
+            Format::DXT3 => (DXGI_FORMAT_BC2_UNORM, Some(DXGI_FORMAT_BC2_UNORM_SRGB)),
+            Format::DXT5 => (DXGI_FORMAT_BC3_UNORM, Some(DXGI_FORMAT_BC3_UNORM_SRGB)),
+            Format::BC7 => (DXGI_FORMAT_BC7_UNORM, Some(DXGI_FORMAT_BC7_UNORM_SRGB)),
+            Format::A8 => (DXGI_FORMAT_A8_UNORM, None),
+            Format::R5G6B5 => (DXGI_FORMAT_B5G6R5_UNORM, None),
+            Format::A1R5G5B5 => (DXGI_FORMAT_B5G5R5A1_UNORM, None),
+            Format::A4R4G4B4 => (DXGI_FORMAT_B4G4R4A4_UNORM, None),
+            Format::X8R8G8B8 => (DXGI_FORMAT_B8G8R8X8_UNORM, Some(DXGI_FORMAT_B8G8R8X8_UNORM_SRGB)),
+            Format::A8R8G8B8 => (DXGI_FORMAT_B8G8R8A8_UNORM, Some(DXGI_FORMAT_B8G8R8A8_UNORM_SRGB)),
+            Format::A8B8G8R8 => (DXGI_FORMAT_R8G8B8A8_UNORM, Some(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB)),
+            Format::Y8 | Format::AY8 | Format::A8Y8 | Format::A4 | Format::Y4 | Format::A4Y4 | Format::P8(_) | Format::P4(_) => {
+                return Err(Error::UnsupportedFormat)
+            }
+        };
+        match (color_space, srgb) {
+            (ColorSpace::Linear, _) => Ok(linear),
+            (ColorSpace::Srgb, Some(srgb)) => Ok(srgb),
+            (ColorSpace::Srgb, None) => Err(Error::UnsupportedFormat)
+        }
+    }
+
+    /// Get the format for a FourCC code, such as the one in a DDS pixel format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] if the code has no equivalent format. This includes
+    /// `DXT2` and `DXT4`, which have premultiplied alpha.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Error, Format};
+    ///
+    /// assert_eq!(Ok(Format::DXT3), Format::from_fourcc(*b"DXT3"));
+    /// assert_eq!(Err(Error::UnsupportedFormat), Format::from_fourcc(*b"ATI2"));
+    /// ```
+    pub const fn from_fourcc(fourcc: [u8; 4]) -> Result<Format<'static>, Error> {
+        match &fourcc {
+            b"DXT1" => Ok(Format::DXT1),
+            b"DXT3" => Ok(Format::DXT3),
+            b"DXT5" => Ok(Format::DXT5),
+            _ => Err(Error::UnsupportedFormat)
+        }
+    }
+
+    /// Get the FourCC code for the format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] if the format has no FourCC code, which is the case for
+    /// every format except `DXT1`, `DXT3`, and `DXT5`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Error, Format};
+    ///
+    /// assert_eq!(Ok(*b"DXT1"), Format::DXT1.to_fourcc());
+    /// assert_eq!(Err(Error::UnsupportedFormat), Format::BC7.to_fourcc());
+    /// ```
+    pub const fn to_fourcc(self) -> Result<[u8; 4], Error> {
+        match self {
+            Format::DXT1 => Ok(*b"DXT1"),
+            Format::DXT3 => Ok(*b"DXT3"),
+            Format::DXT5 => Ok(*b"DXT5"),
+            _ => Err(Error::UnsupportedFormat)
+        }
+    }
+}
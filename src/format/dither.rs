@@ -0,0 +1,258 @@
+use core::ops::Range;
+
+use crate::Pixel;
+
+/// Defines how pixels are dithered when reducing their bit depth.
+///
+/// Dithering applies to the 16-bit, 4-bit, and palettized formats
+/// ([`R5G6B5`](crate::Format::R5G6B5), [`A1R5G5B5`](crate::Format::A1R5G5B5),
+/// [`A4R4G4B4`](crate::Format::A4R4G4B4), [`A4`](crate::Format::A4), [`Y4`](crate::Format::Y4),
+/// [`A4Y4`](crate::Format::A4Y4), [`P8`](crate::Format::P8), and [`P4`](crate::Format::P4)). Alpha is dithered separately from
+/// color, and only if the format stores alpha.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum Dither {
+    /// Round each pixel independently.
+    #[default]
+    None,
+
+    /// Floyd–Steinberg error diffusion, which spreads all of the error to neighboring pixels.
+    ///
+    /// Without an error buffer (see [`Dither::buffer_length`]), images wider than 256 pixels are
+    /// diffused in overlapping strips. Error still crosses from one strip to the next, but the
+    /// output isn't exactly the same as diffusing whole rows.
+    FloydSteinberg,
+
+    /// Atkinson error diffusion, which only spreads 3/4 of the error to neighboring pixels, giving
+    /// more contrast at the cost of banding in very dark and bright areas.
+    ///
+    /// Wide images are diffused in strips without an error buffer, the same as with
+    /// [`FloydSteinberg`](Dither::FloydSteinberg).
+    Atkinson,
+
+    /// Ordered dithering with a 4x4 Bayer matrix.
+    ///
+    /// Unlike error diffusion, each pixel is dithered independently, which gives a regular pattern.
+    Bayer
+}
+
+impl Dither {
+    /// Get the length of the error buffer needed for error diffusion to be exact for an image this
+    /// wide, when it's passed to [`Format::encode_pixels_with_buffer`](crate::Format::encode_pixels_with_buffer).
+    ///
+    /// Without a buffer, there's only room for errors 256 pixels wide, so wider images are diffused
+    /// in strips that overlap by 32 pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Dither, EncodeOptions, Format, Pixel};
+    ///
+    /// let pixels = vec![Pixel { alpha: 255, red: 100, green: 150, blue: 200 }; 1000 * 4];
+    /// let mut bytes = vec![0u8; Format::R5G6B5.required_bytes(1000, 4)];
+    /// let mut buffer = vec![[0; 4]; Dither::buffer_length(1000)];
+    ///
+    /// let options = EncodeOptions { dither: Dither::FloydSteinberg, ..Default::default() };
+    /// Format::R5G6B5.encode_pixels_with_buffer(&options, &pixels, &mut bytes, &mut buffer, 1000, 4);
+    /// ```
+    pub const fn buffer_length(width: usize) -> usize {
+        width.saturating_mul(ERROR_ROWS)
+    }
+}
+
+/// Without a buffer, error diffusion is done in vertical strips at most this wide (including the
+/// lead-in), since there's no allocator to hold rows of errors for arbitrarily wide images.
+const STRIP_WIDTH: usize = 256;
+
+/// Columns before each strip that are diffused to carry error into the strip.
+const LEAD_IN: usize = 32;
+
+/// Error diffusion rows needed at once (the current row plus the rows error is spread to).
+const ERROR_ROWS: usize = 3;
+
+/// Neighbors that error is spread to as (x offset, y offset, 16ths of the error).
+type Taps = &'static [(isize, usize, i32)];
+
+const FLOYD_STEINBERG: Taps = &[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+const ATKINSON: Taps = &[(1, 0, 2), (2, 0, 2), (-1, 1, 2), (0, 1, 2), (1, 1, 2), (0, 2, 2)];
+
+const BAYER_4X4: [[i32; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5]
+];
+
+/// Get the step between output levels of each channel (see [`dither_pixels`]) for a format that
+/// stores each channel (alpha, red, green, blue) with the given number of bits.
+pub(super) const fn bit_steps(bits: [u32; 4]) -> [i32; 4] {
+    let mut steps = [0; 4];
+    let mut channel = 0;
+    while channel < 4 {
+        if bits[channel] > 0 {
+            steps[channel] = (255 << 8) / ((1 << bits[channel]) - 1);
+        }
+        channel += 1;
+    }
+    steps
+}
+
+/// Get the step between output levels of each channel (see [`dither_pixels`]) for a palette.
+///
+/// Palette colors aren't spread evenly, so the step is the median distance from each color to the
+/// closest other color, measured by the channel that differs the most. This is the step of a grid
+/// of colors (like 17 for a 4-bit grid), and the step of each channel for a ramp of grays. Alpha is
+/// only dithered if the palette has colors that aren't opaque.
+pub(super) fn palette_steps(palette: &[Pixel]) -> [i32; 4] {
+    let use_alpha = palette.iter().any(|p| p.alpha != 255);
+    let channels = |pixel: &Pixel| {
+        let mut channels = to_channels(pixel);
+        if !use_alpha {
+            channels[0] = 0;
+        }
+        channels
+    };
+
+    let mut distances = [0i32; 256];
+    let mut count = 0;
+    for (i, pixel) in palette.iter().enumerate() {
+        let pixel = channels(pixel);
+        let closest = palette.iter().enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| {
+                let other = channels(other);
+                (0..4).map(|c| (pixel[c] - other[c]).abs()).max().unwrap_or(0)
+            })
+            .filter(|distance| *distance > 0)
+            .min();
+        if let Some(distance) = closest {
+            distances[count] = distance;
+            count += 1;
+        }
+    }
+
+    // A palette of one color (repeated or not) has nothing to dither between.
+    if count == 0 {
+        return [0; 4]
+    }
+    distances[..count].sort_unstable();
+    let step = distances[count / 2] << 8;
+    [if use_alpha { step } else { 0 }, step, step, step]
+}
+
+/// Dither pixels, given the step between output levels of each channel (alpha, red, green, blue)
+/// in 256ths of an 8-bit level.
+///
+/// `quantize` is called at least once for each pixel with its index and the adjusted pixel, and it
+/// must store the pixel (replacing what an earlier call stored) and return what it will decode as.
+/// Channels with a step of zero are left alone. If `buffer` is given, it must be at least
+/// [`Dither::buffer_length`] long, and error diffusion is exact.
+pub(super) fn dither_pixels(
+    dither: Dither,
+    pixels: &[Pixel],
+    width: usize,
+    height: usize,
+    steps: [i32; 4],
+    buffer: Option<&mut [[i32; 4]]>,
+    mut quantize: impl FnMut(usize, Pixel) -> Pixel
+) {
+    let taps = match dither {
+        Dither::None => {
+            for (i, pixel) in pixels.iter().enumerate() {
+                quantize(i, *pixel);
+            }
+            return
+        },
+        Dither::Bayer => {
+            for (i, pixel) in pixels.iter().enumerate() {
+                let threshold = BAYER_4X4[(i / width) % 4][(i % width) % 4];
+
+                // Offset by -1/2 to +1/2 of a step between levels.
+                let mut channels = to_channels(pixel);
+                for (value, step) in channels.iter_mut().zip(steps).filter(|(_, step)| *step > 0) {
+                    *value += (2 * threshold + 1 - 16) * step / (32 << 8);
+                }
+                quantize(i, from_channels(channels));
+            }
+            return
+        },
+        Dither::FloydSteinberg => FLOYD_STEINBERG,
+        Dither::Atkinson => ATKINSON
+    };
+
+    // With a buffer, whole rows are diffused at once. Otherwise, strips are done right to left,
+    // each starting LEAD_IN columns early so error from the strip on its left is carried in. Those
+    // columns are quantized again with the strip on the left.
+    if let Some(buffer) = buffer {
+        diffuse_strip(pixels, width, height, 0..width, taps, steps, &mut buffer[..width * ERROR_ROWS], &mut quantize);
+        return
+    }
+
+    let step = STRIP_WIDTH - LEAD_IN;
+    let extra_strips = width.saturating_sub(STRIP_WIDTH).div_ceil(step);
+    for strip in (0..=extra_strips).rev() {
+        let columns = match strip {
+            0 => 0..width.min(STRIP_WIDTH),
+            _ => {
+                let start = STRIP_WIDTH + (strip - 1) * step;
+                start - LEAD_IN..(start + step).min(width)
+            }
+        };
+        let mut errors = [[0i32; 4]; STRIP_WIDTH * ERROR_ROWS];
+        diffuse_strip(pixels, width, height, columns, taps, steps, &mut errors, &mut quantize);
+    }
+}
+
+/// Diffuse error over a range of columns, with `errors` holding [`ERROR_ROWS`] rows of errors as
+/// wide as the range.
+#[allow(clippy::too_many_arguments)]
+fn diffuse_strip(
+    pixels: &[Pixel],
+    width: usize,
+    height: usize,
+    columns: Range<usize>,
+    taps: Taps,
+    steps: [i32; 4],
+    errors: &mut [[i32; 4]],
+    quantize: &mut impl FnMut(usize, Pixel) -> Pixel
+) {
+    // Errors are in 256ths so fractions of a level aren't lost, with one row per y modulo
+    // ERROR_ROWS.
+    let strip_width = columns.len();
+    errors.fill([0; 4]);
+
+    for y in 0..height {
+        for x in 0..strip_width {
+            let i = y * width + columns.start + x;
+            let mut exact = to_channels(&pixels[i]).map(|value| value << 8);
+            let error = errors[y % ERROR_ROWS * strip_width + x];
+            for ((value, error), _) in exact.iter_mut().zip(error).zip(steps).filter(|(_, step)| *step > 0) {
+                *value = (*value + error).clamp(0, 255 << 8);
+            }
+
+            let result = to_channels(&quantize(i, from_channels(exact.map(|value| (value + 128) >> 8))));
+
+            for &(dx, dy, weight) in taps {
+                let Some(tx) = x.checked_add_signed(dx).filter(|tx| *tx < strip_width) else {
+                    continue
+                };
+                let target = &mut errors[(y + dy) % ERROR_ROWS * strip_width + tx];
+                for channel in (0..4).filter(|c| steps[*c] > 0) {
+                    target[channel] += ((exact[channel] - (result[channel] << 8)) * weight) >> 4;
+                }
+            }
+        }
+
+        // This row is reused for errors spread to y + ERROR_ROWS.
+        let row = y % ERROR_ROWS * strip_width;
+        errors[row..row + strip_width].fill([0; 4]);
+    }
+}
+
+fn to_channels(pixel: &Pixel) -> [i32; 4] {
+    [pixel.alpha as i32, pixel.red as i32, pixel.green as i32, pixel.blue as i32]
+}
+
+fn from_channels(channels: [i32; 4]) -> Pixel {
+    let [alpha, red, green, blue] = channels.map(|c| c.clamp(0, 255) as u8);
+    Pixel { alpha, red, green, blue }
+}
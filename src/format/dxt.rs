@@ -0,0 +1,476 @@
+use crate::{ColorSpace, EncodeOptions, ErrorMetric, Pixel};
+use super::fit;
+
+/// Decode a DXT1 (BC1) block.
+///
+/// If the first endpoint is greater than the second, the block uses four colors. Otherwise, it
+/// uses three colors and index 3 is transparent black.
+pub(super) fn decode_bc1_block(block: &[u8; 8]) -> [Pixel; 16] {
+    decode_color_block(block, false)
+}
+
+/// Decode a DXT3 (BC2) block.
+///
+/// The first 64 bits hold explicit 4-bit alpha for each pixel, followed by a BC1 color block that
+/// is always in four-color mode.
+pub(super) fn decode_bc2_block(block: &[u8; 16]) -> [Pixel; 16] {
+    let alpha = u64::from_le_bytes(block[0..8].try_into().unwrap());
+    let mut output = decode_color_block(block[8..16].try_into().unwrap(), true);
+
+    for (i, pixel) in output.iter_mut().enumerate() {
+        let alpha4 = ((alpha >> (i * 4)) & 0b1111) as u16;
+        pixel.alpha = Pixel::from_a4r4g4b4((alpha4 << 12).to_le_bytes()).alpha;
+    }
+    output
+}
+
+/// Decode a DXT5 (BC3) block.
+///
+/// The first 64 bits hold an interpolated alpha block, followed by a BC1 color block that is
+/// always in four-color mode.
+pub(super) fn decode_bc3_block(block: &[u8; 16]) -> [Pixel; 16] {
+    let alpha = decode_alpha_block(block[0..8].try_into().unwrap());
+    let mut output = decode_color_block(block[8..16].try_into().unwrap(), true);
+
+    for (pixel, alpha) in output.iter_mut().zip(alpha) {
+        pixel.alpha = alpha;
+    }
+    output
+}
+
+/// Decode a 64-bit interpolated alpha block.
+///
+/// If the first endpoint is greater than the second, the block has eight alpha values. Otherwise,
+/// it has six alpha values plus 0 and 255.
+fn decode_alpha_block(block: &[u8; 8]) -> [u8; 16] {
+    let palette = alpha_palette(block[0], block[1]);
+    let indices = unpack_alpha_indices(block);
+    indices.map(|index| palette[index as usize])
+}
+
+/// Build the eight-entry palette of an interpolated alpha block.
+fn alpha_palette(alpha0: u8, alpha1: u8) -> [u8; 8] {
+    let a0 = alpha0 as u16;
+    let a1 = alpha1 as u16;
+    let mut palette = [alpha0, alpha1, 0, 0, 0, 0, 0, 255];
+
+    if alpha0 > alpha1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u16) * a0 + i as u16 * a1 + 3) / 7) as u8;
+        }
+    }
+    else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u16) * a0 + i as u16 * a1 + 2) / 5) as u8;
+        }
+    }
+
+    palette
+}
+
+/// Read the 16 3-bit indices stored in the last 48 bits of an alpha block.
+fn unpack_alpha_indices(block: &[u8; 8]) -> [u8; 16] {
+    let packed = u64::from_le_bytes(*block) >> 16;
+    let mut indices = [0u8; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = ((packed >> (i * 3)) & 0b111) as u8;
+    }
+    indices
+}
+
+/// Decode a 64-bit BC1 color block.
+///
+/// If `four_color_only` is set, the block is always decoded in four-color mode regardless of the
+/// endpoint order, as is the case for the color block of DXT3 and DXT5.
+pub(super) fn decode_color_block(block: &[u8; 8], four_color_only: bool) -> [Pixel; 16] {
+    let palette = color_palette([block[0], block[1]], [block[2], block[3]], four_color_only);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let mut output = [Pixel::default(); 16];
+    for (i, pixel) in output.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (i * 2)) & 0b11) as usize];
+    }
+    output
+}
+
+/// Build the four-entry palette of a BC1 color block from its two R5G6B5 endpoints.
+fn color_palette(color0: [u8; 2], color1: [u8; 2], four_color_only: bool) -> [Pixel; 4] {
+    let c0 = Pixel::from_r5g6b5(color0);
+    let c1 = Pixel::from_r5g6b5(color1);
+
+    if four_color_only || u16::from_le_bytes(color0) > u16::from_le_bytes(color1) {
+        [c0, c1, lerp_third(c0, c1), lerp_third(c1, c0)]
+    }
+    else {
+        [c0, c1, midpoint(c0, c1), Pixel { alpha: 0, red: 0, green: 0, blue: 0 }]
+    }
+}
+
+/// Returns `(2*a + b) / 3` for each color channel (rounded).
+const fn lerp_third(a: Pixel, b: Pixel) -> Pixel {
+    const fn channel(a: u8, b: u8) -> u8 {
+        ((2 * a as u16 + b as u16 + 1) / 3) as u8
+    }
+
+    Pixel {
+        alpha: 255,
+        red: channel(a.red, b.red),
+        green: channel(a.green, b.green),
+        blue: channel(a.blue, b.blue)
+    }
+}
+
+/// Returns `(a + b) / 2` for each color channel (rounded).
+const fn midpoint(a: Pixel, b: Pixel) -> Pixel {
+    const fn channel(a: u8, b: u8) -> u8 {
+        (a as u16 + b as u16).div_ceil(2) as u8
+    }
+
+    Pixel {
+        alpha: 255,
+        red: channel(a.red, b.red),
+        green: channel(a.green, b.green),
+        blue: channel(a.blue, b.blue)
+    }
+}
+
+/// Encode a DXT1 (BC1) block.
+///
+/// If any pixel has an alpha of `options.alpha_threshold` or less, the block is encoded in
+/// three-color mode and those pixels are stored as transparent black. Otherwise, whichever of the
+/// four-color and three-color modes has the lower error is used.
+pub(super) fn encode_bc1_block(pixels: &[Pixel; 16], options: &EncodeOptions) -> [u8; 8] {
+    let transparent = pixels.map(|p| p.alpha <= options.alpha_threshold);
+    encode_color_block(pixels, &transparent, false, options)
+}
+
+/// Encode a DXT3 (BC2) block.
+///
+/// Alpha is rounded to 4 bits the same way as [`Pixel::as_a4r4g4b4`].
+pub(super) fn encode_bc2_block(pixels: &[Pixel; 16], options: &EncodeOptions) -> [u8; 16] {
+    let mut alpha = 0u64;
+    for (i, pixel) in pixels.iter().enumerate() {
+        let alpha4 = u16::from_le_bytes(pixel.as_a4r4g4b4()) >> 12;
+        alpha |= (alpha4 as u64) << (i * 4);
+    }
+
+    let mut output = [0u8; 16];
+    output[0..8].copy_from_slice(&alpha.to_le_bytes());
+    output[8..16].copy_from_slice(&encode_color_block(pixels, &[false; 16], true, options));
+    output
+}
+
+/// Encode a DXT5 (BC3) block.
+pub(super) fn encode_bc3_block(pixels: &[Pixel; 16], options: &EncodeOptions) -> [u8; 16] {
+    let mut output = [0u8; 16];
+    output[0..8].copy_from_slice(&encode_alpha_block(&pixels.map(|p| p.alpha)));
+    output[8..16].copy_from_slice(&encode_color_block(pixels, &[false; 16], true, options));
+    output
+}
+
+/// Encode a 64-bit interpolated alpha block.
+///
+/// Both the eight-value and the six-value (plus 0 and 255) modes are tried, and whichever has the
+/// lower error is kept.
+fn encode_alpha_block(alpha: &[u8; 16]) -> [u8; 8] {
+    let min = alpha.iter().copied().min().unwrap();
+    let max = alpha.iter().copied().max().unwrap();
+
+    // In the six-value mode, 0 and 255 are free, so only the values between them need to be spanned.
+    let inner = alpha.iter().copied().filter(|a| *a != 0 && *a != 255);
+    let inner_min = inner.clone().min().unwrap_or(0);
+    let inner_max = inner.max().unwrap_or(0);
+
+    let (eight, eight_error) = fit_alpha_block(alpha, max, min);
+    let (six, six_error) = fit_alpha_block(alpha, inner_min, inner_max);
+
+    if six_error < eight_error {
+        six
+    }
+    else {
+        eight
+    }
+}
+
+/// Encode an alpha block with the given endpoints, choosing the closest index for each pixel.
+///
+/// Returns the block and its total squared error.
+fn fit_alpha_block(alpha: &[u8; 16], alpha0: u8, alpha1: u8) -> ([u8; 8], u32) {
+    let palette = alpha_palette(alpha0, alpha1);
+
+    let mut packed = 0u64;
+    let mut error = 0u32;
+    for (i, &a) in alpha.iter().enumerate() {
+        let (index, distance) = palette
+            .iter()
+            .enumerate()
+            .map(|(index, &entry)| (index, (entry as i32 - a as i32).unsigned_abs().pow(2)))
+            .min_by_key(|(_, distance)| *distance)
+            .unwrap();
+        packed |= (index as u64) << (i * 3);
+        error += distance;
+    }
+
+    let mut block = (packed << 16).to_le_bytes();
+    block[0] = alpha0;
+    block[1] = alpha1;
+    (block, error)
+}
+
+/// Encode a 64-bit BC1 color block.
+///
+/// Pixels marked as `transparent` are ignored when fitting and get index 3. If `four_color_only`
+/// is set, the block is always encoded in four-color mode and `transparent` must be all `false`.
+pub(super) fn encode_color_block(
+    pixels: &[Pixel; 16],
+    transparent: &[bool; 16],
+    four_color_only: bool,
+    options: &EncodeOptions
+) -> [u8; 8] {
+    let has_transparency = transparent.contains(&true);
+    debug_assert!(!(four_color_only && has_transparency));
+
+    let mut fit = ColorFit::new(pixels, transparent, four_color_only, options);
+    if fit.opaque_count == 0 {
+        return [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF];
+    }
+
+    let modes: &[bool] = if has_transparency {
+        &[true]
+    }
+    else if four_color_only {
+        &[false]
+    }
+    else {
+        &[false, true]
+    };
+
+    for &three_color in modes {
+        if let Some(color) = fit.single_color() {
+            let (high, low) = single_color_endpoints(color, three_color);
+            fit.try_endpoints(high, low, three_color);
+            continue
+        }
+
+        let (start, end) = fit.principal_axis_endpoints();
+        let mut indices = fit.try_endpoints(start, end, three_color);
+
+        // Refine the endpoints with a least squares fit of the chosen indices.
+        for _ in 0..REFINE_ITERATIONS {
+            let Some((start, end)) = fit.least_squares_endpoints(&indices, three_color) else {
+                break
+            };
+            indices = fit.try_endpoints(start, end, three_color);
+        }
+    }
+
+    fit.best
+}
+
+/// Number of least squares refinement passes done after the principal axis fit.
+const REFINE_ITERATIONS: usize = 2;
+
+/// Interpolation weight of the second endpoint for each index in four-color mode.
+const FOUR_COLOR_WEIGHTS: [f32; 4] = [0.0, 1.0, 1.0 / 3.0, 2.0 / 3.0];
+
+/// Interpolation weight of the second endpoint for each index in three-color mode.
+const THREE_COLOR_WEIGHTS: [f32; 4] = [0.0, 1.0, 0.5, 0.0];
+
+struct ColorFit<'a> {
+    pixels: &'a [Pixel; 16],
+    transparent: &'a [bool; 16],
+    four_color_only: bool,
+    metric: ErrorMetric,
+    color_space: ColorSpace,
+    opaque_count: usize,
+    best: [u8; 8],
+    best_error: u32
+}
+
+impl<'a> ColorFit<'a> {
+    fn new(pixels: &'a [Pixel; 16], transparent: &'a [bool; 16], four_color_only: bool, options: &EncodeOptions) -> Self {
+        Self {
+            pixels,
+            transparent,
+            four_color_only,
+            metric: options.metric,
+            color_space: options.color_space,
+            opaque_count: transparent.iter().filter(|t| !**t).count(),
+            best: [0; 8],
+            best_error: u32::MAX
+        }
+    }
+
+    fn opaque_pixels(&self) -> impl Iterator<Item = (usize, Pixel)> + '_ {
+        self.pixels.iter().copied().enumerate().filter(|(i, _)| !self.transparent[*i])
+    }
+
+    /// Returns the color of the block if every opaque pixel is the same color.
+    fn single_color(&self) -> Option<Pixel> {
+        let mut pixels = self.opaque_pixels().map(|(_, p)| p);
+        let first = pixels.next()?;
+        pixels.all(|p| p.distance_rgb(&first) == 0).then_some(first)
+    }
+
+    /// Get the colors of the opaque pixels, along with the index of each pixel in the block.
+    fn opaque_points(&self) -> ([[f32; 3]; 16], [usize; 16], usize) {
+        let mut points = [[0.0f32; 3]; 16];
+        let mut pixel_indices = [0usize; 16];
+        let mut count = 0;
+        for (i, p) in self.opaque_pixels() {
+            points[count] = [p.red as f32, p.green as f32, p.blue as f32];
+            pixel_indices[count] = i;
+            count += 1;
+        }
+        (points, pixel_indices, count)
+    }
+
+    /// Find endpoints spanning the opaque pixels along their principal axis.
+    fn principal_axis_endpoints(&self) -> ([f32; 3], [f32; 3]) {
+        let (points, _, count) = self.opaque_points();
+        let mean_red = points[..count].iter().map(|p| p[0]).sum::<f32>() / count as f32;
+        let weights = self.metric.channel_weights(mean_red as u32).map(|w| w as f32);
+        fit::principal_axis_endpoints(&points[..count], &weights)
+    }
+
+    /// Solve for the endpoints that minimize the squared error for the given indices.
+    ///
+    /// Returns `None` if the system is degenerate (e.g. all pixels use the same index).
+    fn least_squares_endpoints(&self, indices: &[u8; 16], three_color: bool) -> Option<([f32; 3], [f32; 3])> {
+        let weights = if three_color { &THREE_COLOR_WEIGHTS } else { &FOUR_COLOR_WEIGHTS };
+        let (points, pixel_indices, count) = self.opaque_points();
+        let point_weights = pixel_indices.map(|i| weights[indices[i] as usize]);
+        fit::least_squares_endpoints(&points[..count], &point_weights[..count])
+    }
+
+    /// Quantize the endpoints, pick the best indices, and keep the block if it beats the best one
+    /// so far.
+    ///
+    /// Returns the indices chosen, with index 0 corresponding to `start` and index 1 to `end`.
+    fn try_endpoints(&mut self, start: [f32; 3], end: [f32; 3], three_color: bool) -> [u8; 16] {
+        let start_565 = u16::from_le_bytes(quantize(start).as_r5g6b5());
+        let end_565 = u16::from_le_bytes(quantize(end).as_r5g6b5());
+
+        // Four-color mode requires color0 > color1, and three-color mode requires color0 <= color1.
+        let swapped = if three_color { start_565 > end_565 } else { start_565 < end_565 };
+        let (color0, color1) = if swapped { (end_565, start_565) } else { (start_565, end_565) };
+
+        let palette = color_palette(color0.to_le_bytes(), color1.to_le_bytes(), self.four_color_only);
+        let usable = if self.four_color_only || color0 > color1 { 4 } else { 3 };
+
+        let mut indices = [0u8; 16];
+        let mut packed = 0u32;
+        let mut error = 0u32;
+        for (i, pixel) in self.pixels.iter().enumerate() {
+            let index = if self.transparent[i] {
+                3
+            }
+            else {
+                let (index, distance) = palette[..usable]
+                    .iter()
+                    .enumerate()
+                    .map(|(index, entry)| (index, self.metric.distance_rgb_in(*entry, *pixel, self.color_space)))
+                    .min_by_key(|(_, distance)| *distance)
+                    .unwrap();
+                error += distance;
+                index
+            };
+            packed |= (index as u32) << (i * 2);
+
+            // Report indices relative to the unswapped endpoints for the least squares fit.
+            indices[i] = if swapped && (index < 2 || usable == 4) { index as u8 ^ 1 } else { index as u8 };
+        }
+
+        if error < self.best_error {
+            self.best_error = error;
+            self.best[0..2].copy_from_slice(&color0.to_le_bytes());
+            self.best[2..4].copy_from_slice(&color1.to_le_bytes());
+            self.best[4..8].copy_from_slice(&packed.to_le_bytes());
+        }
+
+        indices
+    }
+}
+
+fn quantize(color: [f32; 3]) -> Pixel {
+    let channel = |c: f32| (c + 0.5).clamp(0.0, 255.0) as u8;
+    Pixel { alpha: 255, red: channel(color[0]), green: channel(color[1]), blue: channel(color[2]) }
+}
+
+/// Get the endpoints that best reproduce a single color through index 2.
+fn single_color_endpoints(color: Pixel, three_color: bool) -> ([f32; 3], [f32; 3]) {
+    let (table5, table6) = if three_color {
+        (&SINGLE_COLOR_HALF_5, &SINGLE_COLOR_HALF_6)
+    }
+    else {
+        (&SINGLE_COLOR_THIRD_5, &SINGLE_COLOR_THIRD_6)
+    };
+
+    let [red_start, red_end] = table5[color.red as usize];
+    let [green_start, green_end] = table6[color.green as usize];
+    let [blue_start, blue_end] = table5[color.blue as usize];
+
+    (
+        [red_start as f32, green_start as f32, blue_start as f32],
+        [red_end as f32, green_end as f32, blue_end as f32]
+    )
+}
+
+const SINGLE_COLOR_THIRD_5: [[u8; 2]; 256] = single_color_table(5, false);
+const SINGLE_COLOR_THIRD_6: [[u8; 2]; 256] = single_color_table(6, false);
+const SINGLE_COLOR_HALF_5: [[u8; 2]; 256] = single_color_table(5, true);
+const SINGLE_COLOR_HALF_6: [[u8; 2]; 256] = single_color_table(6, true);
+
+/// Build a table of (expanded) endpoint pairs whose interpolated value best matches each 8-bit
+/// channel value, using the same interpolation as the decoder.
+const fn single_color_table(bits: u32, half: bool) -> [[u8; 2]; 256] {
+    let levels = 1u16 << bits;
+    let max = levels - 1;
+
+    // Endpoint pair that reproduces each value exactly, if any
+    let mut exact = [[0u8; 2]; 256];
+    let mut found = [false; 256];
+
+    let mut a = 0;
+    while a < levels {
+        let mut b = 0;
+        while b < levels {
+            let a8 = ((a * 255 + max / 2) / max) as u8;
+            let b8 = ((b * 255 + max / 2) / max) as u8;
+            let value = if half {
+                (a8 as u16 + b8 as u16).div_ceil(2)
+            }
+            else {
+                (2 * a8 as u16 + b8 as u16 + 1) / 3
+            } as usize;
+
+            if !found[value] {
+                found[value] = true;
+                exact[value] = [a8, b8];
+            }
+            b += 1;
+        }
+        a += 1;
+    }
+
+    // Fill in the rest with the closest value that can be reproduced.
+    let mut table = [[0u8; 2]; 256];
+    let mut value = 0;
+    while value < 256 {
+        let mut distance = 0;
+        loop {
+            if value >= distance && found[value - distance] {
+                table[value] = exact[value - distance];
+                break
+            }
+            if value + distance < 256 && found[value + distance] {
+                table[value] = exact[value + distance];
+                break
+            }
+            distance += 1;
+        }
+        value += 1;
+    }
+
+    table
+}
@@ -0,0 +1,181 @@
+use crate::math::sqrt;
+
+/// Find the endpoints of the line that best fits the points, spanning all of them.
+///
+/// The line goes through the mean of the points along their principal axis, where the error of
+/// each channel is scaled by `weights`. If there are no points, both endpoints are zero.
+pub(super) fn principal_axis_endpoints<const N: usize>(points: &[[f32; N]], weights: &[f32; N]) -> ([f32; N], [f32; N]) {
+    if points.is_empty() {
+        return ([0.0; N], [0.0; N])
+    }
+
+    // Find the axis in a space where each channel is scaled by the square root of its weight, then
+    // scale it back.
+    let scale = weights.map(sqrt);
+    let mean = mean(points);
+    let scaled_axis = principal_axis(&covariance(points, &mean, &scale));
+    let mut axis = [0.0f32; N];
+    for channel in 0..N {
+        if scale[channel] > 0.0 {
+            axis[channel] = scaled_axis[channel] / scale[channel];
+        }
+    }
+    let axis_length_squared = dot(&axis, &axis);
+    if axis_length_squared <= f32::EPSILON {
+        return (mean, mean)
+    }
+
+    let mut min = f32::MAX;
+    let mut max = f32::MIN;
+    for point in points {
+        let t = dot(&sub(point, &mean), &axis);
+        min = min.min(t);
+        max = max.max(t);
+    }
+
+    let point = |t: f32| {
+        let scale = t / axis_length_squared;
+        let mut output = mean;
+        for (output, axis) in output.iter_mut().zip(axis) {
+            *output += axis * scale;
+        }
+        output
+    };
+
+    (point(min), point(max))
+}
+
+/// Estimate the squared error of fitting the points to a line (ignoring quantization).
+///
+/// This is the total (weighted) variance of the points minus the variance along their principal
+/// axis.
+pub(super) fn line_fit_error<const N: usize>(points: &[[f32; N]], weights: &[f32; N]) -> f32 {
+    if points.is_empty() {
+        return 0.0
+    }
+
+    let covariance = covariance(points, &mean(points), &weights.map(sqrt));
+    let axis = principal_axis(&covariance);
+    let total: f32 = (0..N).map(|i| covariance[i][i]).sum();
+    let along_axis = dot(&axis, &mul(&covariance, &axis)) / dot(&axis, &axis);
+
+    (total - along_axis).max(0.0)
+}
+
+/// Solve for the endpoints that minimize the squared error of the points, where each point is
+/// interpolated between the start and end endpoints by its weight (0 = start, 1 = end).
+///
+/// Returns `None` if the system is degenerate (e.g. all points have the same weight).
+pub(super) fn least_squares_endpoints<const N: usize>(
+    points: &[[f32; N]],
+    weights: &[f32]
+) -> Option<([f32; N], [f32; N])> {
+    let mut aa = 0.0f32;
+    let mut ab = 0.0f32;
+    let mut bb = 0.0f32;
+    let mut ax = [0.0f32; N];
+    let mut bx = [0.0f32; N];
+
+    for (point, &beta) in points.iter().zip(weights) {
+        let alpha = 1.0 - beta;
+        aa += alpha * alpha;
+        ab += alpha * beta;
+        bb += beta * beta;
+        for channel in 0..N {
+            ax[channel] += alpha * point[channel];
+            bx[channel] += beta * point[channel];
+        }
+    }
+
+    let determinant = aa * bb - ab * ab;
+    if determinant.abs() < 1e-6 {
+        return None
+    }
+
+    let factor = 1.0 / determinant;
+    let mut start = [0.0f32; N];
+    let mut end = [0.0f32; N];
+    for channel in 0..N {
+        start[channel] = (ax[channel] * bb - bx[channel] * ab) * factor;
+        end[channel] = (bx[channel] * aa - ax[channel] * ab) * factor;
+    }
+
+    Some((start, end))
+}
+
+fn mean<const N: usize>(points: &[[f32; N]]) -> [f32; N] {
+    let count = points.len() as f32;
+    let mut mean = [0.0f32; N];
+    for point in points {
+        for (mean, value) in mean.iter_mut().zip(point) {
+            *mean += value / count;
+        }
+    }
+    mean
+}
+
+/// Get the covariance matrix of the points, with each channel multiplied by `scale`.
+fn covariance<const N: usize>(points: &[[f32; N]], mean: &[f32; N], scale: &[f32; N]) -> [[f32; N]; N] {
+    let mut covariance = [[0.0f32; N]; N];
+    for point in points {
+        let mut d = sub(point, mean);
+        for (d, scale) in d.iter_mut().zip(scale) {
+            *d *= scale;
+        }
+        for row in 0..N {
+            for column in 0..N {
+                covariance[row][column] += d[row] * d[column];
+            }
+        }
+    }
+    covariance
+}
+
+/// Find the dominant eigenvector of a symmetric matrix with power iteration.
+///
+/// If the matrix is zero (i.e. all points are the same), an axis along every channel is returned.
+fn principal_axis<const N: usize>(matrix: &[[f32; N]; N]) -> [f32; N] {
+    // Start with the row with the greatest magnitude, as it's unlikely to be orthogonal to the axis.
+    let mut axis = matrix[0];
+    for row in matrix {
+        if dot(row, row) > dot(&axis, &axis) {
+            axis = *row;
+        }
+    }
+
+    for _ in 0..8 {
+        let next = mul(matrix, &axis);
+        let magnitude = next.iter().fold(0.0f32, |max, value| max.max(value.abs()));
+        if magnitude <= f32::EPSILON {
+            break
+        }
+        axis = next.map(|value| value / magnitude);
+    }
+
+    if dot(&axis, &axis) <= f32::EPSILON {
+        [1.0; N]
+    }
+    else {
+        axis
+    }
+}
+
+fn mul<const N: usize>(matrix: &[[f32; N]; N], vector: &[f32; N]) -> [f32; N] {
+    let mut output = [0.0f32; N];
+    for (output, row) in output.iter_mut().zip(matrix) {
+        *output = dot(row, vector);
+    }
+    output
+}
+
+fn sub<const N: usize>(a: &[f32; N], b: &[f32; N]) -> [f32; N] {
+    let mut output = *a;
+    for (output, b) in output.iter_mut().zip(b) {
+        *output -= b;
+    }
+    output
+}
+
+fn dot<const N: usize>(a: &[f32; N], b: &[f32; N]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
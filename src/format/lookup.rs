@@ -0,0 +1,201 @@
+use crate::metric::{oklab, oklab_distance};
+use crate::{EncodeOptions, ErrorMetric, Pixel};
+
+/// Number of pixels remembered by [`PaletteLookup`] (must be a power of two).
+const CACHE_SIZE: usize = 1024;
+
+/// Finds the closest palette color for pixels, giving the same result as checking every color in
+/// the palette (including picking the lowest index for ties).
+///
+/// Colors are sorted by green, and the search starts from the pixel's green and works outward until
+/// the difference in green alone is more than the closest distance found. Green's weight is the
+/// same for every pixel with every weighted RGB metric, so this never skips a closer color. With
+/// [`ErrorMetric::Oklab`], colors are sorted by lightness instead, which is one of the coordinates
+/// the distance is measured in. Recent results are also cached, since textures usually repeat
+/// colors a lot.
+pub(crate) struct PaletteLookup<'a> {
+    palette: &'a [Pixel],
+    metric: ErrorMetric,
+    alpha_threshold: u8,
+    transparent_index: Option<usize>,
+    one_bit_alpha: bool,
+    use_alpha: bool,
+
+    /// Oklab coordinates of each color, if the metric is [`ErrorMetric::Oklab`].
+    oklab: Option<[[i32; 3]; 256]>,
+
+    /// Indices of colors that opaque pixels (or all pixels without one-bit alpha) can use, sorted by
+    /// [`sort_key`](Self::sort_key), along with how many there are.
+    opaque: ([u8; 256], usize),
+
+    /// Indices of colors that transparent pixels can use with one-bit alpha, sorted the same way.
+    transparent: ([u8; 256], usize),
+
+    /// Recent pixels as A8R8G8B8 in the upper 32 bits, with their index plus one in the lower bits
+    /// (so 0 is an empty slot).
+    cache: [u64; CACHE_SIZE]
+}
+
+impl<'a> PaletteLookup<'a> {
+    /// Set up a lookup for the palette.
+    ///
+    /// If `options.transparent_index` is set, pixels with an alpha of `options.alpha_threshold` or
+    /// less always use it, and no other pixels do. Otherwise, if the palette only has fully
+    /// transparent and fully opaque colors, pixels are only matched with colors on the same side of
+    /// the threshold.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `palette.is_empty()` or `palette.len() > 256`
+    pub(crate) fn new(palette: &'a [Pixel], options: &EncodeOptions) -> Self {
+        assert!(!palette.is_empty(), "empty palette");
+        assert!(palette.len() <= 256, "palette has more than 256 colors");
+
+        let transparent_index = options.transparent_index.map(usize::from).filter(|i| *i < palette.len());
+        let candidates = || palette.iter().enumerate().filter(move |(i, _)| Some(*i) != transparent_index);
+
+        let mut has_opaque = false;
+        let mut has_transparent = false;
+        let mut has_translucent = false;
+        for (_, pixel) in candidates() {
+            match pixel.alpha {
+                255 => has_opaque = true,
+                0 => has_transparent = true,
+                _ => has_translucent = true
+            }
+        }
+
+        // If the palette only has 100% transparent and 100% opaque colors, consider alpha as binary
+        // rather than calculating the difference. If they're all one or the other, it doesn't make
+        // sense to check alpha when finding a pixel at all.
+        let one_bit_alpha = has_opaque && has_transparent && !has_translucent;
+
+        let oklab = (options.metric == ErrorMetric::Oklab).then(|| {
+            let mut coordinates = [[0; 3]; 256];
+            for (coordinates, pixel) in coordinates.iter_mut().zip(palette) {
+                *coordinates = oklab(*pixel);
+            }
+            coordinates
+        });
+
+        let mut opaque = ([0u8; 256], 0);
+        let mut transparent = ([0u8; 256], 0);
+        for (i, pixel) in candidates() {
+            let (indices, count) = if one_bit_alpha && pixel.alpha == 0 { &mut transparent } else { &mut opaque };
+            indices[*count] = i as u8;
+            *count += 1;
+        }
+        let mut lookup = Self {
+            palette,
+            metric: options.metric,
+            alpha_threshold: options.alpha_threshold,
+            transparent_index,
+            one_bit_alpha,
+            use_alpha: has_translucent,
+            oklab,
+            opaque,
+            transparent,
+            cache: [0; CACHE_SIZE]
+        };
+        for (indices, count) in [&mut lookup.opaque, &mut lookup.transparent] {
+            indices[..*count].sort_unstable_by_key(|i| (Self::sort_key(&lookup.oklab, palette, *i as usize), *i));
+        }
+        lookup
+    }
+
+    /// Get the coordinate colors are sorted by (green, or Oklab lightness).
+    fn sort_key(oklab: &Option<[[i32; 3]; 256]>, palette: &[Pixel], index: usize) -> i32 {
+        match oklab {
+            Some(coordinates) => coordinates[index][0],
+            None => palette[index].green as i32
+        }
+    }
+
+    /// Find the index of the closest color to the pixel.
+    pub(crate) fn find(&mut self, pixel: Pixel) -> usize {
+        let transparent = pixel.alpha <= self.alpha_threshold;
+        if let (true, Some(index)) = (transparent, self.transparent_index) {
+            return index
+        }
+
+        let key = u32::from_le_bytes(pixel.as_a8r8g8b8());
+        let slot = (key.wrapping_mul(0x9E37_79B9) >> (32 - CACHE_SIZE.trailing_zeros())) as usize;
+        let cached = self.cache[slot];
+        if cached != 0 && (cached >> 32) as u32 == key {
+            return (cached as u32 - 1) as usize
+        }
+
+        let (indices, count) = if self.one_bit_alpha && transparent { &self.transparent } else { &self.opaque };
+
+        // The only color in the palette may be the transparent key.
+        let index = self.search(pixel, &indices[..*count]).or(self.transparent_index).expect("no pixels found");
+
+        self.cache[slot] = (key as u64) << 32 | (index as u64 + 1);
+        index
+    }
+
+    /// Search colors sorted by [`sort_key`](Self::sort_key) for the closest one.
+    fn search(&self, pixel: Pixel, indices: &[u8]) -> Option<usize> {
+        let pixel_oklab = if self.oklab.is_some() { oklab(pixel) } else { [0; 3] };
+        let alpha_weight = if self.use_alpha { self.metric.alpha_weight() } else { 0 };
+        let get_distance = |index: usize| match &self.oklab {
+            Some(coordinates) => {
+                let alpha_distance = self.palette[index].alpha as i32 - pixel.alpha as i32;
+                oklab_distance(coordinates[index], pixel_oklab) + (alpha_distance * alpha_distance) as u32 * alpha_weight
+            },
+            None if self.use_alpha => self.metric.distance_argb(self.palette[index], pixel),
+            None => self.metric.distance_rgb(self.palette[index], pixel)
+        };
+
+        // A difference of 1 in the sort key adds at least this much to the distance.
+        let (pixel_key, key_weight) = match self.oklab {
+            Some(_) => (pixel_oklab[0], 1),
+            None => (pixel.green as i32, self.metric.channel_weights(0)[1])
+        };
+        let key_difference = |position: usize| (Self::sort_key(&self.oklab, self.palette, indices[position] as usize) - pixel_key).unsigned_abs();
+
+        let mut best: Option<(u32, usize)> = None;
+        let mut below = indices.partition_point(|i| Self::sort_key(&self.oklab, self.palette, *i as usize) < pixel_key);
+        let mut above = below;
+
+        loop {
+            let below_difference = below.checked_sub(1).map(key_difference);
+            let above_difference = (above < indices.len()).then(|| key_difference(above));
+
+            // Check whichever side is closer in the sort key next.
+            let (position, difference) = match (below_difference, above_difference) {
+                (Some(b), Some(a)) if b <= a => (below - 1, b),
+                (_, Some(a)) => (above, a),
+                (Some(b), None) => (below - 1, b),
+                (None, None) => break
+            };
+
+            // Everything else is at least this far away in the sort key alone. Ties still have to
+            // be checked since lower indices win.
+            if let Some((distance, _)) = best {
+                if difference * difference * key_weight > distance {
+                    break
+                }
+            }
+
+            if position < below {
+                below -= 1;
+            }
+            else {
+                above += 1;
+            }
+
+            let index = indices[position] as usize;
+            let distance = get_distance(index);
+            let closer = match best {
+                Some((best_distance, best_index)) => distance < best_distance || (distance == best_distance && index < best_index),
+                None => true
+            };
+            if closer {
+                best = Some((distance, index));
+            }
+        }
+
+        best.map(|(_, index)| index)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{BC7Profile, ColorSpace, Dither, ErrorMetric};
+
+/// Options for [`Format::encode_pixels_with`](crate::Format::encode_pixels_with).
+///
+/// Options that don't apply to the format being encoded are ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::{BC7Profile, EncodeOptions, ErrorMetric};
+///
+/// let options = EncodeOptions {
+///     bc7_profile: BC7Profile::Thorough,
+///     alpha_threshold: 200,
+///     metric: ErrorMetric::Rec601,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EncodeOptions {
+    /// Speed and quality trade-off used for BC7.
+    pub bc7_profile: BC7Profile,
+
+    /// Highest alpha value that is considered transparent for formats with 1-bit alpha
+    /// (`DXT1` and `A1R5G5B5`), and for `P8` palettes that only have fully transparent and fully
+    /// opaque colors or use [`transparent_index`](Self::transparent_index).
+    ///
+    /// The default is 127.
+    pub alpha_threshold: u8,
+
+    /// Palette index reserved for transparent pixels when encoding `P8`.
+    ///
+    /// If set, pixels at or below [`alpha_threshold`](Self::alpha_threshold) are always encoded as
+    /// this index, and no other pixels are. Many P8 bitmaps use 0 or 255 for this.
+    ///
+    /// The default is `None`.
+    pub transparent_index: Option<u8>,
+
+    /// How the difference between colors is measured when matching palette entries and fitting
+    /// compressed blocks.
+    pub metric: ErrorMetric,
+
+    /// Color space of the pixels.
+    ///
+    /// With [`ColorSpace::Srgb`], block-compressed formats measure color error in linear light
+    /// (the stored endpoints are still sRGB).
+    pub color_space: ColorSpace,
+
+    /// How pixels are dithered when encoding to 16-bit, 4-bit, and palettized formats.
+    ///
+    /// Error diffusion is only exact for images wider than 256 pixels when an error buffer is
+    /// passed to [`Format::encode_pixels_with_buffer`](crate::Format::encode_pixels_with_buffer)
+    /// (which `Image::encode_with` does with the `alloc` feature). Otherwise, it's done in
+    /// overlapping strips.
+    pub dither: Dither
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            bc7_profile: BC7Profile::default(),
+            alpha_threshold: 127,
+            transparent_index: None,
+            metric: ErrorMetric::default(),
+            color_space: ColorSpace::default(),
+            dither: Dither::default()
+        }
+    }
+}
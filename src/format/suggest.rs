@@ -0,0 +1,362 @@
+use super::check_length;
+use crate::{EncodeOptions, Error, Format, Pixel};
+
+/// Formats considered by [`Format::suggest`], in order of preference when two are equally good.
+///
+/// Palettized formats are placeholders for the image's own palette.
+const CANDIDATES: [Format<'static>; 19] = [
+    Format::DXT1, Format::DXT3, Format::DXT5, Format::BC7,
+    Format::A4, Format::Y4, Format::A4Y4, Format::A8, Format::Y8, Format::AY8, Format::A8Y8, Format::P4(&[]), Format::P8(&[]),
+    Format::R5G6B5, Format::A1R5G5B5, Format::A4R4G4B4, Format::X8R8G8B8, Format::A8R8G8B8, Format::A8B8G8R8
+];
+
+/// Images are encoded in tiles this many pixels wide and tall to measure error without allocating.
+///
+/// This is a multiple of every block size, so each tile is encoded the same as it would be in the
+/// full image.
+const TILE_SIZE: usize = 16;
+
+/// Defines how [`Format::suggest`] picks a format.
+///
+/// Formats that are equally good are picked in a fixed order, such as preferring
+/// [`X8R8G8B8`](Format::X8R8G8B8) over [`A8R8G8B8`](Format::A8R8G8B8) for opaque images.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FormatPolicy {
+    /// Pick the smallest block-compressed format that can store the image's alpha, and of those
+    /// the same size, the one with the least error.
+    ///
+    /// Opaque images and images with binary alpha use [`DXT1`](Format::DXT1), and other images use
+    /// whichever of [`DXT3`](Format::DXT3), [`DXT5`](Format::DXT5), or [`BC7`](Format::BC7) has the
+    /// least error.
+    PreferCompressed,
+
+    /// Pick the smallest format that stores the image without any error.
+    ///
+    /// Block-compressed formats are not considered, so this never needs to compress the image.
+    LosslessOnly,
+
+    /// Pick the format with the least error that takes up at most this many bytes (including the
+    /// palette of palettized formats), and of those with the same error, the smallest.
+    MaxBytes(usize)
+}
+
+/// How an image uses its alpha channel.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AlphaKind {
+    /// Every pixel has 255 alpha.
+    Opaque,
+
+    /// Every pixel has 0 or 255 alpha.
+    Binary,
+
+    /// Some pixels have alpha between 0 and 255.
+    Full
+}
+
+/// What [`Format::suggest`] found about an image.
+///
+/// The color of pixels with 0 alpha is not visible, so they are all treated as transparent black.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ImageAnalysis {
+    /// How the image uses alpha.
+    pub alpha: AlphaKind,
+
+    /// Every pixel is gray, with red, green, and blue being equal.
+    pub monochrome: bool,
+
+    /// Every pixel has red, green, and blue equal to its alpha, which [`AY8`](Format::AY8) stores
+    /// exactly.
+    pub alpha_is_luminance: bool,
+
+    /// Number of distinct colors, or `None` if there are more than 256.
+    pub unique_colors: Option<usize>
+}
+
+/// A format considered by [`Format::suggest`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FormatCandidate<'a> {
+    /// Format, which uses the image's own colors as the palette if it is palettized.
+    pub format: Format<'a>,
+
+    /// Number of bytes the image takes up in the format.
+    ///
+    /// For palettized formats, this includes 4 bytes for each color in the palette.
+    pub bytes: usize,
+
+    /// Mean squared error of the alpha, red, green, and blue channels after encoding and decoding,
+    /// where 0 is lossless.
+    pub error: f64
+}
+
+/// A format picked by [`Format::suggest`], along with every format considered.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FormatSuggestion {
+    analysis: ImageAnalysis,
+    palette: [Pixel; 256],
+    palette_length: usize,
+    candidates: [FormatCandidate<'static>; CANDIDATES.len()],
+    candidate_count: usize,
+    best: Option<usize>
+}
+
+impl FormatSuggestion {
+    /// Get what was found about the image.
+    pub fn analysis(&self) -> ImageAnalysis {
+        self.analysis
+    }
+
+    /// Get the picked format, or `None` if no format meets the policy.
+    ///
+    /// This can only be `None` with [`FormatPolicy::MaxBytes`].
+    pub fn best(&self) -> Option<FormatCandidate<'_>> {
+        self.best.map(|index| self.resolve(self.candidates[index]))
+    }
+
+    /// Get every format considered, with its size and error.
+    ///
+    /// Formats that the policy rules out without encoding, such as formats that are too big for
+    /// [`FormatPolicy::MaxBytes`], are not included.
+    pub fn candidates(&self) -> impl Iterator<Item = FormatCandidate<'_>> + '_ {
+        self.candidates[..self.candidate_count].iter().map(|candidate| self.resolve(*candidate))
+    }
+
+    /// Replace the placeholder palette of a candidate with the image's colors.
+    fn resolve(&self, candidate: FormatCandidate<'static>) -> FormatCandidate<'_> {
+        let palette = &self.palette[..self.palette_length];
+        let format = match candidate.format {
+            Format::P8(_) => Format::P8(palette),
+            Format::P4(_) => Format::P4(palette),
+            format => format
+        };
+        FormatCandidate { format, ..candidate }
+    }
+}
+
+impl Format<'_> {
+    /// Analyze an image and pick a format for it.
+    ///
+    /// This is the same as [`suggest_with`](Format::suggest_with) with the default
+    /// [`EncodeOptions`].
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `pixels.len() != width*height` or if a format's size overflows
+    /// [usize].
+    pub fn suggest(pixels: &[Pixel], width: usize, height: usize, policy: FormatPolicy) -> FormatSuggestion {
+        Self::suggest_with(&EncodeOptions::default(), pixels, width, height, policy)
+    }
+
+    /// Analyze an image and pick a format for it, encoding with the given options to measure
+    /// error.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `pixels.len() != width*height` or if a format's size overflows
+    /// [usize].
+    pub fn suggest_with(
+        options: &EncodeOptions,
+        pixels: &[Pixel],
+        width: usize,
+        height: usize,
+        policy: FormatPolicy
+    ) -> FormatSuggestion {
+        match Self::try_suggest_with(options, pixels, width, height, policy) {
+            Ok(suggestion) => suggestion,
+            Err(e) => panic!("suggest_with(): {e}")
+        }
+    }
+
+    /// Analyze an image and pick a format for it, returning an error instead of panicking.
+    ///
+    /// This is the same as [`try_suggest_with`](Format::try_suggest_with) with the default
+    /// [`EncodeOptions`].
+    pub fn try_suggest(pixels: &[Pixel], width: usize, height: usize, policy: FormatPolicy) -> Result<FormatSuggestion, Error> {
+        Self::try_suggest_with(&EncodeOptions::default(), pixels, width, height, policy)
+    }
+
+    /// Analyze an image and pick a format for it, encoding with the given options to measure
+    /// error, and returning an error instead of panicking.
+    ///
+    /// Each candidate format is encoded and decoded to measure its error, so this can take as long
+    /// as encoding the image to every candidate. The image is encoded in 16x16 tiles, so error
+    /// diffusion [`Dither`](crate::Dither) doesn't carry over between tiles, and the error may
+    /// differ slightly from encoding the whole image.
+    ///
+    /// Palettized formats are only considered if the image has few enough colors to use them
+    /// losslessly, with the image's colors as the palette.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::SizeOverflow`] if `width*height` or the size of a format overflows [usize]
+    /// * [`Error::InvalidInputLength`] if `pixels.len() != width*height`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{AlphaKind, Format, FormatPolicy, Pixel};
+    ///
+    /// let gray = |value| Pixel { alpha: 255, red: value, green: value, blue: value };
+    /// let image: [Pixel; 64] = core::array::from_fn(|i| gray(i as u8 * 4));
+    ///
+    /// let suggestion = Format::try_suggest(&image, 8, 8, FormatPolicy::LosslessOnly).unwrap();
+    /// assert_eq!(AlphaKind::Opaque, suggestion.analysis().alpha);
+    /// assert!(suggestion.analysis().monochrome);
+    /// assert_eq!(Format::Y8, suggestion.best().unwrap().format);
+    ///
+    /// let suggestion = Format::try_suggest(&image, 8, 8, FormatPolicy::PreferCompressed).unwrap();
+    /// assert_eq!(Format::DXT1, suggestion.best().unwrap().format);
+    /// assert!(suggestion.best().unwrap().error > 0.0);
+    /// ```
+    pub fn try_suggest_with(
+        options: &EncodeOptions,
+        pixels: &[Pixel],
+        width: usize,
+        height: usize,
+        policy: FormatPolicy
+    ) -> Result<FormatSuggestion, Error> {
+        check_length(width.checked_mul(height).ok_or(Error::SizeOverflow)?, pixels.len(), true)?;
+
+        let mut palette = [Pixel::default(); 256];
+        let analysis = analyze(pixels, &mut palette);
+        let palette_length = analysis.unique_colors.unwrap_or(0);
+
+        let mut candidates = [FormatCandidate { format: Format::A8, bytes: 0, error: 0.0 }; CANDIDATES.len()];
+        let mut candidate_count = 0;
+        for format in CANDIDATES {
+            let (format_with_palette, usable) = match format {
+                Format::P8(_) => (Format::P8(&palette[..palette_length]), palette_length > 0),
+                Format::P4(_) => (Format::P4(&palette[..palette_length]), (1..=16).contains(&palette_length)),
+                format => (format, true)
+            };
+
+            // Palettized formats also need to store the palette, as A8R8G8B8.
+            let palette_bytes = if format.is_palettized() { palette_length * 4 } else { 0 };
+            let bytes = format.checked_required_bytes(width, height)?.checked_add(palette_bytes).ok_or(Error::SizeOverflow)?;
+            let allowed = match policy {
+                FormatPolicy::PreferCompressed => true,
+                FormatPolicy::LosslessOnly => !format.is_compressed(),
+                FormatPolicy::MaxBytes(max) => bytes <= max
+            };
+            if usable && allowed {
+                let error = measure_error(options, format_with_palette, pixels, width, height);
+                candidates[candidate_count] = FormatCandidate { format, bytes, error };
+                candidate_count += 1;
+            }
+        }
+
+        let considered = &candidates[..candidate_count];
+        let smaller = |a: &FormatCandidate, b: &FormatCandidate| (a.bytes, a.error) < (b.bytes, b.error);
+        let best = match policy {
+            FormatPolicy::PreferCompressed => {
+                let alpha_bits = match analysis.alpha {
+                    AlphaKind::Opaque => 0,
+                    AlphaKind::Binary => 1,
+                    AlphaKind::Full => 2
+                };
+                pick(considered, |c| c.format.is_compressed() && c.format.alpha_bits() >= alpha_bits, smaller)
+            },
+            FormatPolicy::LosslessOnly => pick(considered, |c| c.error == 0.0, smaller),
+            FormatPolicy::MaxBytes(_) => pick(considered, |_| true, |a, b| (a.error, a.bytes) < (b.error, b.bytes))
+        };
+
+        Ok(FormatSuggestion { analysis, palette, palette_length, candidates, candidate_count, best })
+    }
+}
+
+/// Get the index of the best eligible candidate, where earlier candidates win ties.
+fn pick(
+    candidates: &[FormatCandidate],
+    eligible: impl Fn(&FormatCandidate) -> bool,
+    better: impl Fn(&FormatCandidate, &FormatCandidate) -> bool
+) -> Option<usize> {
+    let mut best: Option<usize> = None;
+    for (index, candidate) in candidates.iter().enumerate() {
+        if eligible(candidate) && best.is_none_or(|best| better(candidate, &candidates[best])) {
+            best = Some(index);
+        }
+    }
+    best
+}
+
+/// Get the color a pixel is seen as, where every pixel with 0 alpha is transparent black.
+const fn visible(pixel: Pixel) -> Pixel {
+    if pixel.alpha == 0 { Pixel { alpha: 0, red: 0, green: 0, blue: 0 } } else { pixel }
+}
+
+/// Analyze an image, writing its colors to the start of `palette` if there are at most 256.
+fn analyze(pixels: &[Pixel], palette: &mut [Pixel; 256]) -> ImageAnalysis {
+    let mut opaque = true;
+    let mut binary = true;
+    let mut monochrome = true;
+    let mut alpha_is_luminance = true;
+
+    // Colors are kept sorted to find them quickly.
+    let mut colors = [0u32; 256];
+    let mut color_count = Some(0);
+
+    for pixel in pixels.iter().copied().map(visible) {
+        opaque &= pixel.alpha == 255;
+        binary &= pixel.alpha == 0 || pixel.alpha == 255;
+        monochrome &= pixel.red == pixel.green && pixel.green == pixel.blue;
+        alpha_is_luminance &= pixel.red == pixel.alpha && pixel.green == pixel.alpha && pixel.blue == pixel.alpha;
+
+        if let Some(count) = color_count {
+            let key = u32::from_be_bytes([pixel.alpha, pixel.red, pixel.green, pixel.blue]);
+            if let Err(index) = colors[..count].binary_search(&key) {
+                if count == colors.len() {
+                    color_count = None;
+                    continue
+                }
+                colors.copy_within(index..count, index + 1);
+                colors[index] = key;
+                color_count = Some(count + 1);
+            }
+        }
+    }
+
+    if let Some(count) = color_count {
+        for (color, key) in palette.iter_mut().zip(&colors[..count]) {
+            let [alpha, red, green, blue] = key.to_be_bytes();
+            *color = Pixel { alpha, red, green, blue };
+        }
+    }
+
+    let alpha = if opaque { AlphaKind::Opaque } else if binary { AlphaKind::Binary } else { AlphaKind::Full };
+    ImageAnalysis { alpha, monochrome, alpha_is_luminance, unique_colors: color_count }
+}
+
+/// Get the mean squared error of encoding an image with a format and decoding it again.
+fn measure_error(options: &EncodeOptions, format: Format, pixels: &[Pixel], width: usize, height: usize) -> f64 {
+    if pixels.is_empty() {
+        return 0.0
+    }
+
+    let mut tile = [Pixel::default(); TILE_SIZE * TILE_SIZE];
+    let mut decoded = [Pixel::default(); TILE_SIZE * TILE_SIZE];
+    let mut bytes = [0u8; TILE_SIZE * TILE_SIZE * 4];
+    let mut total = 0u64;
+
+    for tile_y in (0..height).step_by(TILE_SIZE) {
+        for tile_x in (0..width).step_by(TILE_SIZE) {
+            let tile_width = (width - tile_x).min(TILE_SIZE);
+            let tile_height = (height - tile_y).min(TILE_SIZE);
+            let count = tile_width * tile_height;
+            for (y, row) in tile[..count].chunks_exact_mut(tile_width).enumerate() {
+                row.copy_from_slice(&pixels[(tile_y + y) * width + tile_x..][..tile_width]);
+            }
+
+            let bytes = &mut bytes[..format.required_bytes(tile_width, tile_height)];
+            format.encode_pixels_with(options, &tile[..count], bytes, tile_width, tile_height);
+            format.decode_pixels(bytes, &mut decoded[..count], tile_width, tile_height);
+
+            for (a, b) in tile[..count].iter().zip(&decoded[..count]) {
+                let (a, b) = (visible(*a), visible(*b));
+                for (a, b) in [(a.alpha, b.alpha), (a.red, b.red), (a.green, b.green), (a.blue, b.blue)] {
+                    total += (a.abs_diff(b) as u64).pow(2);
+                }
+            }
+        }
+    }
+
+    total as f64 / (pixels.len() * 4) as f64
+}
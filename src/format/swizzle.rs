@@ -0,0 +1,176 @@
+use super::check_length;
+use crate::{Error, Format};
+
+impl Format<'_> {
+    /// Reorder an image from row-major order to the swizzled (Morton order) layout used by the
+    /// original Xbox, in place.
+    ///
+    /// Volume textures have each depth slice after the last, and 2D textures have a depth of 1.
+    /// Each mipmap, cubemap face, or other image is swizzled on its own.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if [`try_swizzle`](Format::try_swizzle) would return an error.
+    pub fn swizzle(self, bytes: &mut [u8], width: usize, height: usize, depth: usize) {
+        if let Err(e) = self.try_swizzle(bytes, width, height, depth) {
+            panic!("swizzle(): {e}");
+        }
+    }
+
+    /// Reorder an image from row-major order to the swizzled (Morton order) layout used by the
+    /// original Xbox, in place, returning an error instead of panicking.
+    ///
+    /// Pixels are swizzled by interleaving the bits of their x, y, and z coordinates, starting with
+    /// x in the lowest bit. Once a dimension runs out of bits, the remaining dimensions continue
+    /// interleaving without it, so non-square sizes are supported.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::UnsupportedFormat`] if the format doesn't have one pixel per block
+    /// * [`Error::NonPowerOfTwo`] if the width, height, or depth is not a power of two
+    /// * [`Error::SizeOverflow`] if the required bytes overflow [usize]
+    /// * [`Error::InvalidInputLength`] if `bytes.len()` isn't the required bytes for
+    ///   width*height*depth
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Format;
+    ///
+    /// let mut bytes: [u8; 16] = core::array::from_fn(|i| i as u8);
+    /// Format::A8.try_swizzle(&mut bytes, 4, 4, 1).unwrap();
+    /// assert_eq!([0, 1, 4, 5, 2, 3, 6, 7, 8, 9, 12, 13, 10, 11, 14, 15], bytes);
+    ///
+    /// Format::A8.try_unswizzle(&mut bytes, 4, 4, 1).unwrap();
+    /// assert_eq!(core::array::from_fn::<u8, 16, _>(|i| i as u8), bytes);
+    /// ```
+    pub fn try_swizzle(self, bytes: &mut [u8], width: usize, height: usize, depth: usize) -> Result<(), Error> {
+        let masks = self.check_swizzle(bytes, width, height, depth)?;
+        let (width_bits, height_bits) = (width.trailing_zeros(), height.trailing_zeros());
+        permute(bytes, self.block_size_bytes(), |linear| {
+            let x = linear & (width - 1);
+            let y = (linear >> width_bits) & (height - 1);
+            let z = linear >> (width_bits + height_bits);
+            deposit_bits(x, masks[0]) | deposit_bits(y, masks[1]) | deposit_bits(z, masks[2])
+        });
+        Ok(())
+    }
+
+    /// Reorder an image from the swizzled (Morton order) layout used by the original Xbox to
+    /// row-major order, in place.
+    ///
+    /// This reverses [`swizzle`](Format::swizzle).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if [`try_unswizzle`](Format::try_unswizzle) would return an error.
+    pub fn unswizzle(self, bytes: &mut [u8], width: usize, height: usize, depth: usize) {
+        if let Err(e) = self.try_unswizzle(bytes, width, height, depth) {
+            panic!("unswizzle(): {e}");
+        }
+    }
+
+    /// Reorder an image from the swizzled (Morton order) layout used by the original Xbox to
+    /// row-major order, in place, returning an error instead of panicking.
+    ///
+    /// This reverses [`try_swizzle`](Format::try_swizzle).
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`try_swizzle`](Format::try_swizzle).
+    pub fn try_unswizzle(self, bytes: &mut [u8], width: usize, height: usize, depth: usize) -> Result<(), Error> {
+        let masks = self.check_swizzle(bytes, width, height, depth)?;
+        let (width_bits, height_bits) = (width.trailing_zeros(), height.trailing_zeros());
+        permute(bytes, self.block_size_bytes(), |swizzled| {
+            let x = extract_bits(swizzled, masks[0]);
+            let y = extract_bits(swizzled, masks[1]);
+            let z = extract_bits(swizzled, masks[2]);
+            x | (y << width_bits) | (z << (width_bits + height_bits))
+        });
+        Ok(())
+    }
+
+    /// Check the format, size, and buffer length, returning the x, y, and z swizzle masks.
+    fn check_swizzle(self, bytes: &[u8], width: usize, height: usize, depth: usize) -> Result<[usize; 3], Error> {
+        if self.block_size_pixels() != (1, 1) {
+            return Err(Error::UnsupportedFormat)
+        }
+        if !(width.is_power_of_two() && height.is_power_of_two() && depth.is_power_of_two()) {
+            return Err(Error::NonPowerOfTwo)
+        }
+        let expected = self.checked_required_bytes(width, height)?.checked_mul(depth).ok_or(Error::SizeOverflow)?;
+        check_length(expected, bytes.len(), true)?;
+
+        let mut masks = [0usize; 3];
+        let mut bit = 1;
+        let mut mask_bit = 1;
+        while bit < width || bit < height || bit < depth {
+            for (mask, size) in masks.iter_mut().zip([width, height, depth]) {
+                if bit < size {
+                    *mask |= mask_bit;
+                    mask_bit <<= 1;
+                }
+            }
+            bit <<= 1;
+        }
+        Ok(masks)
+    }
+}
+
+/// Spread the low bits of `value` out to the set bits of `mask`.
+fn deposit_bits(mut value: usize, mut mask: usize) -> usize {
+    let mut result = 0;
+    while mask != 0 {
+        let bit = mask & mask.wrapping_neg();
+        if value & 1 != 0 {
+            result |= bit;
+        }
+        value >>= 1;
+        mask &= mask - 1;
+    }
+    result
+}
+
+/// Gather the bits of `value` at the set bits of `mask` into the low bits.
+fn extract_bits(value: usize, mut mask: usize) -> usize {
+    let mut result = 0;
+    let mut shift = 0;
+    while mask != 0 {
+        let bit = mask & mask.wrapping_neg();
+        if value & bit != 0 {
+            result |= 1 << shift;
+        }
+        shift += 1;
+        mask &= mask - 1;
+    }
+    result
+}
+
+/// Move each element of `bytes` from index `i` to `destination(i)`, in place.
+///
+/// Each cycle of the permutation is rotated once, starting from its lowest index.
+fn permute(bytes: &mut [u8], element_size: usize, destination: impl Fn(usize) -> usize) {
+    let count = bytes.len() / element_size;
+    let mut carry = [0u8; 4];
+    let carry = &mut carry[..element_size];
+
+    for start in 0..count {
+        let mut index = destination(start);
+        while index > start {
+            index = destination(index);
+        }
+        if index != start {
+            continue
+        }
+
+        carry.copy_from_slice(&bytes[start * element_size..][..element_size]);
+        let mut index = destination(start);
+        loop {
+            carry.swap_with_slice(&mut bytes[index * element_size..][..element_size]);
+            if index == start {
+                break
+            }
+            index = destination(index);
+        }
+    }
+}
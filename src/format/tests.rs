@@ -1,9 +1,9 @@
-use crate::{Format, Pixel};
+use crate::{AlphaKind, BC7Profile, ColorSpace, Dither, EncodeOptions, Error, ErrorMetric, Format, FormatPolicy, Pixel};
 
 // 128x128 bmp of the Ringhopper penguin
-const RINGHOPPER_PENGY: &'static [u8] = include_bytes!("ringhopper.bmp");
+const RINGHOPPER_PENGY: &[u8] = include_bytes!("ringhopper.bmp");
 
-fn open_ringhopper_image() -> [Pixel; 128*128] {
+pub(crate) fn open_ringhopper_image() -> [Pixel; 128*128] {
     let data = RINGHOPPER_PENGY[138..].chunks(4);
     let mut output = [Pixel::default(); 128*128];
 
@@ -60,276 +60,1294 @@ pub fn roundtrip_uncompressed() {
     roundtrip_fmt!(Format::X8R8G8B8);
 }
 
+// This palette was generated by GIMP for the 128x128 ringhopper.bmp image.
+const RINGHOPPER_PALETTE: [Pixel; 256] = [
+    Pixel { blue: 0x4C, green: 0x0A, red: 0x1E, alpha: 0xFF },
+    Pixel { blue: 0x20, green: 0x15, red: 0x13, alpha: 0xFF },
+    Pixel { blue: 0x18, green: 0x16, red: 0x14, alpha: 0xFF },
+    Pixel { blue: 0x1D, green: 0x16, red: 0x16, alpha: 0xFF },
+    Pixel { blue: 0x23, green: 0x17, red: 0x15, alpha: 0xFF },
+    Pixel { blue: 0x23, green: 0x16, red: 0x1A, alpha: 0xFF },
+    Pixel { blue: 0x26, green: 0x1A, red: 0x17, alpha: 0xFF },
+    Pixel { blue: 0x1C, green: 0x1B, red: 0x17, alpha: 0xFF },
+    Pixel { blue: 0x2A, green: 0x19, red: 0x1A, alpha: 0xFF },
+    Pixel { blue: 0x2B, green: 0x18, red: 0x1F, alpha: 0xFF },
+    Pixel { blue: 0x23, green: 0x1A, red: 0x20, alpha: 0xFF },
+    Pixel { blue: 0x20, green: 0x1E, red: 0x1B, alpha: 0xFF },
+    Pixel { blue: 0x2E, green: 0x1D, red: 0x1E, alpha: 0xFF },
+    Pixel { blue: 0x2F, green: 0x1C, red: 0x23, alpha: 0xFF },
+    Pixel { blue: 0x4F, green: 0x1A, red: 0x22, alpha: 0xFF },
+    Pixel { blue: 0x2B, green: 0x1E, red: 0x22, alpha: 0xFF },
+    Pixel { blue: 0x1E, green: 0x21, red: 0x1F, alpha: 0xFF },
+    Pixel { blue: 0x23, green: 0x21, red: 0x1E, alpha: 0xFF },
+    Pixel { blue: 0x2D, green: 0x21, red: 0x1D, alpha: 0xFF },
+    Pixel { blue: 0x2E, green: 0x1F, red: 0x28, alpha: 0xFF },
+    Pixel { blue: 0x37, green: 0x21, red: 0x20, alpha: 0xFF },
+    Pixel { blue: 0x2A, green: 0x22, red: 0x21, alpha: 0xFF },
+    Pixel { blue: 0x38, green: 0x20, red: 0x25, alpha: 0xFF },
+    Pixel { blue: 0x27, green: 0x25, red: 0x22, alpha: 0xFF },
+    Pixel { blue: 0x23, green: 0x25, red: 0x24, alpha: 0xFF },
+    Pixel { blue: 0x28, green: 0x23, red: 0x2C, alpha: 0xFF },
+    Pixel { blue: 0x41, green: 0x25, red: 0x27, alpha: 0xFF },
+    Pixel { blue: 0x42, green: 0x27, red: 0x22, alpha: 0xFF },
+    Pixel { blue: 0x42, green: 0x24, red: 0x2D, alpha: 0xFF },
+    Pixel { blue: 0x2B, green: 0x29, red: 0x26, alpha: 0xFF },
+    Pixel { blue: 0x3F, green: 0x28, red: 0x27, alpha: 0xFF },
+    Pixel { blue: 0x28, green: 0x2A, red: 0x29, alpha: 0xFF },
+    Pixel { blue: 0x3F, green: 0x28, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x46, green: 0x29, red: 0x2B, alpha: 0xFF },
+    Pixel { blue: 0x4B, green: 0x28, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x33, green: 0x2C, red: 0x28, alpha: 0xFF },
+    Pixel { blue: 0x48, green: 0x2B, red: 0x26, alpha: 0xFF },
+    Pixel { blue: 0x39, green: 0x29, red: 0x33, alpha: 0xFF },
+    Pixel { blue: 0x2F, green: 0x2D, red: 0x29, alpha: 0xFF },
+    Pixel { blue: 0x39, green: 0x2D, red: 0x28, alpha: 0xFF },
+    Pixel { blue: 0x39, green: 0x2B, red: 0x2F, alpha: 0xFF },
+    Pixel { blue: 0x4D, green: 0x2B, red: 0x2B, alpha: 0xFF },
+    Pixel { blue: 0x40, green: 0x2E, red: 0x26, alpha: 0xFF },
+    Pixel { blue: 0x2D, green: 0x2F, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x32, green: 0x30, red: 0x2D, alpha: 0xFF },
+    Pixel { blue: 0x51, green: 0x2E, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x34, green: 0x2D, red: 0x3A, alpha: 0xFF },
+    Pixel { blue: 0x51, green: 0x2D, red: 0x33, alpha: 0xFF },
+    Pixel { blue: 0x2B, green: 0x2E, red: 0x3D, alpha: 0xFF },
+    Pixel { blue: 0x52, green: 0x32, red: 0x2A, alpha: 0xFF },
+    Pixel { blue: 0x4E, green: 0x30, red: 0x35, alpha: 0xFF },
+    Pixel { blue: 0x3B, green: 0x34, red: 0x2F, alpha: 0xFF },
+    Pixel { blue: 0x40, green: 0x32, red: 0x36, alpha: 0xFF },
+    Pixel { blue: 0x41, green: 0x36, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x5C, green: 0x30, red: 0x3A, alpha: 0xFF },
+    Pixel { blue: 0x47, green: 0x35, red: 0x31, alpha: 0xFF },
+    Pixel { blue: 0x37, green: 0x36, red: 0x33, alpha: 0xFF },
+    Pixel { blue: 0x51, green: 0x36, red: 0x2C, alpha: 0xFF },
+    Pixel { blue: 0x5D, green: 0x33, red: 0x36, alpha: 0xFF },
+    Pixel { blue: 0x5D, green: 0x35, red: 0x32, alpha: 0xFF },
+    Pixel { blue: 0x44, green: 0x37, red: 0x35, alpha: 0xFF },
+    Pixel { blue: 0x4F, green: 0x38, red: 0x31, alpha: 0xFF },
+    Pixel { blue: 0x5F, green: 0x3B, red: 0x24, alpha: 0xFF },
+    Pixel { blue: 0x40, green: 0x39, red: 0x34, alpha: 0xFF },
+    Pixel { blue: 0x38, green: 0x34, red: 0x47, alpha: 0xFF },
+    Pixel { blue: 0x5F, green: 0x39, red: 0x2D, alpha: 0xFF },
+    Pixel { blue: 0x46, green: 0x3B, red: 0x33, alpha: 0xFF },
+    Pixel { blue: 0x4C, green: 0x3A, red: 0x36, alpha: 0xFF },
+    Pixel { blue: 0x4B, green: 0x3E, red: 0x3B, alpha: 0xFF },
+    Pixel { blue: 0x6B, green: 0x3F, red: 0x31, alpha: 0xFF },
+    Pixel { blue: 0x65, green: 0x41, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x6B, green: 0x3E, red: 0x37, alpha: 0xFF },
+    Pixel { blue: 0x5B, green: 0x40, red: 0x35, alpha: 0xFF },
+    Pixel { blue: 0x6D, green: 0x3B, red: 0x42, alpha: 0xFF },
+    Pixel { blue: 0x65, green: 0x3F, red: 0x38, alpha: 0xFF },
+    Pixel { blue: 0x57, green: 0x40, red: 0x39, alpha: 0xFF },
+    Pixel { blue: 0x52, green: 0x40, red: 0x3C, alpha: 0xFF },
+    Pixel { blue: 0x38, green: 0x3B, red: 0x54, alpha: 0xFF },
+    Pixel { blue: 0x6F, green: 0x3E, red: 0x3E, alpha: 0xFF },
+    Pixel { blue: 0x41, green: 0x3C, red: 0x52, alpha: 0xFF },
+    Pixel { blue: 0x42, green: 0x43, red: 0x40, alpha: 0xFF },
+    Pixel { blue: 0x70, green: 0x46, red: 0x2F, alpha: 0xFF },
+    Pixel { blue: 0x75, green: 0x45, red: 0x37, alpha: 0xFF },
+    Pixel { blue: 0x62, green: 0x46, red: 0x3C, alpha: 0xFF },
+    Pixel { blue: 0x4E, green: 0x40, red: 0x56, alpha: 0xFF },
+    Pixel { blue: 0x5D, green: 0x46, red: 0x3F, alpha: 0xFF },
+    Pixel { blue: 0x79, green: 0x49, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x4E, green: 0x45, red: 0x49, alpha: 0xFF },
+    Pixel { blue: 0x6E, green: 0x45, red: 0x43, alpha: 0xFF },
+    Pixel { blue: 0x7D, green: 0x4D, red: 0x32, alpha: 0xFF },
+    Pixel { blue: 0x70, green: 0x4A, red: 0x42, alpha: 0xFF },
+    Pixel { blue: 0x68, green: 0x4B, red: 0x41, alpha: 0xFF },
+    Pixel { blue: 0x61, green: 0x4B, red: 0x43, alpha: 0xFF },
+    Pixel { blue: 0x5E, green: 0x48, red: 0x4E, alpha: 0xFF },
+    Pixel { blue: 0x53, green: 0x4E, red: 0x41, alpha: 0xFF },
+    Pixel { blue: 0x6F, green: 0x4D, red: 0x3F, alpha: 0xFF },
+    Pixel { blue: 0x81, green: 0x52, red: 0x2D, alpha: 0xFF },
+    Pixel { blue: 0x81, green: 0x4E, red: 0x3A, alpha: 0xFF },
+    Pixel { blue: 0x84, green: 0x4C, red: 0x43, alpha: 0xFF },
+    Pixel { blue: 0x82, green: 0x52, red: 0x37, alpha: 0xFF },
+    Pixel { blue: 0x65, green: 0x53, red: 0x3C, alpha: 0xFF },
+    Pixel { blue: 0x89, green: 0x53, red: 0x33, alpha: 0xFF },
+    Pixel { blue: 0x8A, green: 0x56, red: 0x2B, alpha: 0xFF },
+    Pixel { blue: 0x45, green: 0x4A, red: 0x68, alpha: 0xFF },
+    Pixel { blue: 0x50, green: 0x51, red: 0x4F, alpha: 0xFF },
+    Pixel { blue: 0x6E, green: 0x51, red: 0x47, alpha: 0xFF },
+    Pixel { blue: 0x7E, green: 0x51, red: 0x43, alpha: 0xFF },
+    Pixel { blue: 0x74, green: 0x52, red: 0x45, alpha: 0xFF },
+    Pixel { blue: 0x7A, green: 0x52, red: 0x48, alpha: 0xFF },
+    Pixel { blue: 0x71, green: 0x54, red: 0x4A, alpha: 0xFF },
+    Pixel { blue: 0x43, green: 0x4A, red: 0x77, alpha: 0xFF },
+    Pixel { blue: 0x8F, green: 0x5A, red: 0x31, alpha: 0xFF },
+    Pixel { blue: 0x89, green: 0x5B, red: 0x31, alpha: 0xFF },
+    Pixel { blue: 0x90, green: 0x5A, red: 0x3A, alpha: 0xFF },
+    Pixel { blue: 0x94, green: 0x55, red: 0x4D, alpha: 0xFF },
+    Pixel { blue: 0x93, green: 0x60, red: 0x2B, alpha: 0xFF },
+    Pixel { blue: 0x93, green: 0x59, red: 0x47, alpha: 0xFF },
+    Pixel { blue: 0x94, green: 0x5F, red: 0x36, alpha: 0xFF },
+    Pixel { blue: 0x6C, green: 0x54, red: 0x67, alpha: 0xFF },
+    Pixel { blue: 0x88, green: 0x5E, red: 0x40, alpha: 0xFF },
+    Pixel { blue: 0x9C, green: 0x62, red: 0x31, alpha: 0xFF },
+    Pixel { blue: 0x5D, green: 0x53, red: 0x75, alpha: 0xFF },
+    Pixel { blue: 0x5B, green: 0x5D, red: 0x5C, alpha: 0xFF },
+    Pixel { blue: 0x65, green: 0x5C, red: 0x5D, alpha: 0xFF },
+    Pixel { blue: 0x80, green: 0x5D, red: 0x54, alpha: 0xFF },
+    Pixel { blue: 0x6D, green: 0x5E, red: 0x5E, alpha: 0xFF },
+    Pixel { blue: 0xA1, green: 0x66, red: 0x38, alpha: 0xFF },
+    Pixel { blue: 0x2F, green: 0x54, red: 0x93, alpha: 0xFF },
+    Pixel { blue: 0xA3, green: 0x69, red: 0x30, alpha: 0xFF },
+    Pixel { blue: 0x71, green: 0x66, red: 0x4A, alpha: 0xFF },
+    Pixel { blue: 0x46, green: 0x58, red: 0x85, alpha: 0xFF },
+    Pixel { blue: 0x9D, green: 0x68, red: 0x3A, alpha: 0xFF },
+    Pixel { blue: 0x53, green: 0x5A, red: 0x7C, alpha: 0xFF },
+    Pixel { blue: 0xA6, green: 0x6E, red: 0x27, alpha: 0xFF },
+    Pixel { blue: 0x43, green: 0x55, red: 0x94, alpha: 0xFF },
+    Pixel { blue: 0x55, green: 0x57, red: 0x89, alpha: 0xFF },
+    Pixel { blue: 0x98, green: 0x68, red: 0x43, alpha: 0xFF },
+    Pixel { blue: 0x79, green: 0x63, red: 0x5C, alpha: 0xFF },
+    Pixel { blue: 0xAD, green: 0x6E, red: 0x2D, alpha: 0xFF },
+    Pixel { blue: 0xA4, green: 0x65, red: 0x50, alpha: 0xFF },
+    Pixel { blue: 0x69, green: 0x6D, red: 0x4C, alpha: 0xFF },
+    Pixel { blue: 0xA1, green: 0x69, red: 0x4B, alpha: 0xFF },
+    Pixel { blue: 0x64, green: 0x66, red: 0x66, alpha: 0xFF },
+    Pixel { blue: 0x85, green: 0x66, red: 0x60, alpha: 0xFF },
+    Pixel { blue: 0x48, green: 0x5A, red: 0xA0, alpha: 0xFF },
+    Pixel { blue: 0x2F, green: 0x5A, red: 0xA7, alpha: 0xFF },
+    Pixel { blue: 0xB2, green: 0x73, red: 0x33, alpha: 0xFF },
+    Pixel { blue: 0xB5, green: 0x77, red: 0x2B, alpha: 0xFF },
+    Pixel { blue: 0x5B, green: 0x68, red: 0x7E, alpha: 0xFF },
+    Pixel { blue: 0x55, green: 0x60, red: 0x9A, alpha: 0xFF },
+    Pixel { blue: 0x4B, green: 0x60, red: 0xA0, alpha: 0xFF },
+    Pixel { blue: 0xBE, green: 0x7D, red: 0x2E, alpha: 0xFF },
+    Pixel { blue: 0x2A, green: 0x61, red: 0xB5, alpha: 0xFF },
+    Pixel { blue: 0x59, green: 0x67, red: 0x95, alpha: 0xFF },
+    Pixel { blue: 0xA4, green: 0x75, red: 0x55, alpha: 0xFF },
+    Pixel { blue: 0xAE, green: 0x78, red: 0x4A, alpha: 0xFF },
+    Pixel { blue: 0x52, green: 0x68, red: 0x98, alpha: 0xFF },
+    Pixel { blue: 0x76, green: 0x7A, red: 0x59, alpha: 0xFF },
+    Pixel { blue: 0xB2, green: 0x76, red: 0x56, alpha: 0xFF },
+    Pixel { blue: 0xB7, green: 0x74, red: 0x5C, alpha: 0xFF },
+    Pixel { blue: 0x50, green: 0x66, red: 0xA7, alpha: 0xFF },
+    Pixel { blue: 0x72, green: 0x74, red: 0x71, alpha: 0xFF },
+    Pixel { blue: 0x5F, green: 0x66, red: 0xA4, alpha: 0xFF },
+    Pixel { blue: 0x6B, green: 0x67, red: 0xA1, alpha: 0xFF },
+    Pixel { blue: 0xB8, green: 0x7D, red: 0x47, alpha: 0xFF },
+    Pixel { blue: 0x81, green: 0x7C, red: 0x60, alpha: 0xFF },
+    Pixel { blue: 0x87, green: 0x77, red: 0x74, alpha: 0xFF },
+    Pixel { blue: 0x54, green: 0x6A, red: 0xAC, alpha: 0xFF },
+    Pixel { blue: 0x9F, green: 0x79, red: 0x71, alpha: 0xFF },
+    Pixel { blue: 0x94, green: 0x7B, red: 0x71, alpha: 0xFF },
+    Pixel { blue: 0x57, green: 0x6D, red: 0xAF, alpha: 0xFF },
+    Pixel { blue: 0x59, green: 0x6F, red: 0xB1, alpha: 0xFF },
+    Pixel { blue: 0x69, green: 0x6F, red: 0xB2, alpha: 0xFF },
+    Pixel { blue: 0x2E, green: 0x6B, red: 0xCF, alpha: 0xFF },
+    Pixel { blue: 0x61, green: 0x72, red: 0xAB, alpha: 0xFF },
+    Pixel { blue: 0x5A, green: 0x73, red: 0xAE, alpha: 0xFF },
+    Pixel { blue: 0x55, green: 0x74, red: 0xB1, alpha: 0xFF },
+    Pixel { blue: 0x7E, green: 0x81, red: 0x7F, alpha: 0xFF },
+    Pixel { blue: 0xC1, green: 0x89, red: 0x5E, alpha: 0xFF },
+    Pixel { blue: 0xC4, green: 0x88, red: 0x6B, alpha: 0xFF },
+    Pixel { blue: 0xCA, green: 0x87, red: 0x6E, alpha: 0xFF },
+    Pixel { blue: 0x5B, green: 0x7A, red: 0xB7, alpha: 0xFF },
+    Pixel { blue: 0x84, green: 0x87, red: 0x85, alpha: 0xFF },
+    Pixel { blue: 0x7B, green: 0x7C, red: 0xAE, alpha: 0xFF },
+    Pixel { blue: 0x56, green: 0x7D, red: 0xBC, alpha: 0xFF },
+    Pixel { blue: 0x64, green: 0x7D, red: 0xBA, alpha: 0xFF },
+    Pixel { blue: 0x9B, green: 0x8A, red: 0x88, alpha: 0xFF },
+    Pixel { blue: 0x75, green: 0x7A, red: 0xC8, alpha: 0xFF },
+    Pixel { blue: 0x2E, green: 0x76, red: 0xE9, alpha: 0xFF },
+    Pixel { blue: 0x74, green: 0x7F, red: 0xB9, alpha: 0xFF },
+    Pixel { blue: 0x7F, green: 0x7B, red: 0xC4, alpha: 0xFF },
+    Pixel { blue: 0x70, green: 0x7D, red: 0xC5, alpha: 0xFF },
+    Pixel { blue: 0x5A, green: 0x83, red: 0xBB, alpha: 0xFF },
+    Pixel { blue: 0x51, green: 0x82, red: 0xC3, alpha: 0xFF },
+    Pixel { blue: 0x8F, green: 0x96, red: 0x75, alpha: 0xFF },
+    Pixel { blue: 0x90, green: 0x87, red: 0xA9, alpha: 0xFF },
+    Pixel { blue: 0x9E, green: 0x95, red: 0x79, alpha: 0xFF },
+    Pixel { blue: 0x73, green: 0x7F, red: 0xCF, alpha: 0xFF },
+    Pixel { blue: 0x5F, green: 0x87, red: 0xC0, alpha: 0xFF },
+    Pixel { blue: 0x55, green: 0x88, red: 0xC3, alpha: 0xFF },
+    Pixel { blue: 0x71, green: 0x83, red: 0xD5, alpha: 0xFF },
+    Pixel { blue: 0x5D, green: 0x8A, red: 0xC4, alpha: 0xFF },
+    Pixel { blue: 0xD6, green: 0x97, red: 0x7A, alpha: 0xFF },
+    Pixel { blue: 0x7E, green: 0x84, red: 0xD6, alpha: 0xFF },
+    Pixel { blue: 0xAA, green: 0x91, red: 0xA4, alpha: 0xFF },
+    Pixel { blue: 0xCF, green: 0x9C, red: 0x78, alpha: 0xFF },
+    Pixel { blue: 0x71, green: 0x8B, red: 0xC9, alpha: 0xFF },
+    Pixel { blue: 0xBF, green: 0x96, red: 0x92, alpha: 0xFF },
+    Pixel { blue: 0xB3, green: 0x98, red: 0x91, alpha: 0xFF },
+    Pixel { blue: 0xAD, green: 0x98, red: 0x94, alpha: 0xFF },
+    Pixel { blue: 0x5A, green: 0x90, red: 0xC5, alpha: 0xFF },
+    Pixel { blue: 0x60, green: 0x90, red: 0xC4, alpha: 0xFF },
+    Pixel { blue: 0x33, green: 0x82, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0xB9, green: 0x99, red: 0x91, alpha: 0xFF },
+    Pixel { blue: 0x2C, green: 0x84, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0x43, green: 0x83, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0xCD, green: 0x98, red: 0x98, alpha: 0xFF },
+    Pixel { blue: 0xBD, green: 0x9B, red: 0x9A, alpha: 0xFF },
+    Pixel { blue: 0x91, green: 0x8A, red: 0xDF, alpha: 0xFF },
+    Pixel { blue: 0xBF, green: 0x9E, red: 0x93, alpha: 0xFF },
+    Pixel { blue: 0xAE, green: 0x9D, red: 0xA2, alpha: 0xFF },
+    Pixel { blue: 0x8A, green: 0x8D, red: 0xE4, alpha: 0xFF },
+    Pixel { blue: 0xC6, green: 0x9F, red: 0x9A, alpha: 0xFF },
+    Pixel { blue: 0x59, green: 0x89, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0xD7, green: 0xA2, red: 0x91, alpha: 0xFF },
+    Pixel { blue: 0xE3, green: 0xA4, red: 0x8A, alpha: 0xFF },
+    Pixel { blue: 0xD1, green: 0xA3, red: 0xA0, alpha: 0xFF },
+    Pixel { blue: 0x81, green: 0x94, red: 0xE7, alpha: 0xFF },
+    Pixel { blue: 0x84, green: 0x9A, red: 0xD6, alpha: 0xFF },
+    Pixel { blue: 0xA7, green: 0xAF, red: 0x8A, alpha: 0xFF },
+    Pixel { blue: 0x88, green: 0x95, red: 0xE8, alpha: 0xFF },
+    Pixel { blue: 0x72, green: 0x90, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0x89, green: 0x98, red: 0xE0, alpha: 0xFF },
+    Pixel { blue: 0x95, green: 0x95, red: 0xF0, alpha: 0xFF },
+    Pixel { blue: 0xB7, green: 0xB0, red: 0x90, alpha: 0xFF },
+    Pixel { blue: 0xD8, green: 0xA6, red: 0xAE, alpha: 0xFF },
+    Pixel { blue: 0xDE, green: 0xA7, red: 0xAA, alpha: 0xFF },
+    Pixel { blue: 0xE2, green: 0xA8, red: 0xB4, alpha: 0xFF },
+    Pixel { blue: 0x9E, green: 0x9C, red: 0xF9, alpha: 0xFF },
+    Pixel { blue: 0x8D, green: 0xA1, red: 0xF6, alpha: 0xFF },
+    Pixel { blue: 0x93, green: 0xA1, red: 0xF5, alpha: 0xFF },
+    Pixel { blue: 0x92, green: 0xA9, red: 0xE2, alpha: 0xFF },
+    Pixel { blue: 0xB3, green: 0xBF, red: 0x9B, alpha: 0xFF },
+    Pixel { blue: 0xAD, green: 0xA6, red: 0xFC, alpha: 0xFF },
+    Pixel { blue: 0x9C, green: 0xAA, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0xCA, green: 0xC5, red: 0xA6, alpha: 0xFF },
+    Pixel { blue: 0xA0, green: 0xB1, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0xED, green: 0xCA, red: 0x9A, alpha: 0xFF },
+    Pixel { blue: 0xA3, green: 0xB7, red: 0xED, alpha: 0xFF },
+    Pixel { blue: 0xC7, green: 0xCB, red: 0xA9, alpha: 0xFF },
+    Pixel { blue: 0xA5, green: 0xB7, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0xC5, green: 0xD0, red: 0xAD, alpha: 0xFF },
+    Pixel { blue: 0xAE, green: 0xC2, red: 0xF4, alpha: 0xFF },
+    Pixel { blue: 0xB4, green: 0xC0, red: 0xFF, alpha: 0xFF },
+    Pixel { blue: 0xB8, green: 0xC6, red: 0xF7, alpha: 0xFF },
+    Pixel { blue: 0xBA, green: 0xCB, red: 0xF4, alpha: 0xFF },
+];
+
 #[test]
-pub fn roundtrip_palettized() {
-    // This palette was generated by GIMP for the 128x128 ringhopper.bmp image.
+pub fn roundtrip_4bit() {
+    let ringhopper = open_ringhopper_image();
+    let palette = &RINGHOPPER_PALETTE[..16];
+
+    for format in [Format::A4, Format::Y4, Format::A4Y4, Format::P4(palette)] {
+        let mut output = [0u8; 128 * 128];
+        let output = &mut output[..format.required_bytes(128, 128)];
+        format.encode_pixels(&ringhopper, output, 128, 128);
+        let mut decoded = [Pixel::default(); 128 * 128];
+        format.decode_pixels(output, &mut decoded, 128, 128);
+        let mut output_again = [0u8; 128 * 128];
+        let output_again = &mut output_again[..format.required_bytes(128, 128)];
+        format.encode_pixels(&decoded, output_again, 128, 128);
+        assert_eq!(output, output_again);
+    }
+}
+
+#[test]
+pub fn odd_width_4bit() {
+    // Each row starts on a new byte, and the left pixel is in the low 4 bits.
+    assert_eq!(6, Format::A4.required_bytes(3, 3));
+    assert_eq!(3, Format::A4Y4.required_bytes(3, 1));
+
     let palette = [
-        Pixel { blue: 0x4C, green: 0x0A, red: 0x1E, alpha: 0xFF },
-        Pixel { blue: 0x20, green: 0x15, red: 0x13, alpha: 0xFF },
-        Pixel { blue: 0x18, green: 0x16, red: 0x14, alpha: 0xFF },
-        Pixel { blue: 0x1D, green: 0x16, red: 0x16, alpha: 0xFF },
-        Pixel { blue: 0x23, green: 0x17, red: 0x15, alpha: 0xFF },
-        Pixel { blue: 0x23, green: 0x16, red: 0x1A, alpha: 0xFF },
-        Pixel { blue: 0x26, green: 0x1A, red: 0x17, alpha: 0xFF },
-        Pixel { blue: 0x1C, green: 0x1B, red: 0x17, alpha: 0xFF },
-        Pixel { blue: 0x2A, green: 0x19, red: 0x1A, alpha: 0xFF },
-        Pixel { blue: 0x2B, green: 0x18, red: 0x1F, alpha: 0xFF },
-        Pixel { blue: 0x23, green: 0x1A, red: 0x20, alpha: 0xFF },
-        Pixel { blue: 0x20, green: 0x1E, red: 0x1B, alpha: 0xFF },
-        Pixel { blue: 0x2E, green: 0x1D, red: 0x1E, alpha: 0xFF },
-        Pixel { blue: 0x2F, green: 0x1C, red: 0x23, alpha: 0xFF },
-        Pixel { blue: 0x4F, green: 0x1A, red: 0x22, alpha: 0xFF },
-        Pixel { blue: 0x2B, green: 0x1E, red: 0x22, alpha: 0xFF },
-        Pixel { blue: 0x1E, green: 0x21, red: 0x1F, alpha: 0xFF },
-        Pixel { blue: 0x23, green: 0x21, red: 0x1E, alpha: 0xFF },
-        Pixel { blue: 0x2D, green: 0x21, red: 0x1D, alpha: 0xFF },
-        Pixel { blue: 0x2E, green: 0x1F, red: 0x28, alpha: 0xFF },
-        Pixel { blue: 0x37, green: 0x21, red: 0x20, alpha: 0xFF },
-        Pixel { blue: 0x2A, green: 0x22, red: 0x21, alpha: 0xFF },
-        Pixel { blue: 0x38, green: 0x20, red: 0x25, alpha: 0xFF },
-        Pixel { blue: 0x27, green: 0x25, red: 0x22, alpha: 0xFF },
-        Pixel { blue: 0x23, green: 0x25, red: 0x24, alpha: 0xFF },
-        Pixel { blue: 0x28, green: 0x23, red: 0x2C, alpha: 0xFF },
-        Pixel { blue: 0x41, green: 0x25, red: 0x27, alpha: 0xFF },
-        Pixel { blue: 0x42, green: 0x27, red: 0x22, alpha: 0xFF },
-        Pixel { blue: 0x42, green: 0x24, red: 0x2D, alpha: 0xFF },
-        Pixel { blue: 0x2B, green: 0x29, red: 0x26, alpha: 0xFF },
-        Pixel { blue: 0x3F, green: 0x28, red: 0x27, alpha: 0xFF },
-        Pixel { blue: 0x28, green: 0x2A, red: 0x29, alpha: 0xFF },
-        Pixel { blue: 0x3F, green: 0x28, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x46, green: 0x29, red: 0x2B, alpha: 0xFF },
-        Pixel { blue: 0x4B, green: 0x28, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x33, green: 0x2C, red: 0x28, alpha: 0xFF },
-        Pixel { blue: 0x48, green: 0x2B, red: 0x26, alpha: 0xFF },
-        Pixel { blue: 0x39, green: 0x29, red: 0x33, alpha: 0xFF },
-        Pixel { blue: 0x2F, green: 0x2D, red: 0x29, alpha: 0xFF },
-        Pixel { blue: 0x39, green: 0x2D, red: 0x28, alpha: 0xFF },
-        Pixel { blue: 0x39, green: 0x2B, red: 0x2F, alpha: 0xFF },
-        Pixel { blue: 0x4D, green: 0x2B, red: 0x2B, alpha: 0xFF },
-        Pixel { blue: 0x40, green: 0x2E, red: 0x26, alpha: 0xFF },
-        Pixel { blue: 0x2D, green: 0x2F, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x32, green: 0x30, red: 0x2D, alpha: 0xFF },
-        Pixel { blue: 0x51, green: 0x2E, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x34, green: 0x2D, red: 0x3A, alpha: 0xFF },
-        Pixel { blue: 0x51, green: 0x2D, red: 0x33, alpha: 0xFF },
-        Pixel { blue: 0x2B, green: 0x2E, red: 0x3D, alpha: 0xFF },
-        Pixel { blue: 0x52, green: 0x32, red: 0x2A, alpha: 0xFF },
-        Pixel { blue: 0x4E, green: 0x30, red: 0x35, alpha: 0xFF },
-        Pixel { blue: 0x3B, green: 0x34, red: 0x2F, alpha: 0xFF },
-        Pixel { blue: 0x40, green: 0x32, red: 0x36, alpha: 0xFF },
-        Pixel { blue: 0x41, green: 0x36, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x5C, green: 0x30, red: 0x3A, alpha: 0xFF },
-        Pixel { blue: 0x47, green: 0x35, red: 0x31, alpha: 0xFF },
-        Pixel { blue: 0x37, green: 0x36, red: 0x33, alpha: 0xFF },
-        Pixel { blue: 0x51, green: 0x36, red: 0x2C, alpha: 0xFF },
-        Pixel { blue: 0x5D, green: 0x33, red: 0x36, alpha: 0xFF },
-        Pixel { blue: 0x5D, green: 0x35, red: 0x32, alpha: 0xFF },
-        Pixel { blue: 0x44, green: 0x37, red: 0x35, alpha: 0xFF },
-        Pixel { blue: 0x4F, green: 0x38, red: 0x31, alpha: 0xFF },
-        Pixel { blue: 0x5F, green: 0x3B, red: 0x24, alpha: 0xFF },
-        Pixel { blue: 0x40, green: 0x39, red: 0x34, alpha: 0xFF },
-        Pixel { blue: 0x38, green: 0x34, red: 0x47, alpha: 0xFF },
-        Pixel { blue: 0x5F, green: 0x39, red: 0x2D, alpha: 0xFF },
-        Pixel { blue: 0x46, green: 0x3B, red: 0x33, alpha: 0xFF },
-        Pixel { blue: 0x4C, green: 0x3A, red: 0x36, alpha: 0xFF },
-        Pixel { blue: 0x4B, green: 0x3E, red: 0x3B, alpha: 0xFF },
-        Pixel { blue: 0x6B, green: 0x3F, red: 0x31, alpha: 0xFF },
-        Pixel { blue: 0x65, green: 0x41, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x6B, green: 0x3E, red: 0x37, alpha: 0xFF },
-        Pixel { blue: 0x5B, green: 0x40, red: 0x35, alpha: 0xFF },
-        Pixel { blue: 0x6D, green: 0x3B, red: 0x42, alpha: 0xFF },
-        Pixel { blue: 0x65, green: 0x3F, red: 0x38, alpha: 0xFF },
-        Pixel { blue: 0x57, green: 0x40, red: 0x39, alpha: 0xFF },
-        Pixel { blue: 0x52, green: 0x40, red: 0x3C, alpha: 0xFF },
-        Pixel { blue: 0x38, green: 0x3B, red: 0x54, alpha: 0xFF },
-        Pixel { blue: 0x6F, green: 0x3E, red: 0x3E, alpha: 0xFF },
-        Pixel { blue: 0x41, green: 0x3C, red: 0x52, alpha: 0xFF },
-        Pixel { blue: 0x42, green: 0x43, red: 0x40, alpha: 0xFF },
-        Pixel { blue: 0x70, green: 0x46, red: 0x2F, alpha: 0xFF },
-        Pixel { blue: 0x75, green: 0x45, red: 0x37, alpha: 0xFF },
-        Pixel { blue: 0x62, green: 0x46, red: 0x3C, alpha: 0xFF },
-        Pixel { blue: 0x4E, green: 0x40, red: 0x56, alpha: 0xFF },
-        Pixel { blue: 0x5D, green: 0x46, red: 0x3F, alpha: 0xFF },
-        Pixel { blue: 0x79, green: 0x49, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x4E, green: 0x45, red: 0x49, alpha: 0xFF },
-        Pixel { blue: 0x6E, green: 0x45, red: 0x43, alpha: 0xFF },
-        Pixel { blue: 0x7D, green: 0x4D, red: 0x32, alpha: 0xFF },
-        Pixel { blue: 0x70, green: 0x4A, red: 0x42, alpha: 0xFF },
-        Pixel { blue: 0x68, green: 0x4B, red: 0x41, alpha: 0xFF },
-        Pixel { blue: 0x61, green: 0x4B, red: 0x43, alpha: 0xFF },
-        Pixel { blue: 0x5E, green: 0x48, red: 0x4E, alpha: 0xFF },
-        Pixel { blue: 0x53, green: 0x4E, red: 0x41, alpha: 0xFF },
-        Pixel { blue: 0x6F, green: 0x4D, red: 0x3F, alpha: 0xFF },
-        Pixel { blue: 0x81, green: 0x52, red: 0x2D, alpha: 0xFF },
-        Pixel { blue: 0x81, green: 0x4E, red: 0x3A, alpha: 0xFF },
-        Pixel { blue: 0x84, green: 0x4C, red: 0x43, alpha: 0xFF },
-        Pixel { blue: 0x82, green: 0x52, red: 0x37, alpha: 0xFF },
-        Pixel { blue: 0x65, green: 0x53, red: 0x3C, alpha: 0xFF },
-        Pixel { blue: 0x89, green: 0x53, red: 0x33, alpha: 0xFF },
-        Pixel { blue: 0x8A, green: 0x56, red: 0x2B, alpha: 0xFF },
-        Pixel { blue: 0x45, green: 0x4A, red: 0x68, alpha: 0xFF },
-        Pixel { blue: 0x50, green: 0x51, red: 0x4F, alpha: 0xFF },
-        Pixel { blue: 0x6E, green: 0x51, red: 0x47, alpha: 0xFF },
-        Pixel { blue: 0x7E, green: 0x51, red: 0x43, alpha: 0xFF },
-        Pixel { blue: 0x74, green: 0x52, red: 0x45, alpha: 0xFF },
-        Pixel { blue: 0x7A, green: 0x52, red: 0x48, alpha: 0xFF },
-        Pixel { blue: 0x71, green: 0x54, red: 0x4A, alpha: 0xFF },
-        Pixel { blue: 0x43, green: 0x4A, red: 0x77, alpha: 0xFF },
-        Pixel { blue: 0x8F, green: 0x5A, red: 0x31, alpha: 0xFF },
-        Pixel { blue: 0x89, green: 0x5B, red: 0x31, alpha: 0xFF },
-        Pixel { blue: 0x90, green: 0x5A, red: 0x3A, alpha: 0xFF },
-        Pixel { blue: 0x94, green: 0x55, red: 0x4D, alpha: 0xFF },
-        Pixel { blue: 0x93, green: 0x60, red: 0x2B, alpha: 0xFF },
-        Pixel { blue: 0x93, green: 0x59, red: 0x47, alpha: 0xFF },
-        Pixel { blue: 0x94, green: 0x5F, red: 0x36, alpha: 0xFF },
-        Pixel { blue: 0x6C, green: 0x54, red: 0x67, alpha: 0xFF },
-        Pixel { blue: 0x88, green: 0x5E, red: 0x40, alpha: 0xFF },
-        Pixel { blue: 0x9C, green: 0x62, red: 0x31, alpha: 0xFF },
-        Pixel { blue: 0x5D, green: 0x53, red: 0x75, alpha: 0xFF },
-        Pixel { blue: 0x5B, green: 0x5D, red: 0x5C, alpha: 0xFF },
-        Pixel { blue: 0x65, green: 0x5C, red: 0x5D, alpha: 0xFF },
-        Pixel { blue: 0x80, green: 0x5D, red: 0x54, alpha: 0xFF },
-        Pixel { blue: 0x6D, green: 0x5E, red: 0x5E, alpha: 0xFF },
-        Pixel { blue: 0xA1, green: 0x66, red: 0x38, alpha: 0xFF },
-        Pixel { blue: 0x2F, green: 0x54, red: 0x93, alpha: 0xFF },
-        Pixel { blue: 0xA3, green: 0x69, red: 0x30, alpha: 0xFF },
-        Pixel { blue: 0x71, green: 0x66, red: 0x4A, alpha: 0xFF },
-        Pixel { blue: 0x46, green: 0x58, red: 0x85, alpha: 0xFF },
-        Pixel { blue: 0x9D, green: 0x68, red: 0x3A, alpha: 0xFF },
-        Pixel { blue: 0x53, green: 0x5A, red: 0x7C, alpha: 0xFF },
-        Pixel { blue: 0xA6, green: 0x6E, red: 0x27, alpha: 0xFF },
-        Pixel { blue: 0x43, green: 0x55, red: 0x94, alpha: 0xFF },
-        Pixel { blue: 0x55, green: 0x57, red: 0x89, alpha: 0xFF },
-        Pixel { blue: 0x98, green: 0x68, red: 0x43, alpha: 0xFF },
-        Pixel { blue: 0x79, green: 0x63, red: 0x5C, alpha: 0xFF },
-        Pixel { blue: 0xAD, green: 0x6E, red: 0x2D, alpha: 0xFF },
-        Pixel { blue: 0xA4, green: 0x65, red: 0x50, alpha: 0xFF },
-        Pixel { blue: 0x69, green: 0x6D, red: 0x4C, alpha: 0xFF },
-        Pixel { blue: 0xA1, green: 0x69, red: 0x4B, alpha: 0xFF },
-        Pixel { blue: 0x64, green: 0x66, red: 0x66, alpha: 0xFF },
-        Pixel { blue: 0x85, green: 0x66, red: 0x60, alpha: 0xFF },
-        Pixel { blue: 0x48, green: 0x5A, red: 0xA0, alpha: 0xFF },
-        Pixel { blue: 0x2F, green: 0x5A, red: 0xA7, alpha: 0xFF },
-        Pixel { blue: 0xB2, green: 0x73, red: 0x33, alpha: 0xFF },
-        Pixel { blue: 0xB5, green: 0x77, red: 0x2B, alpha: 0xFF },
-        Pixel { blue: 0x5B, green: 0x68, red: 0x7E, alpha: 0xFF },
-        Pixel { blue: 0x55, green: 0x60, red: 0x9A, alpha: 0xFF },
-        Pixel { blue: 0x4B, green: 0x60, red: 0xA0, alpha: 0xFF },
-        Pixel { blue: 0xBE, green: 0x7D, red: 0x2E, alpha: 0xFF },
-        Pixel { blue: 0x2A, green: 0x61, red: 0xB5, alpha: 0xFF },
-        Pixel { blue: 0x59, green: 0x67, red: 0x95, alpha: 0xFF },
-        Pixel { blue: 0xA4, green: 0x75, red: 0x55, alpha: 0xFF },
-        Pixel { blue: 0xAE, green: 0x78, red: 0x4A, alpha: 0xFF },
-        Pixel { blue: 0x52, green: 0x68, red: 0x98, alpha: 0xFF },
-        Pixel { blue: 0x76, green: 0x7A, red: 0x59, alpha: 0xFF },
-        Pixel { blue: 0xB2, green: 0x76, red: 0x56, alpha: 0xFF },
-        Pixel { blue: 0xB7, green: 0x74, red: 0x5C, alpha: 0xFF },
-        Pixel { blue: 0x50, green: 0x66, red: 0xA7, alpha: 0xFF },
-        Pixel { blue: 0x72, green: 0x74, red: 0x71, alpha: 0xFF },
-        Pixel { blue: 0x5F, green: 0x66, red: 0xA4, alpha: 0xFF },
-        Pixel { blue: 0x6B, green: 0x67, red: 0xA1, alpha: 0xFF },
-        Pixel { blue: 0xB8, green: 0x7D, red: 0x47, alpha: 0xFF },
-        Pixel { blue: 0x81, green: 0x7C, red: 0x60, alpha: 0xFF },
-        Pixel { blue: 0x87, green: 0x77, red: 0x74, alpha: 0xFF },
-        Pixel { blue: 0x54, green: 0x6A, red: 0xAC, alpha: 0xFF },
-        Pixel { blue: 0x9F, green: 0x79, red: 0x71, alpha: 0xFF },
-        Pixel { blue: 0x94, green: 0x7B, red: 0x71, alpha: 0xFF },
-        Pixel { blue: 0x57, green: 0x6D, red: 0xAF, alpha: 0xFF },
-        Pixel { blue: 0x59, green: 0x6F, red: 0xB1, alpha: 0xFF },
-        Pixel { blue: 0x69, green: 0x6F, red: 0xB2, alpha: 0xFF },
-        Pixel { blue: 0x2E, green: 0x6B, red: 0xCF, alpha: 0xFF },
-        Pixel { blue: 0x61, green: 0x72, red: 0xAB, alpha: 0xFF },
-        Pixel { blue: 0x5A, green: 0x73, red: 0xAE, alpha: 0xFF },
-        Pixel { blue: 0x55, green: 0x74, red: 0xB1, alpha: 0xFF },
-        Pixel { blue: 0x7E, green: 0x81, red: 0x7F, alpha: 0xFF },
-        Pixel { blue: 0xC1, green: 0x89, red: 0x5E, alpha: 0xFF },
-        Pixel { blue: 0xC4, green: 0x88, red: 0x6B, alpha: 0xFF },
-        Pixel { blue: 0xCA, green: 0x87, red: 0x6E, alpha: 0xFF },
-        Pixel { blue: 0x5B, green: 0x7A, red: 0xB7, alpha: 0xFF },
-        Pixel { blue: 0x84, green: 0x87, red: 0x85, alpha: 0xFF },
-        Pixel { blue: 0x7B, green: 0x7C, red: 0xAE, alpha: 0xFF },
-        Pixel { blue: 0x56, green: 0x7D, red: 0xBC, alpha: 0xFF },
-        Pixel { blue: 0x64, green: 0x7D, red: 0xBA, alpha: 0xFF },
-        Pixel { blue: 0x9B, green: 0x8A, red: 0x88, alpha: 0xFF },
-        Pixel { blue: 0x75, green: 0x7A, red: 0xC8, alpha: 0xFF },
-        Pixel { blue: 0x2E, green: 0x76, red: 0xE9, alpha: 0xFF },
-        Pixel { blue: 0x74, green: 0x7F, red: 0xB9, alpha: 0xFF },
-        Pixel { blue: 0x7F, green: 0x7B, red: 0xC4, alpha: 0xFF },
-        Pixel { blue: 0x70, green: 0x7D, red: 0xC5, alpha: 0xFF },
-        Pixel { blue: 0x5A, green: 0x83, red: 0xBB, alpha: 0xFF },
-        Pixel { blue: 0x51, green: 0x82, red: 0xC3, alpha: 0xFF },
-        Pixel { blue: 0x8F, green: 0x96, red: 0x75, alpha: 0xFF },
-        Pixel { blue: 0x90, green: 0x87, red: 0xA9, alpha: 0xFF },
-        Pixel { blue: 0x9E, green: 0x95, red: 0x79, alpha: 0xFF },
-        Pixel { blue: 0x73, green: 0x7F, red: 0xCF, alpha: 0xFF },
-        Pixel { blue: 0x5F, green: 0x87, red: 0xC0, alpha: 0xFF },
-        Pixel { blue: 0x55, green: 0x88, red: 0xC3, alpha: 0xFF },
-        Pixel { blue: 0x71, green: 0x83, red: 0xD5, alpha: 0xFF },
-        Pixel { blue: 0x5D, green: 0x8A, red: 0xC4, alpha: 0xFF },
-        Pixel { blue: 0xD6, green: 0x97, red: 0x7A, alpha: 0xFF },
-        Pixel { blue: 0x7E, green: 0x84, red: 0xD6, alpha: 0xFF },
-        Pixel { blue: 0xAA, green: 0x91, red: 0xA4, alpha: 0xFF },
-        Pixel { blue: 0xCF, green: 0x9C, red: 0x78, alpha: 0xFF },
-        Pixel { blue: 0x71, green: 0x8B, red: 0xC9, alpha: 0xFF },
-        Pixel { blue: 0xBF, green: 0x96, red: 0x92, alpha: 0xFF },
-        Pixel { blue: 0xB3, green: 0x98, red: 0x91, alpha: 0xFF },
-        Pixel { blue: 0xAD, green: 0x98, red: 0x94, alpha: 0xFF },
-        Pixel { blue: 0x5A, green: 0x90, red: 0xC5, alpha: 0xFF },
-        Pixel { blue: 0x60, green: 0x90, red: 0xC4, alpha: 0xFF },
-        Pixel { blue: 0x33, green: 0x82, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0xB9, green: 0x99, red: 0x91, alpha: 0xFF },
-        Pixel { blue: 0x2C, green: 0x84, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0x43, green: 0x83, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0xCD, green: 0x98, red: 0x98, alpha: 0xFF },
-        Pixel { blue: 0xBD, green: 0x9B, red: 0x9A, alpha: 0xFF },
-        Pixel { blue: 0x91, green: 0x8A, red: 0xDF, alpha: 0xFF },
-        Pixel { blue: 0xBF, green: 0x9E, red: 0x93, alpha: 0xFF },
-        Pixel { blue: 0xAE, green: 0x9D, red: 0xA2, alpha: 0xFF },
-        Pixel { blue: 0x8A, green: 0x8D, red: 0xE4, alpha: 0xFF },
-        Pixel { blue: 0xC6, green: 0x9F, red: 0x9A, alpha: 0xFF },
-        Pixel { blue: 0x59, green: 0x89, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0xD7, green: 0xA2, red: 0x91, alpha: 0xFF },
-        Pixel { blue: 0xE3, green: 0xA4, red: 0x8A, alpha: 0xFF },
-        Pixel { blue: 0xD1, green: 0xA3, red: 0xA0, alpha: 0xFF },
-        Pixel { blue: 0x81, green: 0x94, red: 0xE7, alpha: 0xFF },
-        Pixel { blue: 0x84, green: 0x9A, red: 0xD6, alpha: 0xFF },
-        Pixel { blue: 0xA7, green: 0xAF, red: 0x8A, alpha: 0xFF },
-        Pixel { blue: 0x88, green: 0x95, red: 0xE8, alpha: 0xFF },
-        Pixel { blue: 0x72, green: 0x90, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0x89, green: 0x98, red: 0xE0, alpha: 0xFF },
-        Pixel { blue: 0x95, green: 0x95, red: 0xF0, alpha: 0xFF },
-        Pixel { blue: 0xB7, green: 0xB0, red: 0x90, alpha: 0xFF },
-        Pixel { blue: 0xD8, green: 0xA6, red: 0xAE, alpha: 0xFF },
-        Pixel { blue: 0xDE, green: 0xA7, red: 0xAA, alpha: 0xFF },
-        Pixel { blue: 0xE2, green: 0xA8, red: 0xB4, alpha: 0xFF },
-        Pixel { blue: 0x9E, green: 0x9C, red: 0xF9, alpha: 0xFF },
-        Pixel { blue: 0x8D, green: 0xA1, red: 0xF6, alpha: 0xFF },
-        Pixel { blue: 0x93, green: 0xA1, red: 0xF5, alpha: 0xFF },
-        Pixel { blue: 0x92, green: 0xA9, red: 0xE2, alpha: 0xFF },
-        Pixel { blue: 0xB3, green: 0xBF, red: 0x9B, alpha: 0xFF },
-        Pixel { blue: 0xAD, green: 0xA6, red: 0xFC, alpha: 0xFF },
-        Pixel { blue: 0x9C, green: 0xAA, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0xCA, green: 0xC5, red: 0xA6, alpha: 0xFF },
-        Pixel { blue: 0xA0, green: 0xB1, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0xED, green: 0xCA, red: 0x9A, alpha: 0xFF },
-        Pixel { blue: 0xA3, green: 0xB7, red: 0xED, alpha: 0xFF },
-        Pixel { blue: 0xC7, green: 0xCB, red: 0xA9, alpha: 0xFF },
-        Pixel { blue: 0xA5, green: 0xB7, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0xC5, green: 0xD0, red: 0xAD, alpha: 0xFF },
-        Pixel { blue: 0xAE, green: 0xC2, red: 0xF4, alpha: 0xFF },
-        Pixel { blue: 0xB4, green: 0xC0, red: 0xFF, alpha: 0xFF },
-        Pixel { blue: 0xB8, green: 0xC6, red: 0xF7, alpha: 0xFF },
-        Pixel { blue: 0xBA, green: 0xCB, red: 0xF4, alpha: 0xFF },
+        Pixel { alpha: 255, red: 0, green: 0, blue: 0 },
+        Pixel { alpha: 255, red: 255, green: 0, blue: 0 },
+        Pixel { alpha: 255, red: 0, green: 255, blue: 0 }
     ];
+    let pixels = [palette[1], palette[2], palette[0], palette[2], palette[2], palette[1]];
+    let mut output = [0u8; 4];
+    Format::P4(&palette).encode_pixels(&pixels, &mut output, 3, 2);
+    assert_eq!([0x21, 0x00, 0x22, 0x01], output);
+
+    let mut decoded = [Pixel::default(); 6];
+    Format::P4(&palette).decode_pixels(&output, &mut decoded, 3, 2);
+    assert_eq!(pixels, decoded);
+
+    let grays = [0x00, 0x11, 0x22, 0xFF, 0xEE].map(|y| Pixel { alpha: 255, red: y, green: y, blue: y });
+    let mut output = [0u8; 3];
+    Format::Y4.encode_pixels(&grays, &mut output, 5, 1);
+    assert_eq!([0x10, 0xF2, 0x0E], output);
+
+    let mut decoded = [Pixel::default(); 5];
+    Format::Y4.decode_pixels(&output, &mut decoded, 5, 1);
+    assert_eq!(grays, decoded);
+
+    assert_eq!(
+        Err(Error::InvalidPaletteLength { length: 17 }),
+        Format::P4(&RINGHOPPER_PALETTE[..17]).try_decode_pixels(&output, &mut decoded, 5, 1)
+    );
+}
+
+#[test]
+pub fn roundtrip_palettized() {
+    let palette = RINGHOPPER_PALETTE;
 
     let original = open_ringhopper_image();
     let mut output = [0u8; 128*128];
-    Format::P8(palette).encode_pixels(&original, &mut output, 128, 128);
+    Format::P8(&palette).encode_pixels(&original, &mut output, 128, 128);
 
     let mut new_pixels_lossy = [Pixel::default(); 128*128];
-    Format::P8(palette).decode_pixels(&output, &mut new_pixels_lossy, 128, 128);
+    Format::P8(&palette).decode_pixels(&output, &mut new_pixels_lossy, 128, 128);
 
     let mut output_again = [0u8; 128*128];
-    Format::P8(palette).encode_pixels(&new_pixels_lossy, &mut output_again, 128, 128);
+    Format::P8(&palette).encode_pixels(&new_pixels_lossy, &mut output_again, 128, 128);
     assert_eq!(output, output_again);
 }
+
+/// Find the closest palette color by checking every color, for checking the accelerated lookup.
+fn nearest_color_linear(palette: &[Pixel], options: &EncodeOptions, pixel: Pixel) -> usize {
+    let transparent_index = options.transparent_index.map(usize::from);
+    let transparent = pixel.alpha <= options.alpha_threshold;
+    if let (true, Some(index)) = (transparent, transparent_index) {
+        return index
+    }
+
+    let candidates = || palette.iter().enumerate().filter(|(i, _)| Some(*i) != transparent_index);
+    let has_translucent = candidates().any(|(_, p)| p.alpha != 0 && p.alpha != 255);
+    let one_bit_alpha = !has_translucent
+        && candidates().any(|(_, p)| p.alpha == 0)
+        && candidates().any(|(_, p)| p.alpha == 255);
+
+    candidates()
+        .filter(|(_, p)| !one_bit_alpha || (p.alpha == 0) == transparent)
+        .map(|(i, p)| {
+            let distance = if has_translucent {
+                options.metric.distance_argb(*p, pixel)
+            } else {
+                options.metric.distance_rgb(*p, pixel)
+            };
+            (distance, i)
+        })
+        .min()
+        .unwrap()
+        .1
+}
+
+#[test]
+pub fn palette_lookup_matches_linear_search() {
+    let ringhopper = open_ringhopper_image();
+
+    let mut translucent = RINGHOPPER_PALETTE;
+    let mut one_bit = RINGHOPPER_PALETTE;
+    let mut duplicates = RINGHOPPER_PALETTE;
+    for i in 0..256 {
+        translucent[i].alpha = i as u8;
+        one_bit[i].alpha = if i % 4 == 0 { 0 } else { 255 };
+        duplicates[i] = RINGHOPPER_PALETTE[i / 2 * 2 % 200];
+    }
+
+    for palette in [RINGHOPPER_PALETTE, translucent, one_bit, duplicates] {
+        for metric in [ErrorMetric::Uniform, ErrorMetric::Rec601, ErrorMetric::Rec709, ErrorMetric::Redmean, ErrorMetric::Oklab] {
+            for transparent_index in [None, Some(255)] {
+                let options = EncodeOptions { metric, transparent_index, ..Default::default() };
+                let mut lookup = super::lookup::PaletteLookup::new(&palette, &options);
+
+                // Go through (part of) the image twice so cached results are checked too.
+                for &pixel in ringhopper.iter().step_by(7).chain(ringhopper.iter().step_by(7)) {
+                    assert_eq!(
+                        nearest_color_linear(&palette, &options, pixel),
+                        lookup.find(pixel),
+                        "{pixel:?} {metric:?} {transparent_index:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+pub fn palettized_short_palette() {
+    let palette = [
+        Pixel { alpha: 255, red: 255, green: 0, blue: 0 },
+        Pixel { alpha: 255, red: 0, green: 255, blue: 0 },
+        Pixel { alpha: 255, red: 0, green: 0, blue: 255 }
+    ];
+
+    let mut output = [0u8; 3];
+    Format::P8(&palette).encode_pixels(&[palette[2], palette[0], palette[1]], &mut output, 3, 1);
+    assert_eq!([2, 0, 1], output);
+
+    // Indices past the end of the palette decode as transparent black instead of panicking.
+    let mut decoded = [Pixel::default(); 4];
+    Format::P8(&palette).decode_pixels(&[1, 3, 255, 0], &mut decoded, 4, 1);
+    assert_eq!([palette[1], Pixel::default(), Pixel::default(), palette[0]], decoded);
+}
+
+#[test]
+pub fn palettized_invalid_palette() {
+    let too_many = [Pixel::default(); 257];
+    let mut bytes = [0u8; 4];
+    let mut pixels = [Pixel::default(); 4];
+
+    for (palette, length) in [(&too_many[..0], 0), (&too_many[..], 257)] {
+        assert_eq!(
+            Err(Error::InvalidPaletteLength { length }),
+            Format::P8(palette).try_encode_pixels(&pixels, &mut bytes, 2, 2)
+        );
+        assert_eq!(
+            Err(Error::InvalidPaletteLength { length }),
+            Format::P8(palette).try_decode_pixels(&bytes, &mut pixels, 2, 2)
+        );
+    }
+    assert_eq!(Ok(()), Format::P8(&too_many[..256]).try_decode_pixels(&bytes, &mut pixels, 2, 2));
+}
+
+#[test]
+pub fn palettized_one_bit_alpha() {
+    let mut palette = [Pixel { alpha: 255, red: 255, green: 255, blue: 255 }; 256];
+    palette[10] = Pixel { alpha: 0, red: 255, green: 255, blue: 255 };
+    palette[20] = Pixel { alpha: 0, red: 0, green: 0, blue: 0 };
+    palette[30] = Pixel { alpha: 255, red: 0, green: 0, blue: 0 };
+
+    // Transparent pixels must not map to opaque colors even if the color is closer, and vice versa.
+    let pixels = [
+        Pixel { alpha: 0, red: 10, green: 10, blue: 10 },
+        Pixel { alpha: 100, red: 250, green: 250, blue: 250 },
+        Pixel { alpha: 255, red: 10, green: 10, blue: 10 },
+        Pixel { alpha: 200, red: 250, green: 250, blue: 250 }
+    ];
+    let mut output = [0u8; 4];
+    Format::P8(&palette).encode_pixels(&pixels, &mut output, 4, 1);
+    assert_eq!([20, 10, 30, 0], output);
+
+    // The cutoff is configurable.
+    let options = EncodeOptions { alpha_threshold: 220, ..Default::default() };
+    Format::P8(&palette).encode_pixels_with(&options, &pixels, &mut output, 4, 1);
+    assert_eq!([20, 10, 30, 10], output);
+}
+
+#[test]
+pub fn palettized_transparent_index() {
+    let mut palette = [Pixel { alpha: 255, red: 0, green: 0, blue: 0 }; 256];
+    for (i, pixel) in palette.iter_mut().enumerate() {
+        pixel.red = i as u8;
+    }
+
+    let pixels = [
+        Pixel { alpha: 0, red: 100, green: 0, blue: 0 },
+        Pixel { alpha: 255, red: 100, green: 0, blue: 0 },
+        Pixel { alpha: 255, red: 0, green: 0, blue: 0 },
+        Pixel { alpha: 255, red: 255, green: 0, blue: 0 }
+    ];
+    let mut output = [0u8; 4];
+
+    // The key is only used for transparent pixels, even if it's the closest color.
+    for (index, expected) in [(0, [0, 100, 1, 255]), (255, [255, 100, 0, 254])] {
+        let options = EncodeOptions { transparent_index: Some(index), ..Default::default() };
+        Format::P8(&palette).encode_pixels_with(&options, &pixels, &mut output, 4, 1);
+        assert_eq!(expected, output);
+    }
+}
+
+#[test]
+pub fn decode_dxt1() {
+    let red = Pixel { alpha: 255, red: 255, green: 0, blue: 0 };
+    let blue = Pixel { alpha: 255, red: 0, green: 0, blue: 255 };
+    let transparent = Pixel { alpha: 0, red: 0, green: 0, blue: 0 };
+
+    // Four-color mode (red > blue): indices 0, 1, 2, 3 repeated
+    let mut block = [0u8; 8];
+    block[0..2].copy_from_slice(&0xF800u16.to_le_bytes());
+    block[2..4].copy_from_slice(&0x001Fu16.to_le_bytes());
+    block[4..8].copy_from_slice(&0b11100100_11100100_11100100_11100100u32.to_le_bytes());
+
+    let mut output = [Pixel::default(); 16];
+    Format::DXT1.decode_pixels(&block, &mut output, 4, 4);
+    assert_eq!(red, output[0]);
+    assert_eq!(blue, output[1]);
+    assert_eq!(Pixel { alpha: 255, red: 170, green: 0, blue: 85 }, output[2]);
+    assert_eq!(Pixel { alpha: 255, red: 85, green: 0, blue: 170 }, output[3]);
+
+    // Three-color mode (blue <= red): index 3 is transparent black
+    block[0..2].copy_from_slice(&0x001Fu16.to_le_bytes());
+    block[2..4].copy_from_slice(&0xF800u16.to_le_bytes());
+    Format::DXT1.decode_pixels(&block, &mut output, 4, 4);
+    assert_eq!(blue, output[0]);
+    assert_eq!(red, output[1]);
+    assert_eq!(Pixel { alpha: 255, red: 128, green: 0, blue: 128 }, output[2]);
+    assert_eq!(transparent, output[3]);
+
+    // Textures that are not a multiple of 4 only keep the pixels that fit.
+    let mut blocks = [0u8; 8 * 4];
+    for (i, block) in blocks.chunks_exact_mut(8).enumerate() {
+        block[0..2].copy_from_slice(&(0x0800u16 * (i as u16 + 1)).to_le_bytes());
+    }
+    let mut output = [Pixel::default(); 5 * 6];
+    Format::DXT1.decode_pixels(&blocks, &mut output, 5, 6);
+    assert_eq!(Pixel::from_r5g6b5(0x0800u16.to_le_bytes()), output[3]);
+    assert_eq!(Pixel::from_r5g6b5(0x1000u16.to_le_bytes()), output[4]);
+    assert_eq!(Pixel::from_r5g6b5(0x1800u16.to_le_bytes()), output[5 * 4]);
+    assert_eq!(Pixel::from_r5g6b5(0x2000u16.to_le_bytes()), output[5 * 6 - 1]);
+}
+
+/// Mean squared error per channel between two images (including alpha).
+fn mean_squared_error(a: &[Pixel], b: &[Pixel]) -> f64 {
+    let total: u64 = a.iter().zip(b.iter()).map(|(a, b)| ErrorMetric::Uniform.distance_argb(*a, *b) as u64).sum();
+    total as f64 / (a.len() * 4) as f64
+}
+
+#[test]
+pub fn roundtrip_dxt1() {
+    let ringhopper = open_ringhopper_image();
+    let mut output = [0u8; 128 * 128 / 2];
+    Format::DXT1.encode_pixels(&ringhopper, &mut output, 128, 128);
+
+    let mut decoded = [Pixel::default(); 128 * 128];
+    Format::DXT1.decode_pixels(&output, &mut decoded, 128, 128);
+
+    for (original, decoded) in ringhopper.iter().zip(decoded.iter()) {
+        assert_eq!(original.alpha > 127, decoded.alpha == 255);
+    }
+    let error = mean_squared_error(&ringhopper, &decoded);
+    assert!(error < 16.0, "DXT1 error too high: {error}");
+}
+
+#[test]
+pub fn dxt1_single_color() {
+    // Every 8-bit gray should come back within 1 of the original (apart from 565 expansion error).
+    for value in 0..=255u8 {
+        let gray = Pixel { alpha: 255, red: value, green: value, blue: value };
+        let mut output = [0u8; 8];
+        Format::DXT1.encode_pixels(&[gray; 16], &mut output, 4, 4);
+
+        let mut decoded = [Pixel::default(); 16];
+        Format::DXT1.decode_pixels(&output, &mut decoded, 4, 4);
+        for pixel in decoded {
+            assert!(pixel.distance_rgb(&gray) <= 3, "{gray:?} -> {pixel:?}");
+        }
+    }
+}
+
+#[test]
+pub fn dxt1_transparent_and_odd_size() {
+    let transparent = Pixel { alpha: 0, red: 12, green: 34, blue: 56 };
+    let opaque = Pixel { alpha: 255, red: 200, green: 100, blue: 50 };
+
+    let mut pixels = [opaque; 7 * 5];
+    pixels[0] = transparent;
+    pixels[7 * 5 - 1] = transparent;
+
+    let mut output = [0u8; 2 * 2 * 8];
+    Format::DXT1.encode_pixels(&pixels, &mut output, 7, 5);
+
+    let mut decoded = [Pixel::default(); 7 * 5];
+    Format::DXT1.decode_pixels(&output, &mut decoded, 7, 5);
+    for (original, decoded) in pixels.iter().zip(decoded.iter()) {
+        if original.alpha == 0 {
+            assert_eq!(Pixel { alpha: 0, red: 0, green: 0, blue: 0 }, *decoded);
+        }
+        else {
+            assert!(decoded.distance_rgb(original) <= 12, "{original:?} -> {decoded:?}");
+        }
+    }
+
+    let all_transparent = [transparent; 16];
+    Format::DXT1.encode_pixels(&all_transparent, &mut output[..8], 4, 4);
+    Format::DXT1.decode_pixels(&output[..8], &mut decoded[..16], 4, 4);
+    assert!(decoded[..16].iter().all(|p| p.alpha == 0));
+}
+
+/// Mean squared error per color channel between two images (ignoring alpha).
+fn color_mean_squared_error(a: &[Pixel], b: &[Pixel]) -> f64 {
+    let total: u64 = a.iter().zip(b.iter()).map(|(a, b)| a.distance_rgb(b) as u64).sum();
+    total as f64 / (a.len() * 3) as f64
+}
+
+#[test]
+pub fn roundtrip_dxt3() {
+    let mut ringhopper = open_ringhopper_image();
+    for (i, pixel) in ringhopper.iter_mut().enumerate() {
+        pixel.alpha = (i % 256) as u8;
+    }
+
+    let mut output = [0u8; 128 * 128];
+    Format::DXT3.encode_pixels(&ringhopper, &mut output, 128, 128);
+
+    let mut decoded = [Pixel::default(); 128 * 128];
+    Format::DXT3.decode_pixels(&output, &mut decoded, 128, 128);
+
+    for (original, decoded) in ringhopper.iter().zip(decoded.iter()) {
+        let expected = Pixel::from_a4r4g4b4(original.as_a4r4g4b4()).alpha;
+        assert_eq!(expected, decoded.alpha);
+    }
+    let error = color_mean_squared_error(&ringhopper, &decoded);
+    assert!(error < 20.0, "DXT3 error too high: {error}");
+
+    // Re-encoding already decoded pixels should barely change them.
+    let mut output_again = [0u8; 128 * 128];
+    Format::DXT3.encode_pixels(&decoded, &mut output_again, 128, 128);
+    let mut decoded_again = [Pixel::default(); 128 * 128];
+    Format::DXT3.decode_pixels(&output_again, &mut decoded_again, 128, 128);
+    assert_eq!(decoded.map(|p| p.alpha), decoded_again.map(|p| p.alpha));
+    let error = color_mean_squared_error(&decoded, &decoded_again);
+    assert!(error < 0.5, "DXT3 re-encoding error too high: {error}");
+}
+
+#[test]
+pub fn decode_dxt5_alpha() {
+    let mut block = [0u8; 16];
+
+    // Eight-value mode: 255 > 4
+    block[0] = 255;
+    block[1] = 4;
+    let indices: u64 = (0..16).map(|i| (i % 8) << (i * 3)).sum();
+    block[2..8].copy_from_slice(&indices.to_le_bytes()[0..6]);
+
+    let mut output = [Pixel::default(); 16];
+    Format::DXT5.decode_pixels(&block, &mut output, 4, 4);
+    assert!(output[0..8].iter().map(|p| p.alpha).eq([255, 4, 219, 183, 147, 112, 76, 40]));
+
+    // Six-value mode: 4 <= 255, plus 0 and 255
+    block[0] = 4;
+    block[1] = 255;
+    Format::DXT5.decode_pixels(&block, &mut output, 4, 4);
+    assert!(output[8..16].iter().map(|p| p.alpha).eq([4, 255, 54, 104, 155, 205, 0, 255]));
+}
+
+#[test]
+pub fn roundtrip_dxt5() {
+    let mut ringhopper = open_ringhopper_image();
+    for (i, pixel) in ringhopper.iter_mut().enumerate() {
+        pixel.alpha = match (i / 128) % 4 {
+            0 => 255,
+            1 => (i % 128) as u8,
+            2 => if i % 2 == 0 { 0 } else { 255 },
+            _ => (i % 128) as u8 * 2
+        };
+    }
+
+    let mut output = [0u8; 128 * 128];
+    Format::DXT5.encode_pixels(&ringhopper, &mut output, 128, 128);
+
+    let mut decoded = [Pixel::default(); 128 * 128];
+    Format::DXT5.decode_pixels(&output, &mut decoded, 128, 128);
+
+    for (original, decoded) in ringhopper.iter().zip(decoded.iter()) {
+        if original.alpha == 0 || original.alpha == 255 {
+            assert_eq!(original.alpha, decoded.alpha);
+        }
+        else {
+            assert!(original.alpha.abs_diff(decoded.alpha) <= 16, "{original:?} -> {decoded:?}");
+        }
+    }
+    let error = color_mean_squared_error(&ringhopper, &decoded);
+    assert!(error < 20.0, "DXT5 error too high: {error}");
+
+    // Re-encoding already decoded pixels should barely change them.
+    let mut output_again = [0u8; 128 * 128];
+    Format::DXT5.encode_pixels(&decoded, &mut output_again, 128, 128);
+    let mut decoded_again = [Pixel::default(); 128 * 128];
+    Format::DXT5.decode_pixels(&output_again, &mut decoded_again, 128, 128);
+    assert_eq!(decoded.map(|p| p.alpha), decoded_again.map(|p| p.alpha));
+    let error = color_mean_squared_error(&decoded, &decoded_again);
+    assert!(error < 0.5, "DXT5 re-encoding error too high: {error}");
+}
+
+#[test]
+pub fn roundtrip_bc7() {
+    let mut ringhopper = open_ringhopper_image();
+    for (i, pixel) in ringhopper.iter_mut().take(128 * 64).enumerate() {
+        pixel.alpha = (i % 256) as u8;
+    }
+
+    let mut output = [0u8; 128 * 128];
+    Format::BC7.encode_pixels(&ringhopper, &mut output, 128, 128);
+
+    let mut decoded = [Pixel::default(); 128 * 128];
+    Format::BC7.decode_pixels(&output, &mut decoded, 128, 128);
+
+    let error = color_mean_squared_error(&ringhopper, &decoded);
+    assert!(error < 8.0, "BC7 error too high: {error}");
+    for (original, decoded) in ringhopper.iter().zip(decoded.iter()) {
+        assert!(original.alpha.abs_diff(decoded.alpha) <= 8, "{original:?} -> {decoded:?}");
+    }
+
+    // The fast profile should be worse, but not by much for opaque blocks.
+    let opaque = &ringhopper[128 * 64..];
+    let balanced_error = color_mean_squared_error(opaque, &decoded[128 * 64..]);
+
+    let mut fast = [0u8; 128 * 64];
+    let options = EncodeOptions { bc7_profile: BC7Profile::Fast, ..Default::default() };
+    Format::BC7.encode_pixels_with(&options, opaque, &mut fast, 128, 64);
+    Format::BC7.decode_pixels(&fast, &mut decoded[128 * 64..], 128, 64);
+    let fast_error = color_mean_squared_error(opaque, &decoded[128 * 64..]);
+    assert!(fast_error >= balanced_error && fast_error < 12.0, "BC7 fast error: {fast_error} (balanced: {balanced_error})");
+}
+
+#[test]
+pub fn alpha_threshold() {
+    let half = Pixel { alpha: 150, red: 255, green: 255, blue: 255 };
+    let options = EncodeOptions { alpha_threshold: 200, ..Default::default() };
+
+    for format in [Format::DXT1, Format::A1R5G5B5] {
+        let mut output = [0u8; 32];
+        let output = &mut output[..format.required_bytes(4, 4)];
+        let mut decoded = [Pixel::default(); 16];
+
+        format.encode_pixels(&[half; 16], output, 4, 4);
+        format.decode_pixels(output, &mut decoded, 4, 4);
+        assert!(decoded.iter().all(|p| p.alpha == 255), "{format:?}");
+
+        format.encode_pixels_with(&options, &[half; 16], output, 4, 4);
+        format.decode_pixels(output, &mut decoded, 4, 4);
+        assert!(decoded.iter().all(|p| p.alpha == 0), "{format:?}");
+    }
+}
+
+#[test]
+pub fn error_metric() {
+    // Encoding with a metric should do at least as well by that metric as encoding without it.
+    let ringhopper = open_ringhopper_image();
+    for metric in [ErrorMetric::Rec601, ErrorMetric::Oklab] {
+        let options = EncodeOptions { metric, ..Default::default() };
+        let weighted_error = |decoded: &[Pixel]| -> u64 {
+            ringhopper.iter().zip(decoded).map(|(a, b)| metric.distance_rgb(*a, *b) as u64).sum()
+        };
+
+        for format in [Format::DXT1, Format::DXT5, Format::BC7] {
+            let mut output = [0u8; 128 * 128];
+            let output = &mut output[..format.required_bytes(128, 128)];
+            let mut decoded = [Pixel::default(); 128 * 128];
+
+            format.encode_pixels(&ringhopper, output, 128, 128);
+            format.decode_pixels(output, &mut decoded, 128, 128);
+            let uniform = weighted_error(&decoded);
+
+            format.encode_pixels_with(&options, &ringhopper, output, 128, 128);
+            format.decode_pixels(output, &mut decoded, 128, 128);
+            let weighted = weighted_error(&decoded);
+
+            assert!(weighted <= uniform, "{metric:?} {format:?}: {weighted} > {uniform}");
+        }
+    }
+}
+
+#[test]
+pub fn linear_light_error() {
+    // Encoding sRGB pixels in linear light should do at least as well in linear light.
+    let ringhopper = open_ringhopper_image();
+    let ringhopper = &ringhopper[48 * 128..80 * 128];
+    let options = EncodeOptions { color_space: ColorSpace::Srgb, ..Default::default() };
+    let linear_error = |decoded: &[Pixel]| -> f64 {
+        ringhopper.iter().zip(decoded).map(|(a, b)| {
+            let (a, b) = (a.to_linear(), b.to_linear());
+            (1..4).map(|c| ((a[c] - b[c]) as f64).powi(2)).sum::<f64>()
+        }).sum()
+    };
+
+    for format in [Format::DXT1, Format::DXT5, Format::BC7] {
+        let mut output = [0u8; 128 * 32];
+        let output = &mut output[..format.required_bytes(128, 32)];
+        let mut decoded = [Pixel::default(); 128 * 32];
+
+        format.encode_pixels(ringhopper, output, 128, 32);
+        format.decode_pixels(output, &mut decoded, 128, 32);
+        let gamma = linear_error(&decoded);
+
+        format.encode_pixels_with(&options, ringhopper, output, 128, 32);
+        format.decode_pixels(output, &mut decoded, 128, 32);
+        let linear = linear_error(&decoded);
+
+        assert!(linear <= gamma, "{format:?}: {linear} > {gamma}");
+    }
+}
+
+/// Sum of the squared error of each channel's mean over 4x4 areas, which is lower when dithering
+/// preserves the average color.
+fn blurred_error(a: &[Pixel], b: &[Pixel], width: usize) -> u64 {
+    let mut total = 0;
+    for y in (0..a.len() / width).step_by(4) {
+        for x in (0..width).step_by(4) {
+            let mut sums = [0i64; 4];
+            for i in (0..16).map(|i| (y + i / 4) * width + x + i % 4) {
+                for (sum, (a, b)) in sums.iter_mut().zip([(a[i].alpha, b[i].alpha), (a[i].red, b[i].red), (a[i].green, b[i].green), (a[i].blue, b[i].blue)]) {
+                    *sum += a as i64 - b as i64;
+                }
+            }
+            total += sums.iter().map(|sum| (sum * sum) as u64).sum::<u64>();
+        }
+    }
+    total
+}
+
+#[test]
+pub fn dither_gradient() {
+    // A slow gradient bands badly without dithering.
+    const WIDTH: usize = 256;
+    const HEIGHT: usize = 16;
+    let mut gradient = [Pixel::default(); WIDTH * HEIGHT];
+    for (i, pixel) in gradient.iter_mut().enumerate() {
+        let x = (i % WIDTH) as u8;
+        *pixel = Pixel { alpha: x, red: x / 3, green: x / 3, blue: x / 3 };
+    }
+
+    let mut grays = [Pixel::default(); 256];
+    for (i, gray) in grays.iter_mut().enumerate() {
+        let value = (i % 16) as u8 * 17;
+        *gray = Pixel { alpha: (i / 16) as u8 * 17, red: value, green: value, blue: value };
+    }
+
+    let formats = [
+        ("R5G6B5", Format::R5G6B5),
+        ("A1R5G5B5", Format::A1R5G5B5),
+        ("A4R4G4B4", Format::A4R4G4B4),
+        ("A4Y4", Format::A4Y4),
+        ("Y4", Format::Y4),
+        ("P8", Format::P8(&grays))
+    ];
+    for (name, format) in formats {
+        let mut output = [0u8; WIDTH * HEIGHT * 2];
+        let output = &mut output[..format.required_bytes(WIDTH, HEIGHT)];
+        let mut decoded = [Pixel::default(); WIDTH * HEIGHT];
+
+        format.encode_pixels(&gradient, output, WIDTH, HEIGHT);
+        format.decode_pixels(output, &mut decoded, WIDTH, HEIGHT);
+        let undithered = blurred_error(&gradient, &decoded, WIDTH);
+
+        for dither in [Dither::FloydSteinberg, Dither::Atkinson, Dither::Bayer] {
+            let options = EncodeOptions { dither, ..Default::default() };
+            format.encode_pixels_with(&options, &gradient, output, WIDTH, HEIGHT);
+            format.decode_pixels(output, &mut decoded, WIDTH, HEIGHT);
+            let dithered = blurred_error(&gradient, &decoded, WIDTH);
+            assert!(dithered < undithered, "{name} {dither:?}: {dithered} >= {undithered}");
+        }
+    }
+}
+
+#[test]
+pub fn dither_one_bit_alpha() {
+    // Half transparent should be dithered to about half of the pixels being opaque.
+    let half = Pixel { alpha: 128, red: 255, green: 255, blue: 255 };
+    let mut output = [0u8; 16 * 16 * 2];
+    let mut decoded = [Pixel::default(); 16 * 16];
+
+    for dither in [Dither::FloydSteinberg, Dither::Atkinson, Dither::Bayer] {
+        let options = EncodeOptions { dither, ..Default::default() };
+        Format::A1R5G5B5.encode_pixels_with(&options, &[half; 16 * 16], &mut output, 16, 16);
+        Format::A1R5G5B5.decode_pixels(&output, &mut decoded, 16, 16);
+
+        let opaque = decoded.iter().filter(|p| p.alpha == 255).count();
+        assert!((96..=160).contains(&opaque), "{dither:?}: {opaque}");
+        assert!(decoded.iter().all(|p| p.red == 255 && p.green == 255 && p.blue == 255));
+    }
+}
+
+#[test]
+pub fn dither_palette_steps() {
+    // A 4-bit grid of grays and alphas is 17 apart, and a ramp of four grays is 85 apart.
+    let mut grid = [Pixel::default(); 256];
+    for (i, pixel) in grid.iter_mut().enumerate() {
+        let value = (i % 16) as u8 * 17;
+        *pixel = Pixel { alpha: (i / 16) as u8 * 17, red: value, green: value, blue: value };
+    }
+    assert_eq!([17 << 8; 4], super::dither::palette_steps(&grid));
+
+    let ramp = [0, 85, 170, 255].map(|value| Pixel { alpha: 255, red: value, green: value, blue: value });
+    assert_eq!([0, 85 << 8, 85 << 8, 85 << 8], super::dither::palette_steps(&ramp));
+    assert_eq!([0; 4], super::dither::palette_steps(&[ramp[1]; 4]));
+
+    // A gray between the first two colors of the ramp has to be dithered by the whole step to keep
+    // its brightness (Atkinson drops some error, so it's left out).
+    let gray = Pixel { alpha: 255, red: 100, green: 100, blue: 100 };
+    let mut indices = [0u8; 16 * 16];
+    for dither in [Dither::FloydSteinberg, Dither::Bayer] {
+        let options = EncodeOptions { dither, ..Default::default() };
+        Format::P8(&ramp).encode_pixels_with(&options, &[gray; 16 * 16], &mut indices, 16, 16);
+        let mean = indices.iter().map(|i| ramp[*i as usize].red as u32).sum::<u32>() / indices.len() as u32;
+        assert!((95..=105).contains(&mean), "{dither:?}: {mean}");
+    }
+}
+
+#[test]
+pub fn dither_wide_image() {
+    // Images wider than the error diffusion strip still get dithered everywhere.
+    const WIDTH: usize = 2100;
+    let gray = Pixel { alpha: 255, red: 100, green: 100, blue: 100 };
+    let mut output = [0u8; WIDTH * 2 * 2];
+    let mut decoded = [Pixel::default(); WIDTH * 2];
+
+    let options = EncodeOptions { dither: Dither::FloydSteinberg, ..Default::default() };
+    Format::A1R5G5B5.encode_pixels_with(&options, &[gray; WIDTH * 2], &mut output, WIDTH, 2);
+    Format::A1R5G5B5.decode_pixels(&output, &mut decoded, WIDTH, 2);
+
+    let mean = decoded.iter().map(|p| p.red as u64).sum::<u64>() as f64 / decoded.len() as f64;
+    assert!((mean - 100.0).abs() < 0.5, "{mean}");
+}
+
+#[test]
+pub fn dither_strip_seam() {
+    // Without a buffer, error diffusion carries on between strips (starting at x = 256 and every
+    // 224 columns after) without a seam in the average color. Atkinson doesn't keep the average color of the whole image, so columns are
+    // compared to the image rather than the input, and the first columns of the image start
+    // without any error spread to them.
+    const WIDTH: usize = 2048;
+    const HEIGHT: usize = 32;
+    let gray = Pixel { alpha: 255, red: 100, green: 100, blue: 100 };
+    let mut output = [0u8; WIDTH * HEIGHT * 2];
+    let mut decoded = [Pixel::default(); WIDTH * HEIGHT];
+
+    for dither in [Dither::FloydSteinberg, Dither::Atkinson] {
+        let options = EncodeOptions { dither, ..Default::default() };
+        Format::A1R5G5B5.encode_pixels_with(&options, &[gray; WIDTH * HEIGHT], &mut output, WIDTH, HEIGHT);
+        Format::A1R5G5B5.decode_pixels(&output, &mut decoded, WIDTH, HEIGHT);
+
+        let mean = |x: usize, columns: usize| {
+            let pixels = decoded.chunks(WIDTH).flat_map(|row| &row[x..x + columns]);
+            pixels.map(|p| p.red as u64).sum::<u64>() as f64 / (columns * HEIGHT) as f64
+        };
+        let image_mean = mean(32, WIDTH - 32);
+        for x in (32..WIDTH).step_by(4) {
+            let mean = mean(x, 4);
+            assert!((mean - image_mean).abs() < 0.25, "{dither:?} at {x}: {mean} vs {image_mean}");
+        }
+    }
+}
+
+#[test]
+pub fn dither_buffer_is_exact() {
+    // Floyd–Steinberg over whole rows, one pixel at a time, with error in 256ths.
+    fn floyd_steinberg(format: Format, pixels: &[Pixel], width: usize) -> [Pixel; 600 * 8] {
+        let mut decoded = [Pixel::default(); 600 * 8];
+        let mut errors = [[0i32; 4]; 600 * 9];
+        for (i, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            let channels = [pixel.alpha, pixel.red, pixel.green, pixel.blue];
+            let exact: [i32; 4] = core::array::from_fn(|c| ((channels[c] as i32) << 8) + errors[i][c]).map(|v| v.clamp(0, 255 << 8));
+            let [alpha, red, green, blue] = exact.map(|v| ((v + 128) >> 8) as u8);
+
+            let mut bytes = [0u8; 2];
+            format.encode_pixels(&[Pixel { alpha, red, green, blue }], &mut bytes, 1, 1);
+            format.decode_pixels(&bytes, &mut decoded[i..i + 1], 1, 1);
+            let result = decoded[i];
+            let result = [result.alpha, result.red, result.green, result.blue];
+
+            for (dx, dy, weight) in [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)] {
+                let Some(tx) = x.checked_add_signed(dx).filter(|tx| *tx < width) else {
+                    continue
+                };
+                for c in 0..4 {
+                    errors[(y + dy) * width + tx][c] += ((exact[c] - ((result[c] as i32) << 8)) * weight) >> 4;
+                }
+            }
+        }
+        decoded
+    }
+
+    let options = EncodeOptions { dither: Dither::FloydSteinberg, ..Default::default() };
+    for width in [200, 256, 600] {
+        let height = 8;
+        let mut pixels = [Pixel::default(); 600 * 8];
+        let pixels = &mut pixels[..width * height];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = (i % width, i / width);
+            *pixel = Pixel { alpha: (x * 7 + y * 31) as u8, red: (x / 3) as u8, green: (x * y) as u8, blue: 90 };
+        }
+        let expected = floyd_steinberg(Format::A4R4G4B4, pixels, width);
+
+        let mut output = [0u8; 600 * 8 * 2];
+        let output = &mut output[..width * height * 2];
+        let mut buffer = [[1; 4]; 600 * 3];
+        let mut decoded = [Pixel::default(); 600 * 8];
+        let decoded = &mut decoded[..width * height];
+        Format::A4R4G4B4.encode_pixels_with_buffer(&options, pixels, output, &mut buffer[..Dither::buffer_length(width)], width, height);
+        Format::A4R4G4B4.decode_pixels(output, decoded, width, height);
+        assert_eq!(&expected[..width * height], decoded, "{width}");
+
+        // Images that fit in one strip are exact without a buffer too.
+        Format::A4R4G4B4.encode_pixels_with(&options, pixels, output, width, height);
+        Format::A4R4G4B4.decode_pixels(output, decoded, width, height);
+        assert_eq!(width <= 256, expected[..width * height] == *decoded, "{width}");
+    }
+
+    let pixels = [Pixel::default(); 16];
+    let mut bytes = [0u8; 32];
+    let mut buffer = [[0; 4]; 12];
+    assert_eq!(Ok(()), Format::R5G6B5.try_encode_pixels_with_buffer(&options, &pixels, &mut bytes, &mut buffer, 4, 4));
+    assert_eq!(
+        Err(Error::InvalidBufferLength { expected: 12, actual: 11 }),
+        Format::R5G6B5.try_encode_pixels_with_buffer(&options, &pixels, &mut bytes, &mut buffer[..11], 4, 4)
+    );
+}
+
+#[test]
+pub fn fallible_conversion() {
+    let pixels = [Pixel::default(); 16];
+    let mut bytes = [0u8; 16];
+
+    assert_eq!(Ok(()), Format::DXT1.try_encode_pixels(&pixels, &mut bytes[..8], 4, 4));
+    assert_eq!(
+        Err(Error::InvalidOutputLength { expected: 8, actual: 16 }),
+        Format::DXT1.try_encode_pixels(&pixels, &mut bytes, 4, 4)
+    );
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: 12, actual: 16 }),
+        Format::A8.try_encode_pixels(&pixels, &mut bytes[..12], 4, 3)
+    );
+    assert_eq!(
+        Err(Error::SizeOverflow),
+        Format::A8.try_encode_pixels(&pixels, &mut bytes, usize::MAX, 2)
+    );
+
+    let mut pixels = [Pixel::default(); 16];
+    assert_eq!(Ok(()), Format::DXT5.try_decode_pixels(&bytes, &mut pixels, 4, 4));
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: 32, actual: 16 }),
+        Format::A8Y8.try_decode_pixels(&bytes, &mut pixels, 4, 4)
+    );
+    assert_eq!(
+        Err(Error::InvalidOutputLength { expected: 8, actual: 16 }),
+        Format::A8Y8.try_decode_pixels(&bytes, &mut pixels, 4, 2)
+    );
+    assert_eq!(
+        Err(Error::SizeOverflow),
+        Format::BC7.try_decode_pixels(&bytes, &mut pixels, usize::MAX, usize::MAX)
+    );
+}
+
+#[test]
+pub fn format_codes() {
+    let palette = [Pixel::default(); 4];
+    let formats = [
+        Format::DXT1, Format::DXT3, Format::DXT5, Format::BC7, Format::A8, Format::Y8, Format::AY8, Format::A8Y8,
+        Format::A4, Format::Y4, Format::A4Y4, Format::R5G6B5, Format::A1R5G5B5, Format::A4R4G4B4, Format::X8R8G8B8,
+        Format::A8R8G8B8, Format::A8B8G8R8, Format::P8(&palette), Format::P4(&palette)
+    ];
+
+    // Every code converts back to the same format.
+    let mut d3d_count = 0;
+    let mut dxgi_count = 0;
+    let mut fourcc_count = 0;
+    for format in formats {
+        if let Ok(code) = format.to_d3dformat() {
+            d3d_count += 1;
+            if !matches!(format, Format::P8(_)) {
+                assert_eq!(Ok(format), Format::from_d3dformat(code), "{format:?}");
+            }
+        }
+        if let Ok(code) = format.to_dxgi(ColorSpace::Linear) {
+            dxgi_count += 1;
+            assert_eq!(Ok((format, ColorSpace::Linear)), Format::from_dxgi(code), "{format:?}");
+        }
+        if let Ok(code) = format.to_dxgi(ColorSpace::Srgb) {
+            assert_eq!(Ok((format, ColorSpace::Srgb)), Format::from_dxgi(code), "{format:?}");
+        }
+        if let Ok(code) = format.to_fourcc() {
+            fourcc_count += 1;
+            assert_eq!(Ok(format), Format::from_fourcc(code), "{format:?}");
+            assert_eq!(Ok(u32::from_le_bytes(code)), format.to_d3dformat(), "{format:?}");
+        }
+    }
+    assert_eq!((14, 11, 3), (d3d_count, dxgi_count, fourcc_count));
+
+    // The palette isn't part of the code.
+    assert_eq!(Ok(41), Format::P8(&palette).to_d3dformat());
+    assert_eq!(Err(Error::UnsupportedFormat), Format::from_d3dformat(41));
+
+    for (format, linear, srgb) in [(Format::DXT1, 71, 72), (Format::DXT3, 74, 75), (Format::DXT5, 77, 78), (Format::BC7, 98, 99)] {
+        assert_eq!(Ok(linear), format.to_dxgi(ColorSpace::Linear));
+        assert_eq!(Ok(srgb), format.to_dxgi(ColorSpace::Srgb));
+    }
+    assert_eq!(Err(Error::UnsupportedFormat), Format::A8.to_dxgi(ColorSpace::Srgb));
+    assert_eq!(Err(Error::UnsupportedFormat), Format::Y8.to_dxgi(ColorSpace::Linear));
+
+    // X1R5G5B5, BC5, and premultiplied DXT4 have no equivalent.
+    assert_eq!(Err(Error::UnsupportedFormat), Format::from_d3dformat(24));
+    assert_eq!(Err(Error::UnsupportedFormat), Format::from_dxgi(83));
+    assert_eq!(Err(Error::UnsupportedFormat), Format::from_fourcc(*b"DXT4"));
+}
+
+#[test]
+pub fn swizzle_layout() {
+    // 8x2 runs out of y bits first, so the rest of the bits are x.
+    let mut bytes: [u8; 16] = core::array::from_fn(|i| i as u8);
+    Format::Y8.swizzle(&mut bytes, 8, 2, 1);
+    assert_eq!([0, 1, 8, 9, 2, 3, 10, 11, 4, 5, 12, 13, 6, 7, 14, 15], bytes);
+
+    // 2x8 runs out of x bits first.
+    let mut bytes: [u8; 16] = core::array::from_fn(|i| i as u8);
+    Format::Y8.swizzle(&mut bytes, 2, 8, 1);
+    assert_eq!(core::array::from_fn::<u8, 16, _>(|i| i as u8), bytes);
+
+    // 4x2x2 interleaves x, y, and z, then has one more x bit.
+    let mut bytes: [u8; 16] = core::array::from_fn(|i| i as u8);
+    Format::A8.swizzle(&mut bytes, 4, 2, 2);
+    assert_eq!([0, 1, 4, 5, 8, 9, 12, 13, 2, 3, 6, 7, 10, 11, 14, 15], bytes);
+
+    // Pixels larger than a byte move together.
+    let mut bytes: [u8; 8] = core::array::from_fn(|i| i as u8);
+    Format::A8Y8.swizzle(&mut bytes, 1, 4, 1);
+    assert_eq!([0, 1, 2, 3, 4, 5, 6, 7], bytes);
+    let mut bytes: [u8; 16] = core::array::from_fn(|i| i as u8);
+    Format::A8Y8.swizzle(&mut bytes, 4, 2, 1);
+    assert_eq!([0, 1, 2, 3, 8, 9, 10, 11, 4, 5, 6, 7, 12, 13, 14, 15], bytes);
+}
+
+#[test]
+pub fn swizzle_roundtrip() {
+    let mut original = [0u8; 64 * 16 * 4 * 4];
+    let mut state = 0x9E37_79B9u32;
+    for byte in original.iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *byte = state as u8;
+    }
+
+    for (width, height, depth) in [(64, 16, 4), (16, 64, 4), (4, 16, 64), (32, 32, 4), (1, 64, 64), (4096, 1, 1), (1, 1, 1)] {
+        let mut copy = original;
+        let bytes = &mut copy[..width * height * depth * 4];
+        Format::A8R8G8B8.swizzle(bytes, width, height, depth);
+        // Images with only one dimension are already in swizzled order.
+        let unchanged = original[..bytes.len()] == *bytes;
+        let dimensions = [width, height, depth].iter().filter(|size| **size > 1).count();
+        assert_eq!(dimensions <= 1, unchanged, "{width}x{height}x{depth}");
+        Format::A8R8G8B8.unswizzle(bytes, width, height, depth);
+        assert_eq!(&original[..bytes.len()], bytes, "{width}x{height}x{depth}");
+    }
+}
+
+#[test]
+pub fn swizzle_errors() {
+    let mut bytes = [0u8; 64];
+    assert_eq!(Err(Error::UnsupportedFormat), Format::DXT1.try_swizzle(&mut bytes[..8], 4, 4, 1));
+    assert_eq!(Err(Error::UnsupportedFormat), Format::A4.try_unswizzle(&mut bytes[..8], 4, 4, 1));
+    assert_eq!(Err(Error::NonPowerOfTwo), Format::A8.try_swizzle(&mut bytes[..24], 6, 4, 1));
+    assert_eq!(Err(Error::NonPowerOfTwo), Format::A8.try_swizzle(&mut bytes[..48], 4, 4, 3));
+    assert_eq!(Err(Error::NonPowerOfTwo), Format::A8.try_swizzle(&mut bytes[..0], 0, 4, 1));
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: 32, actual: 64 }),
+        Format::A8Y8.try_unswizzle(&mut bytes, 4, 4, 1)
+    );
+    assert_eq!(Err(Error::SizeOverflow), Format::A8.try_swizzle(&mut bytes, 1 << (usize::BITS - 1), 4, 1));
+}
+
+#[test]
+pub fn format_queries() {
+    let palette = [
+        Pixel { alpha: 0, red: 255, green: 0, blue: 0 },
+        Pixel { alpha: 255, red: 0, green: 255, blue: 0 }
+    ];
+    let formats = [
+        Format::DXT1, Format::DXT3, Format::DXT5, Format::BC7, Format::A8, Format::Y8, Format::AY8, Format::A8Y8,
+        Format::A4, Format::Y4, Format::A4Y4, Format::R5G6B5, Format::A1R5G5B5, Format::A4R4G4B4, Format::X8R8G8B8,
+        Format::A8R8G8B8, Format::A8B8G8R8, Format::P8(&palette), Format::P4(&palette)
+    ];
+
+    // The queries agree with what encoding and decoding actually do.
+    let transparent = [Pixel { alpha: 0, red: 255, green: 0, blue: 0 }; 16];
+    let opaque = [Pixel { alpha: 255, red: 255, green: 0, blue: 0 }; 16];
+    let roundtrip = |format: Format, pixels: &[Pixel]| {
+        let mut bytes = [0u8; 64];
+        let mut decoded = [Pixel::default(); 16];
+        let bytes = &mut bytes[..format.required_bytes(4, 4)];
+        format.encode_pixels(pixels, bytes, 4, 4);
+        format.decode_pixels(bytes, &mut decoded, 4, 4);
+        decoded[0]
+    };
+    for format in formats {
+        let (block_width, block_height) = format.block_size_pixels();
+        assert_eq!(format.required_bytes(4, 4) * 8, format.bits_per_pixel() * 16, "{format:?}");
+        assert_eq!(block_width * block_height * format.bits_per_pixel(), format.block_size_bytes() * 8, "{format:?}");
+        assert_eq!(format.is_compressed(), block_height > 1, "{format:?}");
+
+        assert_eq!(format.has_alpha(), roundtrip(format, &transparent).alpha == 0, "{format:?}");
+        let decoded = roundtrip(format, &opaque);
+        assert_eq!(format.is_monochrome(), decoded.red == decoded.green, "{format:?}");
+
+        let [alpha, red, green, blue] = format.channel_bits();
+        assert_eq!(alpha, format.alpha_bits());
+        assert!(!format.is_monochrome() || red == green && green == blue, "{format:?}");
+        assert!(alpha as usize + red as usize + green as usize + blue as usize <= 32, "{format:?}");
+    }
+
+    assert_eq!(2, formats.iter().filter(|f| f.is_palettized()).count());
+    assert_eq!(0, Format::X8R8G8B8.alpha_bits());
+    assert_eq!(1, Format::A1R5G5B5.alpha_bits());
+    assert_eq!([8, 8, 8, 8], Format::AY8.channel_bits());
+}
+
+#[test]
+pub fn suggest_lossless() {
+    let gray = |value: u8| Pixel { alpha: 255, red: value, green: value, blue: value };
+
+    // Every gray level needs 8 bits, and Y8 is picked over P8, which also needs the palette.
+    let image: [Pixel; 256] = core::array::from_fn(|i| gray(i as u8));
+    let suggestion = Format::suggest(&image, 16, 16, FormatPolicy::LosslessOnly);
+    let analysis = suggestion.analysis();
+    assert_eq!((AlphaKind::Opaque, true, false, Some(256)), (analysis.alpha, analysis.monochrome, analysis.alpha_is_luminance, analysis.unique_colors));
+    assert_eq!(Format::Y8, suggestion.best().unwrap().format);
+    assert!(suggestion.candidates().all(|c| !c.format.is_compressed()));
+
+    // Alpha equal to luminance fits in AY8.
+    let image: [Pixel; 256] = core::array::from_fn(|i| Pixel { alpha: i as u8, ..gray(i as u8) });
+    let suggestion = Format::suggest(&image, 16, 16, FormatPolicy::LosslessOnly);
+    assert!(suggestion.analysis().alpha_is_luminance);
+    assert_eq!(AlphaKind::Full, suggestion.analysis().alpha);
+    assert_eq!(Format::AY8, suggestion.best().unwrap().format);
+
+    // A few colors that 4-bit and 16-bit formats can't store fit in P4 with the image's colors.
+    let colors = [
+        Pixel { alpha: 255, red: 100, green: 30, blue: 7 },
+        Pixel { alpha: 255, red: 7, green: 100, blue: 30 },
+        Pixel { alpha: 0, red: 0, green: 0, blue: 0 }
+    ];
+    let image: [Pixel; 64] = core::array::from_fn(|i| colors[i % 3]);
+    let suggestion = Format::suggest(&image, 8, 8, FormatPolicy::LosslessOnly);
+    assert_eq!(AlphaKind::Binary, suggestion.analysis().alpha);
+    assert_eq!(Some(3), suggestion.analysis().unique_colors);
+    let best = suggestion.best().unwrap();
+    assert_eq!((32 + 3 * 4, 0.0), (best.bytes, best.error));
+    let Format::P4(palette) = best.format else { panic!("{:?}", best.format) };
+    assert_eq!(3, palette.len());
+    assert!(colors.iter().all(|c| palette.contains(c)));
+
+    // The palette counts towards the size.
+    let suggestion = Format::suggest(&image, 8, 8, FormatPolicy::MaxBytes(32));
+    assert!(suggestion.candidates().all(|c| !c.format.is_palettized()));
+
+    // The color of invisible pixels doesn't count.
+    let image: [Pixel; 64] = core::array::from_fn(|i| Pixel { alpha: 0, red: i as u8, green: 3, blue: 200 });
+    let suggestion = Format::suggest(&image, 8, 8, FormatPolicy::LosslessOnly);
+    assert_eq!(Some(1), suggestion.analysis().unique_colors);
+    assert_eq!(Format::A4, suggestion.best().unwrap().format);
+}
+
+#[test]
+pub fn suggest_policies() {
+    let mut image = [Pixel::default(); 32 * 16];
+    let mut state = 0x1234_5678u32;
+    for pixel in image.iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let [alpha, red, green, blue] = state.to_le_bytes();
+        *pixel = Pixel { alpha: alpha | 1, red, green, blue };
+    }
+
+    let suggestion = Format::suggest(&image, 32, 16, FormatPolicy::LosslessOnly);
+    assert_eq!((AlphaKind::Full, false, None), (suggestion.analysis().alpha, suggestion.analysis().monochrome, suggestion.analysis().unique_colors));
+    assert_eq!(Format::A8R8G8B8, suggestion.best().unwrap().format);
+    assert!(suggestion.candidates().all(|c| !c.format.is_palettized()));
+
+    // Full alpha needs more than DXT1, and the rest are the same size.
+    let suggestion = Format::suggest(&image, 32, 16, FormatPolicy::PreferCompressed);
+    let best = suggestion.best().unwrap();
+    assert_eq!(512, best.bytes);
+    assert!(matches!(best.format, Format::DXT3 | Format::DXT5 | Format::BC7), "{:?}", best.format);
+    for candidate in suggestion.candidates().filter(|c| c.format.is_compressed() && c.format != Format::DXT1) {
+        assert!(best.error <= candidate.error, "{candidate:?}");
+    }
+
+    // The least error that fits, which is lossless if there's room.
+    let suggestion = Format::suggest(&image, 32, 16, FormatPolicy::MaxBytes(32 * 16 * 2));
+    let best = suggestion.best().unwrap();
+    assert!(suggestion.candidates().all(|c| c.bytes <= 32 * 16 * 2 && best.error <= c.error));
+    assert_eq!(Format::A4R4G4B4, best.format);
+    let suggestion = Format::suggest(&image, 32, 16, FormatPolicy::MaxBytes(32 * 16 * 4));
+    assert_eq!((Format::A8R8G8B8, 0.0), (suggestion.best().unwrap().format, suggestion.best().unwrap().error));
+
+    // Nothing fits.
+    let suggestion = Format::suggest(&image, 32, 16, FormatPolicy::MaxBytes(127));
+    assert_eq!(None, suggestion.best());
+    assert_eq!(0, suggestion.candidates().count());
+
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: 32 * 15, actual: 32 * 16 }),
+        Format::try_suggest(&image, 32, 15, FormatPolicy::LosslessOnly).map(|s| s.analysis())
+    );
+}
@@ -0,0 +1,235 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{generate_mipmaps, mipmap_count, mipmap_pixel_count, mipmap_size};
+use crate::{ColorSpace, Dither, EncodeOptions, Error, Format, MipmapOptions, Pixel};
+
+/// An owned 8-bit image.
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::{Format, Image, Pixel};
+///
+/// let red = Pixel { alpha: 255, red: 255, green: 0, blue: 0 };
+/// let image = Image::from_pixels(4, 4, vec![red; 16]).unwrap();
+///
+/// let encoded = image.encode(Format::DXT1);
+/// assert_eq!(8, encoded.data().len());
+/// assert_eq!(image, encoded.decode());
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    color_space: ColorSpace,
+    pixels: Vec<Pixel>
+}
+
+impl Image {
+    /// Create an image filled with transparent black.
+    ///
+    /// The color space is [`ColorSpace::Linear`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width*height` overflows [usize].
+    pub fn new(width: usize, height: usize) -> Self {
+        let count = width.checked_mul(height).expect("Image::new(): width*height overflows usize");
+        Self { width, height, color_space: ColorSpace::default(), pixels: vec![Pixel::default(); count] }
+    }
+
+    /// Create an image from its pixels, stored row by row.
+    ///
+    /// The color space is [`ColorSpace::Linear`].
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::SizeOverflow`] if `width*height` overflows [usize]
+    /// * [`Error::InvalidInputLength`] if `pixels.len() != width*height`
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Pixel>) -> Result<Self, Error> {
+        let expected = width.checked_mul(height).ok_or(Error::SizeOverflow)?;
+        if pixels.len() != expected {
+            return Err(Error::InvalidInputLength { expected, actual: pixels.len() })
+        }
+        Ok(Self { width, height, color_space: ColorSpace::default(), pixels })
+    }
+
+    /// Width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Color space of the pixels.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Set the color space of the pixels.
+    ///
+    /// This doesn't change the pixels, only how they're processed.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    /// Get the pixels of the image.
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    /// Get the pixels of the image mutably.
+    pub fn pixels_mut(&mut self) -> &mut [Pixel] {
+        &mut self.pixels
+    }
+
+    /// Take the pixels out of the image.
+    pub fn into_pixels(self) -> Vec<Pixel> {
+        self.pixels
+    }
+
+    /// Generate every mipmap of the image down to 1x1, starting with a copy of the image.
+    ///
+    /// See [`generate_mipmaps`] for details. The image's color space is used instead of
+    /// `options.color_space`, and each mipmap has the same color space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total number of pixels overflows [usize].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Image, MipmapOptions};
+    ///
+    /// let mipmaps = Image::new(8, 2).generate_mipmaps(&MipmapOptions::default());
+    /// let sizes: Vec<_> = mipmaps.iter().map(|m| (m.width(), m.height())).collect();
+    /// assert_eq!(vec![(8, 2), (4, 1), (2, 1), (1, 1)], sizes);
+    /// ```
+    pub fn generate_mipmaps(&self, options: &MipmapOptions) -> Vec<Image> {
+        let mut pixels = vec![Pixel::default(); mipmap_pixel_count(self.width, self.height)];
+        let options = MipmapOptions { color_space: self.color_space, ..*options };
+        generate_mipmaps(&options, &self.pixels, self.width, self.height, &mut pixels);
+
+        let mut remaining = pixels.as_slice();
+        (0..mipmap_count(self.width, self.height)).map(|level| {
+            let (width, height) = mipmap_size(self.width, self.height, level);
+            let (level_pixels, rest) = remaining.split_at(width * height);
+            remaining = rest;
+            Image { width, height, color_space: self.color_space, pixels: level_pixels.to_vec() }
+        }).collect()
+    }
+
+    /// Encode the image to a format.
+    ///
+    /// This is the same as [`encode_with`](Image::encode_with) with the default [`EncodeOptions`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the encoded image overflows [usize] or the format is palettized and
+    /// the palette has no colors or too many colors.
+    pub fn encode<'a>(&self, format: Format<'a>) -> EncodedImage<'a> {
+        self.encode_with(format, &EncodeOptions::default())
+    }
+
+    /// Encode the image to a format with the given options.
+    ///
+    /// The image's color space is used instead of `options.color_space`, and the encoded image has
+    /// the same color space. Error diffusion is always exact, since there's room for a buffer of
+    /// errors (see [`Format::encode_pixels_with_buffer`]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the encoded image overflows [usize] or the format is palettized and
+    /// the palette has no colors or too many colors.
+    pub fn encode_with<'a>(&self, format: Format<'a>, options: &EncodeOptions) -> EncodedImage<'a> {
+        let options = EncodeOptions { color_space: self.color_space, ..*options };
+        let mut data = vec![0u8; format.required_bytes(self.width, self.height)];
+        let mut buffer = vec![[0; 4]; Dither::buffer_length(self.width)];
+        format.encode_pixels_with_buffer(&options, &self.pixels, &mut data, &mut buffer, self.width, self.height);
+        EncodedImage { format, width: self.width, height: self.height, color_space: self.color_space, data }
+    }
+}
+
+/// An owned image encoded in a [`Format`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct EncodedImage<'a> {
+    format: Format<'a>,
+    width: usize,
+    height: usize,
+    color_space: ColorSpace,
+    data: Vec<u8>
+}
+
+impl<'a> EncodedImage<'a> {
+    /// Create an encoded image from its data.
+    ///
+    /// The color space is [`ColorSpace::Linear`].
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::SizeOverflow`] if the required bytes overflow [usize]
+    /// * [`Error::InvalidInputLength`] if `data.len() != format.required_bytes(width, height)`
+    pub fn new(format: Format<'a>, width: usize, height: usize, data: Vec<u8>) -> Result<Self, Error> {
+        let expected = format.checked_required_bytes(width, height)?;
+        if data.len() != expected {
+            return Err(Error::InvalidInputLength { expected, actual: data.len() })
+        }
+        Ok(Self { format, width, height, color_space: ColorSpace::default(), data })
+    }
+
+    /// Format the image is encoded in.
+    pub fn format(&self) -> Format<'a> {
+        self.format
+    }
+
+    /// Width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Color space of the encoded pixels.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Set the color space of the encoded pixels.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    /// Get the encoded data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Take the encoded data out of the image.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Decode the image to 8-bit, keeping its color space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width*height` overflows [usize] or the format is palettized and the palette has
+    /// no colors or too many colors.
+    pub fn decode(&self) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        image.color_space = self.color_space;
+        self.format.decode_pixels(&self.data, &mut image.pixels, self.width, self.height);
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,70 @@
+use alloc::vec;
+
+use crate::{ColorSpace, Dither, EncodeOptions, EncodedImage, Error, Format, Image, MipmapOptions, Pixel};
+
+#[test]
+pub fn invalid_lengths() {
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: 6, actual: 5 }),
+        Image::from_pixels(2, 3, vec![Pixel::default(); 5])
+    );
+    assert_eq!(Err(Error::SizeOverflow), Image::from_pixels(usize::MAX, 2, vec![]));
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: 32, actual: 8 }),
+        EncodedImage::new(Format::DXT5, 5, 1, vec![0; 8])
+    );
+}
+
+#[test]
+pub fn encode_and_decode() {
+    let mut image = Image::new(3, 2);
+    assert_eq!(&[Pixel::default(); 6], image.pixels());
+
+    let gray = Pixel { alpha: 255, red: 0x88, green: 0x88, blue: 0x88 };
+    image.pixels_mut()[1] = gray;
+
+    let encoded = image.encode(Format::Y4);
+    assert_eq!((3, 2), (encoded.width(), encoded.height()));
+    assert_eq!(Format::Y4, encoded.format());
+    assert_eq!(&[0x80, 0x00, 0x00, 0x00], encoded.data());
+
+    let decoded = encoded.decode();
+    assert_eq!(gray, decoded.pixels()[1]);
+
+    let copy = EncodedImage::new(Format::Y4, 3, 2, encoded.clone().into_data()).unwrap();
+    assert_eq!(encoded, copy);
+}
+
+#[test]
+pub fn color_space_is_kept() {
+    let mut image = Image::new(4, 4);
+    assert_eq!(ColorSpace::Linear, image.color_space());
+    image.set_color_space(ColorSpace::Srgb);
+
+    let encoded = image.encode(Format::DXT1);
+    assert_eq!(ColorSpace::Srgb, encoded.color_space());
+    assert_eq!(ColorSpace::Srgb, encoded.decode().color_space());
+
+    let mipmaps = image.generate_mipmaps(&MipmapOptions::default());
+    assert_eq!(3, mipmaps.len());
+    assert!(mipmaps.iter().all(|m| m.color_space() == ColorSpace::Srgb));
+}
+
+#[test]
+pub fn wide_images_are_dithered_exactly() {
+    let mut image = Image::new(1000, 4);
+    for (i, pixel) in image.pixels_mut().iter_mut().enumerate() {
+        let value = (i % 1000 / 4) as u8;
+        *pixel = Pixel { alpha: 255, red: value, green: value, blue: value };
+    }
+
+    let options = EncodeOptions { dither: Dither::FloydSteinberg, ..Default::default() };
+    let mut expected = vec![0u8; Format::R5G6B5.required_bytes(1000, 4)];
+    let mut buffer = vec![[0; 4]; Dither::buffer_length(1000)];
+    Format::R5G6B5.encode_pixels_with_buffer(&options, image.pixels(), &mut expected, &mut buffer, 1000, 4);
+    assert_eq!(expected, image.encode_with(Format::R5G6B5, &options).data());
+
+    let mut strips = vec![0u8; expected.len()];
+    Format::R5G6B5.encode_pixels_with(&options, image.pixels(), &mut strips, 1000, 4);
+    assert_ne!(expected, strips);
+}
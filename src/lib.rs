@@ -1,7 +1,30 @@
 #![no_std]
 
-pub use crate::pixel::Pixel;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub use crate::dds::DdsHeader;
+pub use crate::error::Error;
+pub use crate::metric::ErrorMetric;
+pub use crate::palette::Quantizer;
+pub use crate::pixel::{ColorSpace, Pixel};
 pub use crate::format::Format;
+pub use crate::format::{BC7Profile, Dither, EncodeOptions};
+pub use crate::format::{AlphaKind, FormatCandidate, FormatPolicy, FormatSuggestion, ImageAnalysis};
+pub use crate::mipmap::{FadeCurve, MipmapFade, MipmapFilter, MipmapOptions};
+pub use crate::mipmap::{checked_mipmap_pixel_count, generate_mipmaps, mipmap_count, mipmap_pixel_count, mipmap_size, try_generate_mipmaps};
+
+#[cfg(feature = "alloc")]
+pub use crate::image::{EncodedImage, Image};
 
+mod dds;
+mod error;
+mod metric;
+mod palette;
 mod pixel;
 mod format;
+mod math;
+mod mipmap;
+
+#[cfg(feature = "alloc")]
+mod image;
@@ -0,0 +1,87 @@
+//! Floating point functions that `f32` only provides with `std`.
+
+use core::f32::consts::PI;
+
+/// Square root using Newton's method.
+pub(crate) fn sqrt(value: f32) -> f32 {
+    if value <= 0.0 {
+        return 0.0
+    }
+
+    // Halving the exponent gives a close enough first guess.
+    let mut x = f32::from_bits((value.to_bits() >> 1) + 0x1FC0_0000);
+    for _ in 0..4 {
+        x = 0.5 * (x + value / x);
+    }
+    x
+}
+
+/// Cube root using Newton's method.
+pub(crate) fn cbrt(value: f32) -> f32 {
+    if value <= 0.0 {
+        return 0.0
+    }
+
+    // Dividing the exponent by three gives a close enough first guess.
+    let mut x = f32::from_bits(value.to_bits() / 3 + 0x2A50_8935);
+    for _ in 0..4 {
+        x = (2.0 * x + value / (x * x)) / 3.0;
+    }
+    x
+}
+
+/// Round down to an integer (for values that fit in an `i32`).
+pub(crate) fn floor(value: f32) -> f32 {
+    let truncated = value as i32 as f32;
+    if truncated > value { truncated - 1.0 } else { truncated }
+}
+
+/// Calculate `sin(PI * x)`.
+pub(crate) fn sin_pi(x: f32) -> f32 {
+    // Reduce to [-1, 1), then to [-1/2, 1/2] using sin(PI - a) = sin(a).
+    let mut x = x - 2.0 * floor((x + 1.0) * 0.5);
+    if x > 0.5 {
+        x = 1.0 - x;
+    }
+    else if x < -0.5 {
+        x = -1.0 - x;
+    }
+
+    // Taylor series, which is accurate to about 1e-7 for |x| <= PI/2.
+    let x = x * PI;
+    let x2 = x * x;
+    let mut term = x;
+    let mut sum = x;
+    for n in 1..6 {
+        term *= -x2 / ((2 * n) * (2 * n + 1)) as f32;
+        sum += term;
+    }
+    sum
+}
+
+/// Normalized sinc, `sin(PI * x) / (PI * x)`.
+pub(crate) fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    }
+    else {
+        sin_pi(x) / (PI * x)
+    }
+}
+
+/// Zeroth order modified Bessel function of the first kind.
+pub(crate) fn bessel_i0(x: f32) -> f32 {
+    let quarter_x2 = x * x * 0.25;
+    let mut term = 1.0f32;
+    let mut sum = 1.0f32;
+    let mut k = 1.0f32;
+    while term > sum * 1e-8 {
+        term *= quarter_x2 / (k * k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests;
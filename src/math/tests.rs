@@ -0,0 +1,57 @@
+use super::{bessel_i0, cbrt, floor, sin_pi, sinc, sqrt};
+
+#[test]
+pub fn sqrt_matches_squares() {
+    assert_eq!(0.0, sqrt(0.0));
+    assert_eq!(0.0, sqrt(-4.0));
+    for i in 1..1000 {
+        let value = i as f32 * 0.37;
+        assert!((sqrt(value * value) - value).abs() <= value * 1e-6, "sqrt({})", value * value);
+    }
+}
+
+#[test]
+pub fn cbrt_matches_cubes() {
+    assert_eq!(0.0, cbrt(0.0));
+    for i in 1..1000 {
+        let value = i as f32 * 0.0013;
+        assert!((cbrt(value * value * value) - value).abs() <= value * 1e-6, "cbrt({})", value * value * value);
+    }
+}
+
+#[test]
+pub fn floor_negative() {
+    assert_eq!(-2.0, floor(-1.5));
+    assert_eq!(-1.0, floor(-1.0));
+    assert_eq!(1.0, floor(1.99));
+    assert_eq!(0.0, floor(0.0));
+}
+
+#[test]
+pub fn sin_known_values() {
+    // sin(PI * x) for x in 1/6ths
+    let expected = [0.0, 0.5, 0.866_025_4, 1.0, 0.866_025_4, 0.5, 0.0, -0.5, -0.866_025_4, -1.0, -0.866_025_4, -0.5];
+    for turns in -2..=2 {
+        for (i, expected) in expected.iter().enumerate() {
+            let x = i as f32 / 6.0 + (turns * 2) as f32;
+            assert!((sin_pi(x) - expected).abs() < 1e-5, "sin_pi({x}) = {}", sin_pi(x));
+        }
+    }
+}
+
+#[test]
+pub fn sinc_zeros() {
+    assert_eq!(1.0, sinc(0.0));
+    for i in 1..6 {
+        assert!(sinc(i as f32).abs() < 1e-6);
+        assert!(sinc(-(i as f32)).abs() < 1e-6);
+    }
+    assert!((sinc(0.5) - 2.0 / core::f32::consts::PI).abs() < 1e-6);
+}
+
+#[test]
+pub fn bessel_known_values() {
+    assert_eq!(1.0, bessel_i0(0.0));
+    assert!((bessel_i0(1.0) - 1.266_066).abs() < 1e-5);
+    assert!((bessel_i0(4.0) - 11.301_922).abs() < 1e-4);
+}
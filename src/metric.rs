@@ -0,0 +1,148 @@
+use crate::math::cbrt;
+use crate::pixel::{difference_squared, srgb_to_linear};
+use crate::{ColorSpace, Pixel};
+
+/// Defines how the difference between two colors is measured when picking the closest color.
+///
+/// This is used when matching pixels to a palette and when choosing endpoints and indices for
+/// block compression.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum ErrorMetric {
+    /// Red, green, and blue are weighted equally.
+    #[default]
+    Uniform,
+
+    /// Channels are weighted by their contribution to Rec. 601 luma (the same weights used by
+    /// [`Pixel::as_y8`]).
+    ///
+    /// `0.299 * red + 0.587 * green + 0.114 * blue`
+    Rec601,
+
+    /// Channels are weighted by their contribution to Rec. 709 luma.
+    ///
+    /// `0.2126 * red + 0.7152 * green + 0.0722 * blue`
+    Rec709,
+
+    /// The "redmean" weighted RGB distance, where the weights of red and blue depend on how red
+    /// the two colors are.
+    ///
+    /// This is a cheap approximation of how different colors look, and not a perceptual color space
+    /// difference such as [`Oklab`](Self::Oklab).
+    Redmean,
+
+    /// Perceptual difference, measured as the distance between the colors in the Oklab color space.
+    ///
+    /// Pixels are always treated as sRGB, regardless of the [`ColorSpace`]. This is the slowest
+    /// metric. Block compression fits endpoints with fixed channel weights that approximate it.
+    Oklab
+}
+
+impl ErrorMetric {
+    /// Returns the weighted distance squared between two pixels, ignoring alpha.
+    ///
+    /// Distances are only comparable with other distances from the same metric.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{ErrorMetric, Pixel};
+    ///
+    /// let gray = Pixel { alpha: 255, red: 128, green: 128, blue: 128 };
+    /// let greener = Pixel { green: 148, ..gray };
+    /// let bluer = Pixel { blue: 148, ..gray };
+    ///
+    /// assert_eq!(ErrorMetric::Uniform.distance_rgb(gray, greener), ErrorMetric::Uniform.distance_rgb(gray, bluer));
+    /// assert!(ErrorMetric::Rec601.distance_rgb(gray, greener) > ErrorMetric::Rec601.distance_rgb(gray, bluer));
+    /// ```
+    pub fn distance_rgb(self, a: Pixel, b: Pixel) -> u32 {
+        if self == ErrorMetric::Oklab {
+            return oklab_distance(oklab(a), oklab(b))
+        }
+
+        let red_distance = a.red as i32 - b.red as i32;
+        let green_distance = a.green as i32 - b.green as i32;
+        let blue_distance = a.blue as i32 - b.blue as i32;
+
+        let [red_weight, green_weight, blue_weight] = self.channel_weights((a.red as u32 + b.red as u32) / 2);
+
+        (red_distance*red_distance) as u32 * red_weight
+            + (green_distance*green_distance) as u32 * green_weight
+            + (blue_distance*blue_distance) as u32 * blue_weight
+    }
+
+    /// Returns the weighted distance squared between two pixels, also including alpha as its own
+    /// channel.
+    ///
+    /// Alpha is weighted the same as an average color channel.
+    pub fn distance_argb(self, a: Pixel, b: Pixel) -> u32 {
+        let alpha_distance = a.alpha as i32 - b.alpha as i32;
+        (alpha_distance*alpha_distance) as u32 * self.alpha_weight() + self.distance_rgb(a, b)
+    }
+
+    /// Returns the weighted distance squared between two pixels, ignoring alpha, measured in linear
+    /// light if the pixels are sRGB.
+    ///
+    /// This is the same as [`distance_rgb`](Self::distance_rgb) for [`ColorSpace::Linear`].
+    pub(crate) fn distance_rgb_in(self, a: Pixel, b: Pixel, color_space: ColorSpace) -> u32 {
+        if color_space == ColorSpace::Linear || self == ErrorMetric::Oklab {
+            return self.distance_rgb(a, b)
+        }
+
+        let [red_weight, green_weight, blue_weight] = self.channel_weights((a.red as u32 + b.red as u32) / 2);
+        difference_squared(a.red, b.red, true) * red_weight
+            + difference_squared(a.green, b.green, true) * green_weight
+            + difference_squared(a.blue, b.blue, true) * blue_weight
+    }
+
+    /// Get the weights of red, green, and blue, given the average red of the colors being compared.
+    ///
+    /// Oklab isn't a weighted sum, so it uses the mean squared Oklab distance of a step in each
+    /// channel over all sRGB colors.
+    pub(crate) const fn channel_weights(self, mean_red: u32) -> [u32; 3] {
+        match self {
+            ErrorMetric::Uniform => [1, 1, 1],
+            ErrorMetric::Rec601 => [299, 587, 114],
+            ErrorMetric::Rec709 => [213, 715, 72],
+            ErrorMetric::Redmean => [512 + mean_red, 1024, 767 - mean_red],
+            ErrorMetric::Oklab => [36, 103, 29]
+        }
+    }
+
+    /// Get the weight of alpha (the average weight of a color channel).
+    pub(crate) const fn alpha_weight(self) -> u32 {
+        match self {
+            ErrorMetric::Uniform => 1,
+            ErrorMetric::Rec601 => 333,
+            ErrorMetric::Rec709 => 333,
+            ErrorMetric::Redmean => 768,
+            ErrorMetric::Oklab => 56
+        }
+    }
+}
+
+/// Scale of Oklab coordinates, where white has a lightness of 4096.
+const OKLAB_SCALE: f32 = 4096.0;
+
+/// Convert an sRGB pixel to Oklab lightness, green-red, and blue-yellow, scaled by
+/// [`OKLAB_SCALE`].
+pub(crate) fn oklab(pixel: Pixel) -> [i32; 3] {
+    let [red, green, blue] = [pixel.red, pixel.green, pixel.blue].map(srgb_to_linear);
+
+    let l = cbrt(0.412_221_47 * red + 0.536_332_54 * green + 0.051_445_99 * blue);
+    let m = cbrt(0.211_903_5 * red + 0.680_699_5 * green + 0.107_396_96 * blue);
+    let s = cbrt(0.088_302_46 * red + 0.281_718_85 * green + 0.629_978_7 * blue);
+
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s
+    ].map(|value| (value * OKLAB_SCALE + if value < 0.0 { -0.5 } else { 0.5 }) as i32)
+}
+
+/// Get the distance squared between two colors converted with [`oklab`].
+pub(crate) fn oklab_distance(a: [i32; 3], b: [i32; 3]) -> u32 {
+    a.iter().zip(b).map(|(a, b)| ((a - b) * (a - b)) as u32).sum()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,74 @@
+use super::oklab;
+use crate::{ErrorMetric, Pixel};
+
+const METRICS: [ErrorMetric; 5] = [ErrorMetric::Uniform, ErrorMetric::Rec601, ErrorMetric::Rec709, ErrorMetric::Redmean, ErrorMetric::Oklab];
+
+#[test]
+pub fn uniform_matches_euclidean() {
+    let a = Pixel { alpha: 10, red: 20, green: 30, blue: 40 };
+    let b = Pixel { alpha: 50, red: 25, green: 20, blue: 43 };
+    assert_eq!(ErrorMetric::Uniform.distance_rgb(a, b), 5*5 + 10*10 + 3*3);
+    assert_eq!(ErrorMetric::Uniform.distance_argb(a, b), 40*40 + 5*5 + 10*10 + 3*3);
+}
+
+#[test]
+pub fn distance_is_symmetric() {
+    let a = Pixel { alpha: 255, red: 250, green: 10, blue: 100 };
+    let b = Pixel { alpha: 0, red: 3, green: 200, blue: 255 };
+    for metric in METRICS {
+        assert_eq!(metric.distance_rgb(a, a), 0);
+        assert_eq!(metric.distance_argb(b, b), 0);
+        assert_eq!(metric.distance_rgb(a, b), metric.distance_rgb(b, a));
+        assert_eq!(metric.distance_argb(a, b), metric.distance_argb(b, a));
+    }
+}
+
+#[test]
+pub fn no_overflow() {
+    // All 16 pixels of a block at maximum distance still have to fit in a u32.
+    let black = Pixel { alpha: 0, red: 0, green: 0, blue: 0 };
+    let white = Pixel { alpha: 255, red: 255, green: 255, blue: 255 };
+    for metric in METRICS {
+        assert!(metric.distance_argb(black, white).checked_mul(16).is_some(), "{metric:?}");
+    }
+}
+
+#[test]
+pub fn redmean_weights_red_with_red() {
+    let dark = Pixel { alpha: 255, red: 0, green: 0, blue: 0 };
+    let red = Pixel { alpha: 255, red: 255, green: 0, blue: 0 };
+
+    // Blue differences matter less among reds.
+    let metric = ErrorMetric::Redmean;
+    let red_blue = Pixel { blue: 20, ..red };
+    let dark_blue = Pixel { blue: 20, ..dark };
+    assert!(metric.distance_rgb(red, red_blue) < metric.distance_rgb(dark, dark_blue));
+}
+
+#[test]
+pub fn oklab_known_values() {
+    assert_eq!([0, 0, 0], oklab(Pixel { alpha: 255, red: 0, green: 0, blue: 0 }));
+    assert_eq!([4096, 0, 0], oklab(Pixel { alpha: 255, red: 255, green: 255, blue: 255 }));
+
+    // Reference values of pure red, green, and blue, scaled by 4096
+    let expected = [[2572, 921, 515], [3549, -958, 735], [1851, -133, -1276]];
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]].map(|[red, green, blue]| Pixel { alpha: 255, red, green, blue });
+    for (color, expected) in colors.into_iter().zip(expected) {
+        let actual = oklab(color);
+        assert!(actual.iter().zip(expected).all(|(a, e)| a.abs_diff(e) <= 2), "{color:?}: {actual:?}");
+    }
+}
+
+#[test]
+pub fn oklab_is_perceptual() {
+    // The same step is more visible in dark colors than bright ones in sRGB.
+    let metric = ErrorMetric::Oklab;
+    let dark = Pixel { alpha: 255, red: 40, green: 40, blue: 40 };
+    let bright = Pixel { alpha: 255, red: 220, green: 220, blue: 220 };
+    let step = |p: Pixel| Pixel { red: p.red + 10, green: p.green + 10, blue: p.blue + 10, ..p };
+    assert!(metric.distance_rgb(dark, step(dark)) > metric.distance_rgb(bright, step(bright)));
+
+    // Green changes lightness more than blue.
+    let gray = Pixel { alpha: 255, red: 128, green: 128, blue: 128 };
+    assert!(metric.distance_rgb(gray, Pixel { green: 148, ..gray }) > metric.distance_rgb(gray, Pixel { blue: 148, ..gray }));
+}
@@ -0,0 +1,463 @@
+use crate::format::check_length;
+use crate::math::{bessel_i0, floor, sinc, sqrt};
+use crate::pixel::{linear_to_srgb, srgb_to_linear};
+use crate::{ColorSpace, Error, Pixel};
+
+mod fade;
+
+pub use self::fade::{FadeCurve, MipmapFade};
+
+/// Defines the filter used to downsample each mipmap from the one before it.
+///
+/// Filters are applied to each channel (including alpha) separately, and pixels past the edges of
+/// the image repeat the edge pixels.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum MipmapFilter {
+    /// Average the pixels covered by each pixel.
+    ///
+    /// This is the fastest filter, but it can alias and looks blocky when magnified.
+    #[default]
+    Box,
+
+    /// Weight pixels by distance with a tent filter, which is smoother than box at the cost of
+    /// some blurring.
+    Triangle,
+
+    /// Windowed sinc with a Kaiser window (radius 3, alpha 4).
+    ///
+    /// This keeps detail sharper than triangle, with less ringing around hard edges than Lanczos.
+    Kaiser,
+
+    /// Windowed sinc with a 3-lobe Lanczos window.
+    ///
+    /// This is the sharpest filter, but it rings the most around hard edges.
+    Lanczos
+}
+
+/// Alpha (shape) parameter of the Kaiser window.
+const KAISER_ALPHA: f32 = 4.0;
+
+impl MipmapFilter {
+    /// Distance from the center, in output pixels, past which the filter is zero.
+    fn radius(self) -> f32 {
+        match self {
+            MipmapFilter::Box => 0.5,
+            MipmapFilter::Triangle => 1.0,
+            MipmapFilter::Kaiser | MipmapFilter::Lanczos => 3.0
+        }
+    }
+
+    /// Weight of a pixel at a distance from the center, in output pixels.
+    fn weight(self, distance: f32) -> f32 {
+        let distance = distance.abs();
+        if distance > self.radius() {
+            return 0.0
+        }
+
+        match self {
+            // Pixels exactly on the edge are shared with the neighboring box.
+            MipmapFilter::Box if distance == 0.5 => 0.5,
+            MipmapFilter::Box => 1.0,
+            MipmapFilter::Triangle => 1.0 - distance,
+            MipmapFilter::Kaiser => {
+                let t = distance / 3.0;
+                sinc(distance) * bessel_i0(KAISER_ALPHA * sqrt(1.0 - t * t)) / bessel_i0(KAISER_ALPHA)
+            },
+            MipmapFilter::Lanczos => sinc(distance) * sinc(distance / 3.0)
+        }
+    }
+}
+
+/// Options for [`generate_mipmaps`].
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::{MipmapFilter, MipmapOptions};
+///
+/// let options = MipmapOptions {
+///     filter: MipmapFilter::Kaiser,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct MipmapOptions {
+    /// Filter used to downsample each mipmap.
+    pub filter: MipmapFilter,
+
+    /// Color space of the pixels.
+    ///
+    /// With [`ColorSpace::Srgb`], color is filtered in linear light, which keeps mipmaps from
+    /// getting darker where bright and dark pixels are averaged.
+    pub color_space: ColorSpace,
+
+    /// Alpha test cutoff to preserve coverage for.
+    ///
+    /// If set, the alpha of each mipmap is scaled so that the fraction of pixels with an alpha
+    /// above the cutoff is the same as the base image. This keeps alpha-tested textures (such as
+    /// foliage and fences) from thinning out in the distance, since averaging otherwise pulls
+    /// thin opaque areas under the cutoff. This should match the alpha test reference, or
+    /// [`EncodeOptions::alpha_threshold`](crate::EncodeOptions::alpha_threshold) for formats with
+    /// 1-bit alpha.
+    ///
+    /// The default is `None`.
+    pub alpha_coverage: Option<u8>,
+
+    /// Fade mipmaps toward a color, such as for detail maps.
+    ///
+    /// Each mipmap is still downsampled from the unfaded mipmap before it.
+    ///
+    /// The default is `None`.
+    pub fade: Option<MipmapFade>
+}
+
+/// Get the number of mipmaps (including the base image) down to 1x1.
+///
+/// Returns 0 if the width or height is 0.
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::mipmap_count;
+///
+/// assert_eq!(8, mipmap_count(128, 128));
+/// assert_eq!(3, mipmap_count(5, 2));
+/// assert_eq!(1, mipmap_count(1, 1));
+/// ```
+pub const fn mipmap_count(width: usize, height: usize) -> usize {
+    if width == 0 || height == 0 {
+        return 0
+    }
+    let largest = if width > height { width } else { height };
+    (usize::BITS - largest.leading_zeros()) as usize
+}
+
+/// Get the width and height of a mipmap, where level 0 is the base image.
+///
+/// Each level is half the size of the previous one (rounded down), but never less than 1.
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::mipmap_size;
+///
+/// assert_eq!((5, 2), mipmap_size(5, 2, 0));
+/// assert_eq!((2, 1), mipmap_size(5, 2, 1));
+/// assert_eq!((1, 1), mipmap_size(5, 2, 2));
+/// ```
+pub const fn mipmap_size(width: usize, height: usize, level: usize) -> (usize, usize) {
+    const fn halve(size: usize, level: usize) -> usize {
+        let size = if level < usize::BITS as usize { size >> level } else { 0 };
+        if size == 0 { 1 } else { size }
+    }
+    (halve(width, level), halve(height, level))
+}
+
+/// Get the total number of pixels of every mipmap (including the base image).
+///
+/// # Panics
+///
+/// This function will panic if the result exceeds [usize::MAX].
+pub const fn mipmap_pixel_count(width: usize, height: usize) -> usize {
+    match checked_mipmap_pixel_count(width, height) {
+        Ok(count) => count,
+        Err(_) => panic!("mipmap_pixel_count(): total pixels overflows usize")
+    }
+}
+
+/// Get the total number of pixels of every mipmap (including the base image).
+///
+/// Returns [`Error::SizeOverflow`] if the result exceeds [usize::MAX].
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::checked_mipmap_pixel_count;
+///
+/// assert_eq!(Ok(16 + 4 + 1), checked_mipmap_pixel_count(4, 4));
+/// assert_eq!(Ok(10 + 2 + 1), checked_mipmap_pixel_count(5, 2));
+/// ```
+pub const fn checked_mipmap_pixel_count(width: usize, height: usize) -> Result<usize, Error> {
+    let mut total = 0usize;
+    let mut level = 0;
+    while level < mipmap_count(width, height) {
+        let (level_width, level_height) = mipmap_size(width, height, level);
+        let Some(pixels) = level_width.checked_mul(level_height) else {
+            return Err(Error::SizeOverflow)
+        };
+        let Some(sum) = total.checked_add(pixels) else {
+            return Err(Error::SizeOverflow)
+        };
+        total = sum;
+        level += 1;
+    }
+    Ok(total)
+}
+
+/// Generate every mipmap of an image down to 1x1.
+///
+/// `to_pixels` receives each mipmap one after the other, starting with a copy of the base image,
+/// in the order given by [`mipmap_size`]. Each mipmap is downsampled from the one before it.
+///
+/// # Panics
+///
+/// This function will panic if:
+/// * `from_pixels.len() != width*height`
+/// * `to_pixels.len() != mipmap_pixel_count(width, height)`
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::{generate_mipmaps, mipmap_pixel_count, MipmapOptions, Pixel};
+///
+/// let black = Pixel { alpha: 255, red: 0, green: 0, blue: 0 };
+/// let white = Pixel { alpha: 255, red: 255, green: 255, blue: 255 };
+/// let image = [black, white, white, black];
+///
+/// let mut mipmaps = [Pixel::default(); mipmap_pixel_count(2, 2)];
+/// generate_mipmaps(&MipmapOptions::default(), &image, 2, 2, &mut mipmaps);
+///
+/// assert_eq!(image, mipmaps[..4]);
+/// assert_eq!(Pixel { alpha: 255, red: 128, green: 128, blue: 128 }, mipmaps[4]);
+/// ```
+pub fn generate_mipmaps(
+    options: &MipmapOptions,
+    from_pixels: &[Pixel],
+    width: usize,
+    height: usize,
+    to_pixels: &mut [Pixel]
+) {
+    assert_eq!(width*height, from_pixels.len());
+    assert_eq!(mipmap_pixel_count(width, height), to_pixels.len());
+
+    generate_mipmaps_unchecked(options, from_pixels, width, height, to_pixels)
+}
+
+/// Generate every mipmap of an image down to 1x1, returning an error instead of panicking if the
+/// buffers are the wrong size.
+///
+/// # Errors
+///
+/// * [`Error::SizeOverflow`] if `width*height` or the total pixels overflow [usize]
+/// * [`Error::InvalidInputLength`] if `from_pixels.len() != width*height`
+/// * [`Error::InvalidOutputLength`] if `to_pixels.len() != mipmap_pixel_count(width, height)`
+pub fn try_generate_mipmaps(
+    options: &MipmapOptions,
+    from_pixels: &[Pixel],
+    width: usize,
+    height: usize,
+    to_pixels: &mut [Pixel]
+) -> Result<(), Error> {
+    check_length(width.checked_mul(height).ok_or(Error::SizeOverflow)?, from_pixels.len(), true)?;
+    check_length(checked_mipmap_pixel_count(width, height)?, to_pixels.len(), false)?;
+
+    generate_mipmaps_unchecked(options, from_pixels, width, height, to_pixels);
+    Ok(())
+}
+
+fn generate_mipmaps_unchecked(
+    options: &MipmapOptions,
+    from_pixels: &[Pixel],
+    width: usize,
+    height: usize,
+    to_pixels: &mut [Pixel]
+) {
+    if mipmap_count(width, height) == 0 {
+        return
+    }
+    to_pixels[..from_pixels.len()].copy_from_slice(from_pixels);
+
+    let coverage = options.alpha_coverage.map(|cutoff| {
+        let covered = from_pixels.iter().filter(|p| p.alpha > cutoff).count();
+        (cutoff, covered as f64 / from_pixels.len() as f64)
+    });
+
+    let mut offset = 0;
+    for level in 1..mipmap_count(width, height) {
+        let (from_width, from_height) = mipmap_size(width, height, level - 1);
+        let (to_width, to_height) = mipmap_size(width, height, level);
+        let next = offset + from_width * from_height;
+
+        let (previous, rest) = to_pixels.split_at_mut(next);
+        downsample(
+            options,
+            &previous[offset..],
+            from_width,
+            from_height,
+            &mut rest[..to_width * to_height],
+            to_width,
+            to_height
+        );
+        if let Some((cutoff, coverage)) = coverage {
+            let pixels = &mut rest[..to_width * to_height];
+            let target = (coverage * pixels.len() as f64 + 0.5) as usize;
+            scale_alpha_coverage(pixels, cutoff, target);
+        }
+        offset = next;
+    }
+
+    // This is done last so that fading doesn't carry over into the next mipmap.
+    if let Some(fade) = options.fade {
+        let mut offset = 0;
+        for level in 0..mipmap_count(width, height) {
+            let (level_width, level_height) = mipmap_size(width, height, level);
+            let pixels = &mut to_pixels[offset..][..level_width * level_height];
+            let amount = fade.amount(level, mipmap_count(width, height));
+            if amount > 0.0 {
+                fade.apply(pixels, amount);
+            }
+            offset += pixels.len();
+        }
+    }
+}
+
+/// Scale the alpha of the pixels so that as close to `target` pixels as possible have an alpha
+/// above `cutoff`.
+fn scale_alpha_coverage(pixels: &mut [Pixel], cutoff: u8, target: usize) {
+    let mut histogram = [0usize; 256];
+    for pixel in pixels.iter() {
+        histogram[pixel.alpha as usize] += 1;
+    }
+
+    // Find the alpha that should become the new cutoff, preferring the one closest to the current
+    // cutoff (i.e. the smallest change).
+    let mut best = (usize::MAX, usize::MAX, cutoff);
+    let mut above = 0usize;
+    for alpha in (0..=255u8).rev() {
+        let difference = above.abs_diff(target);
+        let candidate = (difference, alpha.abs_diff(cutoff) as usize, alpha);
+        if candidate < best {
+            best = candidate;
+        }
+        above += histogram[alpha as usize];
+    }
+
+    let new_cutoff = best.2;
+    if new_cutoff == cutoff {
+        return
+    }
+
+    // Alpha at or below the new cutoff rounds to the cutoff or less, and alpha above it rounds to
+    // above the cutoff.
+    let scale = (cutoff as f32 + 0.5) / (new_cutoff as f32 + 0.5);
+    for pixel in pixels.iter_mut() {
+        pixel.alpha = (pixel.alpha as f32 * scale + 0.5).clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Most source pixels a filter can cover in one direction.
+///
+/// Each level is at most 3 times smaller than the last (3 to 1), so the widest filter (radius 3)
+/// covers up to 18 pixels, plus up to one on each side from rounding.
+const MAX_TAPS: usize = 24;
+
+/// Columns of filter taps calculated at once when downsampling.
+const COLUMN_CHUNK: usize = 64;
+
+/// Source pixels and normalized weights contributing to an output pixel in one direction.
+#[derive(Copy, Clone)]
+struct Taps {
+    count: usize,
+    indices: [usize; MAX_TAPS],
+    weights: [f32; MAX_TAPS]
+}
+
+impl Taps {
+    fn new(filter: MipmapFilter, to_index: usize, from_size: usize, to_size: usize) -> Self {
+        let scale = from_size as f32 / to_size as f32;
+        let center = (to_index as f32 + 0.5) * scale;
+        let support = filter.radius() * scale;
+
+        let mut taps = Taps { count: 0, indices: [0; MAX_TAPS], weights: [0.0; MAX_TAPS] };
+        let first = floor(center - support - 0.5) as isize;
+        let last = floor(center + support - 0.5) as isize + 1;
+        let mut total = 0.0;
+        for index in first..=last {
+            let weight = filter.weight((index as f32 + 0.5 - center) / scale);
+            if weight == 0.0 {
+                continue
+            }
+            taps.indices[taps.count] = index.clamp(0, from_size as isize - 1) as usize;
+            taps.weights[taps.count] = weight;
+            taps.count += 1;
+            total += weight;
+        }
+
+        for weight in &mut taps.weights[..taps.count] {
+            *weight /= total;
+        }
+        taps
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.indices[..self.count].iter().copied().zip(self.weights[..self.count].iter().copied())
+    }
+}
+
+/// Downsample an image with a filter.
+fn downsample(
+    options: &MipmapOptions,
+    from_pixels: &[Pixel],
+    from_width: usize,
+    from_height: usize,
+    to_pixels: &mut [Pixel],
+    to_width: usize,
+    to_height: usize
+) {
+    let filter = options.filter;
+
+    // Channels are filtered from 0.0 to 1.0, and color is converted to and from linear light if
+    // needed.
+    let load_color: fn(u8) -> f32 = match options.color_space {
+        ColorSpace::Linear => load_linear,
+        ColorSpace::Srgb => srgb_to_linear
+    };
+    let store_color: fn(f32) -> u8 = match options.color_space {
+        ColorSpace::Linear => store_linear,
+        ColorSpace::Srgb => linear_to_srgb
+    };
+
+    for chunk_start in (0..to_width).step_by(COLUMN_CHUNK) {
+        let chunk_width = (to_width - chunk_start).min(COLUMN_CHUNK);
+        let columns: [Taps; COLUMN_CHUNK] = core::array::from_fn(|x| {
+            Taps::new(filter, (chunk_start + x).min(to_width - 1), from_width, to_width)
+        });
+
+        for y in 0..to_height {
+            let rows = Taps::new(filter, y, from_height, to_height);
+            for (x, columns) in columns[..chunk_width].iter().enumerate() {
+                let mut sum = [0.0f32; 4];
+                for (row, row_weight) in rows.iter() {
+                    let from_row = &from_pixels[row * from_width..][..from_width];
+                    for (column, column_weight) in columns.iter() {
+                        let pixel = from_row[column];
+                        let weight = row_weight * column_weight;
+                        sum[0] += load_linear(pixel.alpha) * weight;
+                        sum[1] += load_color(pixel.red) * weight;
+                        sum[2] += load_color(pixel.green) * weight;
+                        sum[3] += load_color(pixel.blue) * weight;
+                    }
+                }
+
+                // Sinc filters can overshoot, so this is clamped.
+                to_pixels[y * to_width + chunk_start + x] = Pixel {
+                    alpha: store_linear(sum[0]),
+                    red: store_color(sum[1]),
+                    green: store_color(sum[2]),
+                    blue: store_color(sum[3])
+                };
+            }
+        }
+    }
+}
+
+fn load_linear(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+fn store_linear(value: f32) -> u8 {
+    (value * 255.0 + 0.5).clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests;
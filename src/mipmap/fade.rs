@@ -0,0 +1,102 @@
+use crate::Pixel;
+
+/// Fades mipmaps toward a color over the mipmap chain.
+///
+/// This works like the detail fade factor of Halo CE bitmaps, where detail maps fade to mid-gray
+/// (which doesn't change the base map) as they get further away.
+///
+/// # Examples
+///
+/// ```rust
+/// use macaroni_tex::{FadeCurve, MipmapFade, MipmapOptions};
+///
+/// let options = MipmapOptions {
+///     fade: Some(MipmapFade { factor: 0.5, curve: FadeCurve::Smooth, ..Default::default() }),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MipmapFade {
+    /// Color that mipmaps fade toward.
+    ///
+    /// The default is mid-gray (128) with full alpha.
+    pub target: Pixel,
+
+    /// Fade alpha toward the target's alpha too.
+    ///
+    /// The default is `false`.
+    pub fade_alpha: bool,
+
+    /// How soon the target is reached, from 0.0 (at the last mipmap) to 1.0 (at the first mipmap
+    /// after the base image). Values out of range are clamped.
+    ///
+    /// The default is 0.0.
+    pub factor: f32,
+
+    /// How the fade progresses between the base image and the mipmap where the target is reached.
+    pub curve: FadeCurve
+}
+
+impl Default for MipmapFade {
+    fn default() -> Self {
+        Self {
+            target: Pixel { alpha: 255, red: 128, green: 128, blue: 128 },
+            fade_alpha: false,
+            factor: 0.0,
+            curve: FadeCurve::default()
+        }
+    }
+}
+
+/// Defines how a [`MipmapFade`] progresses over the mipmap chain.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum FadeCurve {
+    /// Fade evenly.
+    #[default]
+    Linear,
+
+    /// Fade slowly at first and faster toward the end, which keeps more detail up close.
+    EaseIn,
+
+    /// Fade quickly at first and slower toward the end.
+    EaseOut,
+
+    /// Fade slowly at both ends (smoothstep).
+    Smooth
+}
+
+impl FadeCurve {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            FadeCurve::Linear => t,
+            FadeCurve::EaseIn => t * t,
+            FadeCurve::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            FadeCurve::Smooth => t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl MipmapFade {
+    /// Get how much a mipmap is faded, from 0.0 (not at all) to 1.0 (all the way to the target).
+    pub(super) fn amount(&self, level: usize, mipmap_count: usize) -> f32 {
+        let Some(last) = mipmap_count.checked_sub(1).filter(|last| *last > 0) else {
+            return 0.0
+        };
+
+        let end = last as f32 - self.factor.clamp(0.0, 1.0) * (last - 1) as f32;
+        self.curve.apply((level as f32 / end).min(1.0))
+    }
+
+    /// Fade pixels toward the target by an amount.
+    pub(super) fn apply(&self, pixels: &mut [Pixel], amount: f32) {
+        let blend = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount + 0.5) as u8;
+        for pixel in pixels {
+            pixel.red = blend(pixel.red, self.target.red);
+            pixel.green = blend(pixel.green, self.target.green);
+            pixel.blue = blend(pixel.blue, self.target.blue);
+            if self.fade_alpha {
+                pixel.alpha = blend(pixel.alpha, self.target.alpha);
+            }
+        }
+    }
+}
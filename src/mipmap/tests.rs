@@ -0,0 +1,211 @@
+use crate::{checked_mipmap_pixel_count, generate_mipmaps, mipmap_count, mipmap_pixel_count, mipmap_size, try_generate_mipmaps};
+use crate::{ColorSpace, Error, FadeCurve, MipmapFade, MipmapFilter, MipmapOptions, Pixel};
+
+const FILTERS: [MipmapFilter; 4] = [MipmapFilter::Box, MipmapFilter::Triangle, MipmapFilter::Kaiser, MipmapFilter::Lanczos];
+
+#[test]
+pub fn counts_and_sizes() {
+    assert_eq!(0, mipmap_count(0, 16));
+    assert_eq!(1, mipmap_count(1, 1));
+    assert_eq!(5, mipmap_count(16, 1));
+    assert_eq!(5, mipmap_count(17, 31));
+    assert_eq!(6, mipmap_count(17, 32));
+
+    let sizes: [_; 5] = core::array::from_fn(|level| mipmap_size(17, 3, level));
+    assert_eq!([(17, 3), (8, 1), (4, 1), (2, 1), (1, 1)], sizes);
+    assert_eq!((1, 1), mipmap_size(usize::MAX, usize::MAX, usize::MAX));
+
+    assert_eq!(51 + 8 + 4 + 2 + 1, mipmap_pixel_count(17, 3));
+    assert_eq!(0, mipmap_pixel_count(0, 0));
+    assert_eq!(Err(Error::SizeOverflow), checked_mipmap_pixel_count(usize::MAX, 2));
+}
+
+#[test]
+pub fn invalid_lengths() {
+    let options = MipmapOptions::default();
+    let mut output = [Pixel::default(); 16];
+    assert_eq!(
+        Err(Error::InvalidInputLength { expected: 12, actual: 11 }),
+        try_generate_mipmaps(&options, &[Pixel::default(); 11], 4, 3, &mut output[..16])
+    );
+    assert_eq!(
+        Err(Error::InvalidOutputLength { expected: 15, actual: 16 }),
+        try_generate_mipmaps(&options, &[Pixel::default(); 12], 4, 3, &mut output[..16])
+    );
+    assert_eq!(Ok(()), try_generate_mipmaps(&options, &[Pixel::default(); 12], 4, 3, &mut output[..15]));
+}
+
+#[test]
+pub fn solid_color_is_kept() {
+    let color = Pixel { alpha: 77, red: 255, green: 3, blue: 140 };
+    for (width, height) in [(7, 3), (5, 5), (1, 9), (16, 16)] {
+        let image = [color; 256];
+        let mut output = [Pixel::default(); 512];
+        let count = mipmap_pixel_count(width, height);
+        for filter in FILTERS {
+            generate_mipmaps(&MipmapOptions { filter, ..Default::default() }, &image[..width * height], width, height, &mut output[..count]);
+            assert!(output[..count].iter().all(|p| *p == color), "{filter:?} {width}x{height}");
+        }
+    }
+}
+
+#[test]
+pub fn box_averages_pixels() {
+    let gray = |value: u8| Pixel { alpha: 255, red: value, green: value, blue: value };
+    let image = [
+        gray(0), gray(10), gray(100), gray(100),
+        gray(20), gray(30), gray(100), gray(100)
+    ];
+    let mut output = [Pixel::default(); 8 + 2 + 1];
+    generate_mipmaps(&MipmapOptions { filter: MipmapFilter::Box, ..Default::default() }, &image, 4, 2, &mut output);
+    assert_eq!(image, output[..8]);
+    assert_eq!([gray(15), gray(100)], output[8..10]);
+    assert_eq!(gray(58), output[10]);
+
+    // 3 to 1 covers all 3 pixels evenly.
+    let mut output = [Pixel::default(); 3 + 1];
+    generate_mipmaps(&MipmapOptions { filter: MipmapFilter::Box, ..Default::default() }, &[gray(0), gray(30), gray(90)], 3, 1, &mut output);
+    assert_eq!(gray(40), output[3]);
+}
+
+#[test]
+pub fn gradient_is_kept() {
+    // Every filter is symmetric, so a linear gradient stays linear away from the edges.
+    let mut image = [Pixel::default(); 64];
+    for (x, pixel) in image.iter_mut().enumerate() {
+        let value = (x * 4 + 2) as u8;
+        *pixel = Pixel { alpha: 255, red: value, green: value, blue: 255 - value };
+    }
+
+    let mut output = [Pixel::default(); 64 + 32 + 16 + 8 + 4 + 2 + 1];
+    for filter in FILTERS {
+        generate_mipmaps(&MipmapOptions { filter, ..Default::default() }, &image, 64, 1, &mut output);
+        for (x, pixel) in output[64..96].iter().enumerate().skip(4).take(24) {
+            let expected = (x * 8 + 4) as i32;
+            assert!((pixel.red as i32 - expected).abs() <= 1, "{filter:?} at {x}: {pixel:?}");
+            assert!((pixel.blue as i32 - (255 - expected)).abs() <= 1, "{filter:?} at {x}: {pixel:?}");
+        }
+    }
+}
+
+#[test]
+pub fn sharper_filters_keep_more_contrast() {
+    // Alternating 4 pixel stripes become 2 pixel stripes, which blurrier filters flatten more.
+    let mut image = [Pixel::default(); 32 * 32];
+    for (i, pixel) in image.iter_mut().enumerate() {
+        let value = if (i % 32) / 4 % 2 == 0 { 0 } else { 255 };
+        *pixel = Pixel { alpha: 255, red: value, green: value, blue: value };
+    }
+
+    let mut output = [Pixel::default(); 32 * 32 + 16 * 16 + 8 * 8 + 4 * 4 + 2 * 2 + 1];
+    let contrast = |filter, output: &mut [Pixel]| {
+        generate_mipmaps(&MipmapOptions { filter, ..Default::default() }, &image, 32, 32, output);
+        let row = &output[32 * 32 + 16 * 8..][..16];
+        row[4..12].iter().map(|p| p.green).max().unwrap() - row[4..12].iter().map(|p| p.green).min().unwrap()
+    };
+
+    let triangle = contrast(MipmapFilter::Triangle, &mut output);
+    let kaiser = contrast(MipmapFilter::Kaiser, &mut output);
+    let lanczos = contrast(MipmapFilter::Lanczos, &mut output);
+    assert!(triangle < kaiser, "{triangle} {kaiser}");
+    assert!(kaiser <= lanczos, "{kaiser} {lanczos}");
+}
+
+#[test]
+pub fn srgb_is_filtered_in_linear_light() {
+    let black = Pixel { alpha: 0, red: 0, green: 0, blue: 0 };
+    let white = Pixel { alpha: 255, red: 255, green: 255, blue: 255 };
+    let image = [black, white, white, black];
+    let mut output = [Pixel::default(); 4 + 1];
+
+    generate_mipmaps(&MipmapOptions::default(), &image, 2, 2, &mut output);
+    assert_eq!(Pixel { alpha: 128, red: 128, green: 128, blue: 128 }, output[4]);
+
+    // Half of white in linear light is much brighter in sRGB, but alpha is still linear.
+    let options = MipmapOptions { color_space: ColorSpace::Srgb, ..Default::default() };
+    generate_mipmaps(&options, &image, 2, 2, &mut output);
+    assert_eq!(Pixel { alpha: 128, red: 188, green: 188, blue: 188 }, output[4]);
+}
+
+#[test]
+pub fn alpha_coverage_is_kept() {
+    // Noisy alpha where few pixels pass the cutoff, and averaging makes even fewer pass.
+    let mut image = [Pixel::default(); 64 * 64];
+    let mut state = 0x2545_F491u32;
+    for pixel in image.iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *pixel = Pixel { alpha: (state >> 24) as u8, red: 40, green: 120, blue: 30 };
+    }
+    let cutoff = 200;
+    let coverage = |pixels: &[Pixel]| pixels.iter().filter(|p| p.alpha > cutoff).count() as f64 / pixels.len() as f64;
+    let base_coverage = coverage(&image);
+
+    let mut output = [Pixel::default(); mipmap_pixel_count(64, 64)];
+    let level_coverage = |output: &[Pixel], level: usize| {
+        let offset: usize = (0..level).map(|l| mipmap_size(64, 64, l)).map(|(w, h)| w * h).sum();
+        let (width, height) = mipmap_size(64, 64, level);
+        coverage(&output[offset..][..width * height])
+    };
+
+    generate_mipmaps(&MipmapOptions::default(), &image, 64, 64, &mut output);
+    assert!(level_coverage(&output, 2) < base_coverage / 2.0);
+
+    let options = MipmapOptions { alpha_coverage: Some(cutoff), ..Default::default() };
+    generate_mipmaps(&options, &image, 64, 64, &mut output);
+    assert_eq!(image, output[..64 * 64]);
+    for level in 1..mipmap_count(64, 64) {
+        // Pixels with the same alpha can't be split, so this may be off by a little, or by a pixel
+        // for small mipmaps.
+        let (width, height) = mipmap_size(64, 64, level);
+        let difference = (level_coverage(&output, level) - base_coverage).abs();
+        assert!(
+            difference <= 0.01 || difference * (width * height) as f64 <= 1.0,
+            "level {level}: {} vs {base_coverage}",
+            level_coverage(&output, level)
+        );
+    }
+
+    // Color isn't touched.
+    assert!(output.iter().all(|p| (p.red, p.green, p.blue) == (40, 120, 30)));
+}
+
+#[test]
+pub fn fade_to_gray() {
+    let color = Pixel { alpha: 0, red: 255, green: 0, blue: 64 };
+    let gray = Pixel { alpha: 255, red: 128, green: 128, blue: 128 };
+    let image = [color; 16 * 16];
+    let mut output = [Pixel::default(); mipmap_pixel_count(16, 16)];
+    let offsets = [0, 256, 256 + 64, 256 + 64 + 16, 256 + 64 + 16 + 4];
+
+    // Without a factor, the last mipmap is the target, and the rest fade evenly toward it.
+    let fade = MipmapFade::default();
+    generate_mipmaps(&MipmapOptions { fade: Some(fade), ..Default::default() }, &image, 16, 16, &mut output);
+    assert_eq!(color, output[0]);
+    assert_eq!(Pixel { alpha: 0, red: 223, green: 32, blue: 80 }, output[offsets[1]]);
+    assert_eq!(Pixel { alpha: 0, red: 192, green: 64, blue: 96 }, output[offsets[2]]);
+    assert_eq!(Pixel { alpha: 0, ..gray }, output[offsets[4]]);
+
+    // Each mipmap is faded from the unfaded one before it, so a mipmap is the same color throughout.
+    for level in 1..5 {
+        let end = offsets.get(level + 1).copied().unwrap_or(output.len());
+        assert!(output[offsets[level]..end].iter().all(|p| *p == output[offsets[level]]));
+    }
+
+    // With a factor of 1, the first mipmap is the target.
+    let fade = MipmapFade { factor: 1.0, fade_alpha: true, ..Default::default() };
+    generate_mipmaps(&MipmapOptions { fade: Some(fade), ..Default::default() }, &image, 16, 16, &mut output);
+    assert_eq!(color, output[0]);
+    assert!(output[offsets[1]..].iter().all(|p| *p == gray));
+
+    // Curves change how far along the way each mipmap is.
+    let amounts = |curve| {
+        let fade = MipmapFade { curve, ..Default::default() };
+        core::array::from_fn::<f32, 5, _>(|level| fade.amount(level, 5))
+    };
+    assert_eq!([0.0, 0.25, 0.5, 0.75, 1.0], amounts(FadeCurve::Linear));
+    assert_eq!([0.0, 0.0625, 0.25, 0.5625, 1.0], amounts(FadeCurve::EaseIn));
+    assert_eq!([0.0, 0.4375, 0.75, 0.9375, 1.0], amounts(FadeCurve::EaseOut));
+    assert_eq!([0.0, 0.15625, 0.5, 0.84375, 1.0], amounts(FadeCurve::Smooth));
+}
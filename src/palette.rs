@@ -0,0 +1,285 @@
+use crate::format::PaletteLookup;
+use crate::{EncodeOptions, Pixel};
+
+/// Defines a method for generating a 256-color palette from an image.
+///
+/// Alpha is clustered as its own channel. Fully transparent pixels are all treated as transparent
+/// black, since their color isn't visible.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum Quantizer {
+    /// Repeatedly split the boxes of colors with the most error at the median of their widest
+    /// channel.
+    ///
+    /// This is fast and deterministic.
+    #[default]
+    MedianCut,
+
+    /// Start with a median cut palette and refine it with k-means clustering, moving each color to
+    /// the mean of the pixels closest to it.
+    ///
+    /// This is slower, but usually has less error.
+    KMeans
+}
+
+impl Quantizer {
+    /// Generate a palette from an image, ready to be used with [`Format::P8`](crate::Format::P8).
+    ///
+    /// If the image has fewer than 256 distinct colors, each color is included exactly and the
+    /// remaining entries repeat the last color. If the image is empty, the palette is all
+    /// transparent black.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::{Format, Pixel, Quantizer};
+    ///
+    /// let red = Pixel { alpha: 255, red: 255, green: 0, blue: 0 };
+    /// let blue = Pixel { alpha: 255, red: 0, green: 0, blue: 255 };
+    /// let image = [red, blue, blue, red];
+    ///
+    /// let palette = Quantizer::MedianCut.generate_palette(&image);
+    /// assert!(palette.contains(&red) && palette.contains(&blue));
+    ///
+    /// let mut indices = [0u8; 4];
+    /// Format::P8(&palette).encode_pixels(&image, &mut indices, 2, 2);
+    /// assert_eq!(palette[indices[0] as usize], red);
+    /// ```
+    pub fn generate_palette(self, pixels: &[Pixel]) -> [Pixel; 256] {
+        let (mut palette, count) = median_cut(pixels);
+        if count == 0 {
+            return palette
+        }
+
+        if self == Quantizer::KMeans {
+            k_means(pixels, &mut palette[..count]);
+        }
+
+        let last = palette[count - 1];
+        palette[count..].fill(last);
+        palette
+    }
+}
+
+/// Number of k-means passes to do at most (it stops early if no colors move).
+const K_MEANS_ITERATIONS: usize = 16;
+
+/// Get the color that a pixel is clustered as ([alpha, red, green, blue]).
+const fn cluster_key(pixel: &Pixel) -> [u8; 4] {
+    if pixel.alpha == 0 {
+        [0; 4]
+    }
+    else {
+        [pixel.alpha, pixel.red, pixel.green, pixel.blue]
+    }
+}
+
+/// Get the rounded mean color from channel sums.
+fn mean_pixel(sum: &[u64; 4], count: u64) -> Pixel {
+    let [alpha, red, green, blue] = sum.map(|sum| ((sum + count / 2) / count) as u8);
+    Pixel { alpha, red, green, blue }
+}
+
+/// A box in color space containing some of the pixels, shrunk to fit them.
+#[derive(Copy, Clone)]
+struct ColorBox {
+    min: [u8; 4],
+    max: [u8; 4],
+    count: u64,
+    sum: [u64; 4]
+}
+
+impl ColorBox {
+    const EMPTY: ColorBox = ColorBox { min: [u8::MAX; 4], max: [0; 4], count: 0, sum: [0; 4] };
+
+    fn add(&mut self, key: &[u8; 4]) {
+        for (channel, &value) in key.iter().enumerate() {
+            self.min[channel] = self.min[channel].min(value);
+            self.max[channel] = self.max[channel].max(value);
+            self.sum[channel] += value as u64;
+        }
+        self.count += 1;
+    }
+
+    /// Get the channel with the greatest range along with the range.
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..4)
+            .map(|channel| (channel, self.max[channel] - self.min[channel]))
+            .max_by_key(|(_, range)| *range)
+            .unwrap()
+    }
+
+    /// Estimate how much splitting the box would reduce the error.
+    ///
+    /// This is zero if the box only contains one color and can't be split.
+    fn priority(&self) -> u64 {
+        let (_, range) = self.widest_channel();
+        self.count * (range as u64).pow(2)
+    }
+}
+
+/// Histogram entries shared by the boxes split in one pass over the pixels, with one entry for each
+/// value in the range of the channel a box is split on.
+const HISTOGRAM_SIZE: usize = 4096;
+
+/// A node of the tree of splits, used to find which box a pixel is in.
+#[derive(Copy, Clone)]
+enum Node {
+    /// A box that hasn't been split, by its index.
+    Leaf(u8),
+
+    /// A box split in two, with the pixels with `channel` at most `median` going to `lower`.
+    Split { channel: u8, median: u8, lower: u16, upper: u16 }
+}
+
+/// Find the box a pixel is in.
+fn find_box(nodes: &[Node], key: &[u8; 4]) -> usize {
+    let mut node = 0;
+    loop {
+        match nodes[node] {
+            Node::Leaf(index) => return index as usize,
+            Node::Split { channel, median, lower, upper } => {
+                node = if key[channel as usize] <= median { lower } else { upper } as usize;
+            }
+        }
+    }
+}
+
+/// Generate up to 256 colors with median cut, returning the palette and how many were generated.
+fn median_cut(pixels: &[Pixel]) -> ([Pixel; 256], usize) {
+    let mut palette = [Pixel::default(); 256];
+
+    let mut boxes = [ColorBox::EMPTY; 256];
+    for pixel in pixels {
+        boxes[0].add(&cluster_key(pixel));
+    }
+    if boxes[0].count == 0 {
+        return (palette, 0)
+    }
+
+    // Boxes are split in passes of the boxes with the highest priority whose histograms fit, and no
+    // more boxes than there are, so the first passes split each box in two. Each pass goes over the
+    // pixels once to find the medians and once to fill the new boxes.
+    let mut nodes = [Node::Leaf(0); 511];
+    let mut box_nodes = [0usize; 256];
+    let mut node_count = 1;
+    let mut count = 1;
+    while count < boxes.len() {
+        let limit = count.min(boxes.len() - count);
+        let mut slots = [None; 256];
+        let mut splitting = [(0usize, 0usize, 0usize); 128];
+        let mut split_count = 0;
+        let mut histogram_length = 0;
+        while split_count < limit {
+            let best = (0..count)
+                .filter(|index| slots[*index].is_none())
+                .map(|index| (index, boxes[index].priority()))
+                .max_by_key(|(_, priority)| *priority);
+            let Some((index, _)) = best.filter(|(_, priority)| *priority > 0) else {
+                break
+            };
+            let (channel, range) = boxes[index].widest_channel();
+            if histogram_length + range as usize + 1 > HISTOGRAM_SIZE {
+                break
+            }
+            slots[index] = Some(split_count);
+            splitting[split_count] = (index, channel, histogram_length);
+            split_count += 1;
+            histogram_length += range as usize + 1;
+        }
+        if split_count == 0 {
+            break
+        }
+
+        let mut histograms = [0u32; HISTOGRAM_SIZE];
+        for key in pixels.iter().map(cluster_key) {
+            let index = find_box(&nodes[..node_count], &key);
+            if let Some(slot) = slots[index] {
+                let (_, channel, offset) = splitting[slot];
+                let value = &mut histograms[offset + (key[channel] - boxes[index].min[channel]) as usize];
+                *value = value.saturating_add(1);
+            }
+        }
+
+        let mut changed = [false; 256];
+        for &(index, channel, offset) in &splitting[..split_count] {
+            let color_box = &boxes[index];
+            let histogram = &histograms[offset..][..=(color_box.max[channel] - color_box.min[channel]) as usize];
+
+            // Both halves must be non-empty, so the lower half can't include the maximum.
+            let mut median = color_box.min[channel];
+            let mut total = 0;
+            for (value, &value_count) in histogram.iter().enumerate() {
+                total += value_count as u64;
+                median = color_box.min[channel] + value as u8;
+                if total * 2 >= color_box.count {
+                    break
+                }
+            }
+            let median = median.min(color_box.max[channel] - 1);
+
+            let (lower, upper) = (node_count, node_count + 1);
+            nodes[box_nodes[index]] = Node::Split { channel: channel as u8, median, lower: lower as u16, upper: upper as u16 };
+            nodes[lower] = Node::Leaf(index as u8);
+            nodes[upper] = Node::Leaf(count as u8);
+            (box_nodes[index], box_nodes[count]) = (lower, upper);
+            node_count += 2;
+
+            boxes[index] = ColorBox::EMPTY;
+            changed[index] = true;
+            changed[count] = true;
+            count += 1;
+        }
+
+        for key in pixels.iter().map(cluster_key) {
+            let index = find_box(&nodes[..node_count], &key);
+            if changed[index] {
+                boxes[index].add(&key);
+            }
+        }
+    }
+
+    for (entry, color_box) in palette.iter_mut().zip(&boxes[..count]) {
+        *entry = mean_pixel(&color_box.sum, color_box.count);
+    }
+    (palette, count)
+}
+
+/// Refine a palette by moving each color to the mean of the pixels closest to it.
+///
+/// Pixels are matched to colors the same way as when encoding to [`Format::P8`](crate::Format::P8)
+/// with the default options. Colors that no pixels are closest to are left alone.
+fn k_means(pixels: &[Pixel], palette: &mut [Pixel]) {
+    for _ in 0..K_MEANS_ITERATIONS {
+        let mut sums = [[0u64; 4]; 256];
+        let mut counts = [0u64; 256];
+        let mut entries = [Pixel::default(); 256];
+        entries[..palette.len()].copy_from_slice(palette);
+        let mut lookup = PaletteLookup::new(&entries[..palette.len()], &EncodeOptions::default());
+
+        for key in pixels.iter().map(cluster_key) {
+            let [alpha, red, green, blue] = key;
+            let nearest = lookup.find(Pixel { alpha, red, green, blue });
+            for channel in 0..4 {
+                sums[nearest][channel] += key[channel] as u64;
+            }
+            counts[nearest] += 1;
+        }
+
+        let mut changed = false;
+        for (entry, (sum, &count)) in palette.iter_mut().zip(sums.iter().zip(&counts)) {
+            if count == 0 {
+                continue
+            }
+            let mean = mean_pixel(sum, count);
+            changed |= mean != *entry;
+            *entry = mean;
+        }
+
+        if !changed {
+            break
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
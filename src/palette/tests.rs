@@ -0,0 +1,69 @@
+use crate::format::tests::open_ringhopper_image;
+use crate::{Format, Pixel, Quantizer};
+
+/// Sum of squared ARGB error after palettizing an image with a palette.
+fn palettized_error(pixels: &[Pixel], palette: [Pixel; 256]) -> u64 {
+    let mut indices = [0u8; 128*128];
+    let mut decoded = [Pixel::default(); 128*128];
+    let indices = &mut indices[..pixels.len()];
+    let decoded = &mut decoded[..pixels.len()];
+    Format::P8(&palette).encode_pixels(pixels, indices, pixels.len(), 1);
+    Format::P8(&palette).decode_pixels(indices, decoded, pixels.len(), 1);
+
+    pixels.iter().zip(decoded.iter()).map(|(a, b)| {
+        [(a.alpha, b.alpha), (a.red, b.red), (a.green, b.green), (a.blue, b.blue)]
+            .iter()
+            .map(|&(a, b)| (a as i64 - b as i64).pow(2) as u64)
+            .sum::<u64>()
+    }).sum()
+}
+
+#[test]
+pub fn empty_image() {
+    for quantizer in [Quantizer::MedianCut, Quantizer::KMeans] {
+        assert_eq!([Pixel::default(); 256], quantizer.generate_palette(&[]));
+    }
+}
+
+#[test]
+pub fn few_colors_are_exact() {
+    // Fewer than 256 colors should all end up in the palette exactly.
+    let mut pixels = [Pixel::default(); 200];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let i = i as u8;
+        *pixel = Pixel { alpha: 255 - (i % 3) * 100, red: i, green: i.wrapping_mul(37), blue: 255 - i };
+    }
+
+    for quantizer in [Quantizer::MedianCut, Quantizer::KMeans] {
+        let palette = quantizer.generate_palette(&pixels);
+        for pixel in &pixels {
+            assert!(palette.contains(pixel), "{quantizer:?} is missing {pixel:?}");
+        }
+        assert_eq!(0, palettized_error(&pixels, palette));
+    }
+}
+
+#[test]
+pub fn transparent_pixels_are_black() {
+    let pixels = [
+        Pixel { alpha: 0, red: 255, green: 0, blue: 0 },
+        Pixel { alpha: 0, red: 0, green: 255, blue: 0 },
+        Pixel { alpha: 255, red: 0, green: 0, blue: 255 }
+    ];
+    let palette = Quantizer::MedianCut.generate_palette(&pixels);
+    assert_eq!(Pixel { alpha: 0, red: 0, green: 0, blue: 0 }, palette[0]);
+    assert_eq!(pixels[2], palette[1]);
+    assert!(palette[2..].iter().all(|p| *p == pixels[2]));
+}
+
+#[test]
+pub fn quantize_ringhopper() {
+    let ringhopper = open_ringhopper_image();
+
+    let median_cut = palettized_error(&ringhopper, Quantizer::MedianCut.generate_palette(&ringhopper));
+    let k_means = palettized_error(&ringhopper, Quantizer::KMeans.generate_palette(&ringhopper));
+
+    let mean_squared_error = median_cut as f64 / (ringhopper.len() * 4) as f64;
+    assert!(mean_squared_error < 10.0, "median cut error too high: {mean_squared_error}");
+    assert!(k_means <= median_cut, "{k_means} > {median_cut}");
+}
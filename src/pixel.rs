@@ -1,3 +1,8 @@
+mod srgb;
+
+pub use self::srgb::ColorSpace;
+pub(crate) use self::srgb::{difference_squared, linear_to_srgb, srgb_to_linear};
+
 macro_rules! convert_channel {
     ($from_bits:expr, $to_bits:expr, $channel:expr) => {
         if $from_bits == 0 || $to_bits == 0 {
@@ -138,6 +143,47 @@ impl Pixel {
         [y8, a8]
     }
 
+    /// Load a pixel from 4-bit A4 (in the low 4 bits).
+    ///
+    /// Red, green, and blue channels will be set to 255.
+    pub const fn from_a4(a4: u8) -> Self {
+        Self::from_a8([convert_channel!(4, 8, a4 & 0xF)])
+    }
+
+    /// Encode the pixel as 4-bit A4 (in the low 4 bits).
+    pub const fn as_a4(self) -> u8 {
+        convert_channel!(8, 4, self.alpha)
+    }
+
+    /// Load a pixel from 4-bit Y4 (in the low 4 bits).
+    ///
+    /// The pixel will have 255 alpha.
+    pub const fn from_y4(y4: u8) -> Self {
+        Self::from_y8([convert_channel!(4, 8, y4 & 0xF)])
+    }
+
+    /// Encode the pixel as 4-bit Y4 (in the low 4 bits).
+    ///
+    /// This uses the same conversion to monochrome as [`as_y8()`](Pixel::as_y8).
+    pub const fn as_y4(self) -> u8 {
+        let [y8] = self.as_y8();
+        convert_channel!(8, 4, y8)
+    }
+
+    /// Load a pixel from 8-bit A4Y4 (alpha in the high 4 bits).
+    pub const fn from_a4y4(a4y4: [u8; 1]) -> Self {
+        let [a4y4] = a4y4;
+        Self {
+            alpha: Self::from_a4(a4y4 >> 4).alpha,
+            .. Self::from_y4(a4y4)
+        }
+    }
+
+    /// Encode the pixel as 8-bit A4Y4 (alpha in the high 4 bits).
+    pub const fn as_a4y4(self) -> [u8; 1] {
+        [self.as_a4() << 4 | self.as_y4()]
+    }
+
     /// Load a pixel from 16-bit R5G6B5 (little endian).
     ///
     /// The pixel will have 255 alpha.
@@ -222,10 +268,41 @@ impl Pixel {
         [self.red, self.green, self.blue, self.alpha]
     }
 
-    /// Returns distance squared between two pixels, also including alpha as its own channel.
-    pub(crate) const fn distance_argb(self, other: &Self) -> u32 {
-        let alpha_distance = self.alpha as i32 - other.alpha as i32;
-        (alpha_distance*alpha_distance) as u32 + self.distance_rgb(other)
+    /// Convert an sRGB pixel to linear light, as alpha, red, green, and blue from 0.0 to 1.0.
+    ///
+    /// Alpha is scaled without any conversion.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use macaroni_tex::Pixel;
+    ///
+    /// let pixel = Pixel { alpha: 255, red: 255, green: 188, blue: 0 };
+    /// let [alpha, red, green, blue] = pixel.to_linear();
+    /// assert_eq!((1.0, 1.0, 0.0), (alpha, red, blue));
+    /// assert!((green - 0.5).abs() < 0.01);
+    /// assert_eq!(pixel, Pixel::from_linear(pixel.to_linear()));
+    /// ```
+    pub fn to_linear(self) -> [f32; 4] {
+        [
+            self.alpha as f32 / 255.0,
+            srgb_to_linear(self.red),
+            srgb_to_linear(self.green),
+            srgb_to_linear(self.blue)
+        ]
+    }
+
+    /// Convert linear light alpha, red, green, and blue (0.0 to 1.0) to the nearest sRGB pixel.
+    ///
+    /// Alpha is scaled without any conversion, and values out of range are clamped.
+    pub fn from_linear(channels: [f32; 4]) -> Self {
+        let [alpha, red, green, blue] = channels;
+        Self {
+            alpha: (alpha * 255.0 + 0.5).clamp(0.0, 255.0) as u8,
+            red: linear_to_srgb(red),
+            green: linear_to_srgb(green),
+            blue: linear_to_srgb(blue)
+        }
     }
 
     /// Returns distance squared between two pixels, ignoring alpha.
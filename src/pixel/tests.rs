@@ -1,5 +1,4 @@
-use core::u16;
-use super::Pixel;
+use super::{linear_to_srgb, srgb_to_linear, Pixel};
 
 #[test]
 pub fn white_to_white() {
@@ -12,6 +11,9 @@ pub fn white_to_white() {
     assert_eq!(u16::MAX, u16::from_le_bytes(white.as_a4r4g4b4()));
     assert_eq!(u8::MAX, white.as_a8()[0]);
     assert_eq!(u8::MAX, white.as_y8()[0]);
+    assert_eq!(u8::MAX, white.as_a4y4()[0]);
+    assert_eq!(0xF, white.as_a4());
+    assert_eq!(0xF, white.as_y4());
 
     assert_eq!(white, Pixel::from_x8r8g8b8(u32::MAX.to_le_bytes()));
     assert_eq!(white, Pixel::from_a8r8g8b8(u32::MAX.to_le_bytes()));
@@ -20,6 +22,9 @@ pub fn white_to_white() {
     assert_eq!(white, Pixel::from_a4r4g4b4(u16::MAX.to_le_bytes()));
     assert_eq!(white, Pixel::from_a8([u8::MAX]));
     assert_eq!(white, Pixel::from_y8([u8::MAX]));
+    assert_eq!(white, Pixel::from_a4y4([u8::MAX]));
+    assert_eq!(white, Pixel::from_a4(0xF));
+    assert_eq!(white, Pixel::from_y4(0xF));
 }
 
 #[test]
@@ -72,3 +77,25 @@ pub fn round_trip() {
         assert_eq!(i, u16::from_le_bytes(Pixel::from_a4r4g4b4(i.to_le_bytes()).as_a4r4g4b4()));
     }
 }
+
+#[test]
+pub fn srgb_round_trip() {
+    for value in 0..=255u8 {
+        let pixel = Pixel { alpha: value, red: value, green: 255 - value, blue: value / 2 };
+        assert_eq!(pixel, Pixel::from_linear(pixel.to_linear()));
+    }
+
+    // Linear light values between two sRGB values go to the closer one (in sRGB).
+    for value in 0..255u8 {
+        let low = srgb_to_linear(value);
+        let high = srgb_to_linear(value + 1);
+        assert!(low < high);
+        assert_eq!(value, linear_to_srgb(low + (high - low) * 0.1));
+        assert_eq!(value + 1, linear_to_srgb(low + (high - low) * 0.9));
+    }
+
+    assert_eq!(0, linear_to_srgb(-1.0));
+    assert_eq!(255, linear_to_srgb(2.0));
+    assert_eq!(188, linear_to_srgb(0.5));
+    assert!((srgb_to_linear(128) - 0.215_861_1).abs() < 1e-6);
+}